@@ -0,0 +1,2345 @@
+use clap::{Parser, Subcommand};
+use ruzule_core::{
+    compress_cyan, diff_cyans, AppBundle, CyanCompression, CyanConfig, CyanConflict, CyanWriter,
+    Arch, EntitlementOps, AppReport, CancellationToken, Events, ExportFormat, Fs, HookStage,
+    InjectOptions, Injector, MinimumCheck, OptimizeOptions, PlistFormat, PlistMerge, Preset, RealFs,
+    Result, RuzuleError, SubstrateBackend, Version, create_ipa, install_app, AssumeYes, NoInput,
+    Prompter,
+};
+use ruzule_core::buffer::LinkKind;
+use ruzule_core::plist_ext::is_valid_url_scheme;
+use sha2::{Sha256, Digest};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tempfile::TempDir;
+use uuid::Uuid;
+
+#[derive(Parser, Debug)]
+#[command(name = "ruzule")]
+#[command(about = "iOS app injector and modifier - Rust rewrite of pyzule-rw/cyan")]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Only print notices, warnings and errors
+    #[arg(long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also print every binary edit
+    #[arg(long, global = true)]
+    verbose: bool,
+
+    /// Answer yes to every confirmation, such as replacing an existing output
+    #[arg(short = 'y', long, global = true, conflicts_with = "no_input")]
+    yes: bool,
+
+    /// Never ask for confirmation; fail instead of waiting for an answer
+    #[arg(long, global = true)]
+    no_input: bool,
+
+    /// Print a JSON summary of the run on stdout when it's done, and progress on stderr
+    #[arg(long, conflicts_with = "dry_run")]
+    json: bool,
+
+    // Default inject command args (when no subcommand is specified)
+    /// The app to be modified (.app/.ipa/.tipa, or - to read it from stdin)
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+
+    /// What `-i -` reads from stdin
+    #[arg(long, value_name = "FORMAT", default_value = "ipa", value_parser = ["ipa", "tipa"])]
+    input_format: String,
+
+    /// Output path (if unspecified, overwrites input; - writes the ipa to stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// The .cyan file(s) to use
+    #[arg(short = 'z', long = "cyan")]
+    cyan: Option<Vec<PathBuf>>,
+
+    /// A preset (name or .toml path) to start from; other flags take precedence
+    #[arg(long)]
+    preset: Option<String>,
+
+    /// How to resolve an option set by more than one .cyan (last, first, error)
+    #[arg(long, default_value = "last")]
+    cyan_conflict: CyanConflict,
+
+    /// Define a variable for ${VAR} placeholders in .cyan files (KEY=VALUE)
+    #[arg(short = 'D', long = "define", value_name = "KEY=VALUE", value_parser = parse_define)]
+    defines: Vec<(String, String)>,
+
+    /// Tweaks/files to inject
+    #[arg(short = 'f')]
+    files: Option<Vec<PathBuf>>,
+
+    /// An .lproj, or a directory of them, to add to the app's localizations
+    #[arg(long, value_name = "DIR")]
+    localizations: Vec<PathBuf>,
+
+    /// Modify the app's name
+    #[arg(short = 'n')]
+    name: Option<String>,
+
+    /// Modify the app's version
+    #[arg(short = 'v')]
+    version: Option<String>,
+
+    /// Modify the app's bundle id
+    #[arg(short = 'b')]
+    bundle_id: Option<String>,
+
+    /// Modify the app's minimum OS version
+    #[arg(short = 'm')]
+    minimum: Option<String>,
+
+    /// Refuse tweaks that require a newer OS than the app's minimum
+    #[arg(long, conflicts_with = "bump_minimum")]
+    strict: bool,
+
+    /// Raise the app's minimum OS to what the injected tweaks require
+    #[arg(long)]
+    bump_minimum: bool,
+
+    /// Like --bump-minimum, also counting bundled frameworks and updating the main binary
+    #[arg(long, conflicts_with_all = ["minimum", "strict", "bump_minimum"])]
+    auto_minimum: bool,
+
+    /// Modify the app's icon
+    #[arg(short = 'k')]
+    icon: Option<PathBuf>,
+
+    /// Add an alternate icon the app can switch to (NAME=IMAGE)
+    #[arg(long = "alt-icon", value_name = "NAME=IMAGE", value_parser = parse_alternate_icon)]
+    alternate_icons: Vec<(String, PathBuf)>,
+
+    /// A plist to merge with the app's Info.plist
+    #[arg(short = 'l')]
+    plist: Option<PathBuf>,
+
+    /// How -l is merged: replace top-level keys, deep-merge dictionaries, or
+    /// also append to arrays (replace, deep, append)
+    #[arg(long, value_name = "MODE")]
+    plist_merge: Option<PlistMerge>,
+
+    /// Write Info.plist as xml or binary (default: the format it had)
+    #[arg(long, value_name = "FORMAT")]
+    plist_format: Option<PlistFormat>,
+
+    /// Delete an Info.plist key, or a dotted path like A.B into a dictionary (repeatable)
+    #[arg(long = "plist-remove", value_name = "KEY")]
+    remove_plist_keys: Vec<String>,
+
+    /// Add or modify entitlements to the main binary
+    #[arg(short = 'x')]
+    entitlements: Option<PathBuf>,
+
+    /// Remove UISupportedDevices
+    #[arg(short = 'u', long)]
+    remove_supported_devices: bool,
+
+    /// Register a URL scheme the app opens, e.g. a tweak's callback scheme (repeatable)
+    #[arg(long = "add-url-scheme", value_name = "SCHEME")]
+    add_url_schemes: Vec<String>,
+
+    /// Unregister one of the app's URL schemes (repeatable)
+    #[arg(long = "remove-url-scheme", value_name = "SCHEME")]
+    remove_url_schemes: Vec<String>,
+
+    /// Remove all watch apps
+    #[arg(short = 'w', long)]
+    no_watch: bool,
+
+    /// Modify the watch app in Watch/ instead of the app itself
+    #[arg(long, conflicts_with = "no_watch")]
+    watch_app: bool,
+
+    /// Strip watch apps down to a placeholder instead of removing them
+    #[arg(long, conflicts_with_all = ["no_watch", "watch_app"])]
+    stub_watch: bool,
+
+    /// Delete Frameworks/<NAME>.framework and weak-link it (repeatable)
+    #[arg(long = "remove-framework", value_name = "NAME")]
+    remove_frameworks: Vec<String>,
+
+    /// Unlink and delete a previously injected tweak, by file name or load path (repeatable)
+    #[arg(long, value_name = "NAME")]
+    uninject: Vec<String>,
+
+    /// Delete an rpath from the main binary before injecting, every copy of it (repeatable)
+    #[arg(long = "remove-rpath", value_name = "RPATH")]
+    remove_rpaths: Vec<String>,
+
+    /// Enable documents support
+    #[arg(short = 'd', long)]
+    enable_documents: bool,
+
+    /// Fakesign all binaries for use with appsync/trollstore
+    #[arg(short = 's', long)]
+    fakesign: bool,
+
+    /// Sign with this developer certificate (.p12) for sideloading
+    #[arg(long, value_name = "P12")]
+    cert: Option<PathBuf>,
+
+    /// Password of --cert (default: $RUZULE_P12_PASSWORD, or empty)
+    #[arg(long, value_name = "PASSWORD", requires = "cert")]
+    cert_password: Option<String>,
+
+    /// Provisioning profile to embed when signing with --cert
+    #[arg(long, value_name = "MOBILEPROVISION", requires = "cert")]
+    profile: Option<PathBuf>,
+
+    /// Remove an entitlement from the main binary, e.g. aps-environment (repeatable)
+    #[arg(long = "remove-entitlement", value_name = "KEY")]
+    remove_entitlements: Vec<String>,
+
+    /// Remove entitlements ad-hoc signing can't honor (push, associated domains, iCloud)
+    #[arg(long)]
+    strip_risky_entitlements: bool,
+
+    /// Thin all binaries to arm64
+    #[arg(short = 'q', long)]
+    thin: bool,
+
+    /// With --thin, the architecture to keep instead of arm64 (arm64, arm64e, x86_64)
+    #[arg(long, requires = "thin")]
+    arch: Option<Arch>,
+
+    /// With --thin, leave injected tweaks and frameworks fat
+    #[arg(long, requires = "thin")]
+    no_thin_tweaks: bool,
+
+    /// Remove all app extensions
+    #[arg(short = 'e', long)]
+    remove_extensions: bool,
+
+    /// Only remove encrypted app extensions
+    #[arg(short = 'g', long)]
+    remove_encrypted: bool,
+
+    /// The compression level of the ipa (0-9, defaults to 6)
+    #[arg(short = 'c', long, default_value = "6", value_parser = clap::value_parser!(u32).range(0..=9))]
+    compress: u32,
+
+    /// Threads for tweaks, signing, thinning and packing (defaults to one per core)
+    #[arg(short = 'j', long, value_parser = clap::value_parser!(u64).range(1..))]
+    jobs: Option<u64>,
+
+    /// Skip main binary encryption check
+    #[arg(long)]
+    ignore_encrypted: bool,
+
+    /// Keep the working directory after the run (it's always kept when a run fails)
+    #[arg(long)]
+    keep_temp: bool,
+
+    /// Continue an interrupted run of the same command from its last finished phase
+    #[arg(long)]
+    resume: bool,
+
+    /// Package the output as just the ipa, or for on-device signers (ipa, esign)
+    #[arg(long, value_name = "FORMAT", default_value = "ipa")]
+    export: ExportFormat,
+
+    /// A .p12 to include in an esign export
+    #[arg(long, value_name = "P12")]
+    export_cert: Option<PathBuf>,
+
+    /// A .mobileprovision to include in an esign export
+    #[arg(long, value_name = "PROFILE")]
+    export_profile: Option<PathBuf>,
+
+    /// Install the output with the sideload command once it's written
+    #[arg(long)]
+    sideload: bool,
+
+    /// Sideloading command, with {ipa} and {udid} placeholders
+    /// (default: $RUZULE_SIDELOAD_COMMAND)
+    #[arg(long, value_name = "COMMAND")]
+    sideload_command: Option<String>,
+
+    /// Device to sideload to, for {udid} (default: $RUZULE_UDID)
+    #[arg(long)]
+    udid: Option<String>,
+
+    /// Overwrite existing files without confirming
+    #[arg(long)]
+    overwrite: bool,
+
+    /// List what would be changed without writing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Place dylibs in Frameworks/ with @rpath instead of app root with @executable_path
+    #[arg(long)]
+    use_frameworks_dir: bool,
+
+    /// Link tweaks from this binary instead of the main one, relative to the app
+    /// (e.g. Frameworks/SDK.framework/SDK)
+    #[arg(long, value_name = "BINARY")]
+    inject_into: Option<String>,
+
+    /// Patch plugins to fix share sheet, widgets, VPNs, etc.
+    #[arg(short = 'p', long)]
+    patch_plugins: bool,
+
+    /// Custom dylib to inject instead of the bundled zxPluginsInject (implies -p)
+    #[arg(long, value_name = "DYLIB")]
+    patch_plugins_dylib: Option<PathBuf>,
+
+    /// Hooking library for substrate dependencies (substrate, ellekit, libhooker)
+    #[arg(long, alias = "rewrite-substrate", default_value = "substrate")]
+    substrate_backend: SubstrateBackend,
+
+    /// Directory with Swift compatibility dylibs (libswift*.dylib) to bundle when needed
+    #[arg(long, value_name = "DIR")]
+    swift_libs: Option<PathBuf>,
+
+    /// Run a command with the unpacked .app path as its last argument
+    /// (stages: post-extract, post-inject, pre-sign, pre-pack)
+    #[arg(long = "hook", value_name = "STAGE=COMMAND", value_parser = parse_hook)]
+    hooks: Vec<(HookStage, String)>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Generate a .cyan configuration file
+    Cgen {
+        /// Output path for the .cyan file
+        #[arg(short, long, required = true)]
+        output: PathBuf,
+
+        /// Tweaks/files to inject
+        #[arg(short = 'f')]
+        files: Option<Vec<PathBuf>>,
+
+        /// An .lproj, or a directory of them, to add to the app's localizations
+        #[arg(long, value_name = "DIR")]
+        localizations: Vec<PathBuf>,
+
+        /// Modify the app's name
+        #[arg(short = 'n')]
+        name: Option<String>,
+
+        /// Modify the app's version
+        #[arg(short = 'v')]
+        version: Option<String>,
+
+        /// Modify the app's bundle id
+        #[arg(short = 'b')]
+        bundle_id: Option<String>,
+
+        /// Modify the app's minimum OS version
+        #[arg(short = 'm')]
+        minimum: Option<String>,
+
+        /// Modify the app's icon
+        #[arg(short = 'k')]
+        icon: Option<PathBuf>,
+
+        /// Add an alternate icon the app can switch to (NAME=IMAGE)
+        #[arg(long = "alt-icon", value_name = "NAME=IMAGE", value_parser = parse_alternate_icon)]
+        alternate_icons: Vec<(String, PathBuf)>,
+
+        /// A plist to merge with the app's Info.plist
+        #[arg(short = 'l')]
+        plist: Option<PathBuf>,
+
+        /// How -l is merged: replace top-level keys, deep-merge dictionaries, or
+        /// also append to arrays (replace, deep, append)
+        #[arg(long, value_name = "MODE", requires = "plist")]
+        plist_merge: Option<PlistMerge>,
+
+        /// Add or modify entitlements to the main binary
+        #[arg(short = 'x')]
+        entitlements: Option<PathBuf>,
+
+        /// JSON file of entitlement edits keyed by binary path relative to the app
+        #[arg(long)]
+        binary_entitlements: Option<PathBuf>,
+
+        /// Remove UISupportedDevices
+        #[arg(short = 'u', long)]
+        remove_supported_devices: bool,
+
+        /// Remove all watch apps
+        #[arg(short = 'w', long)]
+        no_watch: bool,
+
+        /// Enable documents support
+        #[arg(short = 'd', long)]
+        enable_documents: bool,
+
+        /// Fakesign all binaries for use with appsync/trollstore
+        #[arg(short = 's', long)]
+        fakesign: bool,
+
+        /// Thin all binaries to arm64
+        #[arg(short = 'q', long)]
+        thin: bool,
+
+        /// Remove all app extensions
+        #[arg(short = 'e', long)]
+        remove_extensions: bool,
+
+        /// Only remove encrypted app extensions
+        #[arg(short = 'g', long)]
+        remove_encrypted: bool,
+
+        /// Patch plugins to fix share sheet, widgets, VPNs, etc.
+        #[arg(short = 'p', long)]
+        patch_plugins: bool,
+
+        /// Compression for the .cyan members (0-9 for deflate, or zstd; defaults to 1,
+        /// or 0 when writing a .cyan.zst)
+        #[arg(long)]
+        cyan_compress: Option<CyanCompression>,
+
+        /// Store identical files only once
+        #[arg(long)]
+        dedup: bool,
+
+        /// Overwrite existing files without confirming
+        #[arg(long)]
+        overwrite: bool,
+    },
+
+    /// Compare two .cyan files (config fields and payload hashes)
+    Cdiff {
+        /// The original .cyan
+        old: PathBuf,
+
+        /// The updated .cyan
+        new: PathBuf,
+    },
+
+    /// Combine thin binaries (e.g. arm64 and arm64e builds of a tweak) into a fat one
+    Lipo {
+        /// The binaries to combine; a fat one adds all of its slices
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+
+        /// Where to write the fat binary
+        #[arg(short, long, required = true)]
+        output: PathBuf,
+
+        /// Overwrite existing files without confirming
+        #[arg(long)]
+        overwrite: bool,
+    },
+
+    /// Show the bundled frameworks with their versions and hashes
+    Frameworks {
+        #[command(subcommand)]
+        action: Option<FrameworksAction>,
+    },
+
+    /// Show or clear ruzule's download cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
+    /// List the code signing identities in the keychain and identities directory
+    Identities {
+        /// Directory of .p12 files (default: the identities directory)
+        #[arg(long)]
+        dir: Option<PathBuf>,
+
+        /// Print the identities as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Make an app smaller without changing what it does
+    Optimize {
+        /// The app to optimize (.ipa/.tipa/.app)
+        #[arg(short, long, required = true)]
+        input: PathBuf,
+
+        /// Where to write the optimized .ipa
+        #[arg(short, long, required = true)]
+        output: PathBuf,
+
+        /// Languages to keep (e.g. en,fr); the other .lproj directories are removed
+        #[arg(long, value_name = "LANG", value_delimiter = ',')]
+        keep_locale: Vec<String>,
+
+        /// Don't thin binaries to arm64
+        #[arg(long)]
+        no_thin: bool,
+
+        /// Don't remove embedded bitcode
+        #[arg(long)]
+        no_bitcode: bool,
+
+        /// Don't strip debug and local symbols
+        #[arg(long)]
+        no_strip: bool,
+
+        /// Don't recompress PNGs
+        #[arg(long)]
+        no_png: bool,
+
+        /// Don't remove extensions' copies of the app's frameworks
+        #[arg(long)]
+        no_dedupe: bool,
+
+        /// The compression level of the ipa (0-9)
+        #[arg(short = 'c', long, default_value = "9", value_parser = clap::value_parser!(u32).range(0..=9))]
+        compress: u32,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List the permissions, entitlements and network exceptions an app declares
+    Report {
+        /// The app to inspect (.ipa/.tipa/.app)
+        #[arg(short, long, required = true)]
+        input: PathBuf,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List the libraries and rpaths an app's binaries link, like `otool -L`
+    Deps {
+        /// The app to inspect (.ipa/.tipa/.app)
+        #[arg(short, long, required = true)]
+        input: PathBuf,
+
+        /// Also list every dylib, framework and extension, not just the main binary
+        #[arg(long)]
+        all: bool,
+
+        /// Print the list as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List or apply presets from the presets directory
+    Preset {
+        #[command(subcommand)]
+        action: PresetAction,
+    },
+
+    /// Serve a line-delimited JSON protocol on stdin/stdout for GUI frontends
+    Daemon,
+
+    /// Time each stage of a run (fakesigning and thinning included) under different settings
+    Bench {
+        /// The app to run on (.ipa/.tipa/.app); it isn't modified
+        #[arg(short, long, required = true)]
+        input: PathBuf,
+
+        /// Tweaks/files to inject
+        #[arg(short = 'f')]
+        files: Option<Vec<PathBuf>>,
+
+        /// .cyan file(s) to apply
+        #[arg(short = 'z', long = "cyan")]
+        cyan: Option<Vec<PathBuf>>,
+
+        /// Runs per setting; the table shows the median
+        #[arg(long, default_value = "3", value_parser = clap::value_parser!(u64).range(1..))]
+        runs: u64,
+
+        /// Thread counts to compare (defaults to 1 and one per core)
+        #[arg(short = 'j', long, value_delimiter = ',')]
+        jobs: Vec<usize>,
+
+        /// Compression levels to compare
+        #[arg(
+            short = 'c',
+            long,
+            value_delimiter = ',',
+            default_value = "6",
+            value_parser = clap::value_parser!(u32).range(0..=9)
+        )]
+        compress: Vec<u32>,
+    },
+
+    /// Duplicate an app with a new bundle ID (allows installing multiple copies)
+    Dupe {
+        /// Input app to duplicate (.ipa/.tipa/.app)
+        #[arg(short, long, required = true)]
+        input: PathBuf,
+
+        /// Output path for the duplicate (.ipa or .app)
+        #[arg(short, long, required = true)]
+        output: PathBuf,
+
+        /// A seed to derive the team ID from (any string, save it for related apps)
+        #[arg(short, long)]
+        seed: Option<String>,
+
+        /// Bundle suffix to use (10 hex chars, see README)
+        #[arg(short, long)]
+        bundle: Option<String>,
+
+        /// Overwrite existing files without confirming
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Keep app extensions, moving them to the new bundle and team id
+        #[arg(long)]
+        keep_extensions: bool,
+
+        /// Derive the bundle suffix from the seed and the app's bundle id, so
+        /// re-running on an update gives the same identifier
+        #[arg(long, conflicts_with = "bundle")]
+        stable: bool,
+
+        /// Make this many copies (out-1.ipa, out-2.ipa, ...) sharing the team id
+        #[arg(long, default_value = "1", value_parser = clap::value_parser!(u64).range(1..))]
+        count: u64,
+
+        /// Keep URL schemes, renamed to <scheme>-<SUFFIX>, instead of removing them
+        #[arg(long, value_name = "SUFFIX")]
+        url_scheme_suffix: Option<String>,
+
+        /// Register a URL scheme in the duplicate (repeatable)
+        #[arg(long = "add-url-scheme", value_name = "SCHEME")]
+        add_url_schemes: Vec<String>,
+
+        /// Tweaks/files to inject into the duplicate
+        #[arg(short = 'f')]
+        files: Option<Vec<PathBuf>>,
+
+        /// .cyan file(s) to apply to the duplicate
+        #[arg(short = 'z', long = "cyan")]
+        cyan: Option<Vec<PathBuf>>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum FrameworksAction {
+    /// Download newer builds of the bundled frameworks, if any
+    Update {
+        /// Only report what's newer, without downloading
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// Show how much space each part of the cache takes
+    Stats,
+
+    /// Delete cached files; they're downloaded again when needed
+    Clean {
+        /// Only delete files not modified for this long (e.g. 30d, 12h, 2w)
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        older_than: Option<Duration>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PresetAction {
+    /// Show the available presets
+    List,
+
+    /// Modify an app with only a preset's options
+    Apply {
+        /// Preset name, or path to a .toml file
+        name: String,
+
+        /// The app to be modified (.app/.ipa/.tipa)
+        #[arg(short, long, required = true)]
+        input: PathBuf,
+
+        /// Output path (if unspecified, overwrites input; - writes the ipa to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Overwrite existing files without confirming
+        #[arg(long)]
+        overwrite: bool,
+
+        /// List what would be changed without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Prints log records (including library events) in the usual `[*]`/`[?]`/`[!]` style,
+/// to stdout unless it carries the output (`-o -`)
+struct CliLogger {
+    stderr: AtomicBool,
+    /// A progress line is on the terminal, to be replaced by whatever comes next
+    progress: AtomicBool,
+}
+
+static LOGGER: CliLogger = CliLogger {
+    stderr: AtomicBool::new(false),
+    progress: AtomicBool::new(false),
+};
+
+/// Asks on stdout and reads the answer from stdin; an empty answer is yes,
+/// a closed stdin is no
+struct TerminalPrompter;
+
+impl Prompter for TerminalPrompter {
+    fn confirm(&self, question: &str) -> Result<bool> {
+        print!("[<] {} [Y/n] ", question);
+        std::io::stdout().flush()?;
+
+        let mut response = String::new();
+        if std::io::stdin().read_line(&mut response)? == 0 {
+            println!();
+            return Ok(false);
+        }
+        let response = response.trim().to_lowercase();
+        Ok(matches!(response.as_str(), "y" | "yes" | ""))
+    }
+}
+
+/// Answers confirmations, per --yes and --no-input
+static PROMPTER: OnceLock<Box<dyn Prompter>> = OnceLock::new();
+
+fn confirm(question: &str) -> Result<bool> {
+    match PROMPTER.get() {
+        Some(prompter) => prompter.confirm(question),
+        None => TerminalPrompter.confirm(question),
+    }
+}
+
+impl CliLogger {
+    fn print(&self, line: std::fmt::Arguments) {
+        if self.stderr.load(Ordering::Relaxed) {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+impl log::Log for CliLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // Only our own debug output, not that of dependencies
+        metadata.level() <= log::Level::Info || metadata.target().starts_with("ruzule")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // Redrawn in place on a terminal, left out when stderr is a file or pipe
+        if record.target() == "ruzule::progress" {
+            if std::io::stderr().is_terminal() {
+                eprint!("\r\x1b[K[*] {}", record.args());
+                self.progress.store(true, Ordering::Relaxed);
+            }
+            return;
+        }
+        if self.progress.swap(false, Ordering::Relaxed) {
+            eprint!("\r\x1b[K");
+        }
+
+        let prefix = match record.level() {
+            log::Level::Error => "[!]",
+            log::Level::Warn => "[?]",
+            log::Level::Info => "[*]",
+            log::Level::Debug | log::Level::Trace => "[.]",
+        };
+        self.print(format_args!("{} {}", prefix, record.args()));
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+fn parse_define(s: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid define \"{}\" (expected KEY=VALUE)", s))?;
+    if key.is_empty() {
+        return Err(format!("invalid define \"{}\" (empty key)", s));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn parse_alternate_icon(s: &str) -> std::result::Result<(String, PathBuf), String> {
+    let (name, image) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid alternate icon \"{}\" (expected NAME=IMAGE)", s))?;
+    if name.is_empty() || name.contains('/') {
+        return Err(format!("invalid alternate icon name \"{}\"", name));
+    }
+    Ok((name.to_string(), PathBuf::from(image)))
+}
+
+/// `files` plus the .lproj directories in `localizations`; each is an .lproj
+/// itself or a translation pack holding several
+fn with_localizations(
+    files: Option<Vec<PathBuf>>,
+    localizations: Vec<PathBuf>,
+) -> Result<Option<Vec<PathBuf>>> {
+    if localizations.is_empty() {
+        return Ok(files);
+    }
+
+    let mut files = files.unwrap_or_default();
+    for dir in localizations {
+        if !dir.is_dir() {
+            return Err(RuzuleError::FileNotFound(dir));
+        }
+        if dir.extension() == Some("lproj".as_ref()) {
+            files.push(dir);
+            continue;
+        }
+        let mut found: Vec<PathBuf> = fs::read_dir(&dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        found.retain(|p| p.is_dir() && p.extension() == Some("lproj".as_ref()));
+        if found.is_empty() {
+            return Err(RuzuleError::InvalidInput(format!(
+                "no .lproj directories in {}",
+                dir.display()
+            )));
+        }
+        found.sort();
+        files.extend(found);
+    }
+    Ok(Some(files))
+}
+
+/// `<n>s`, `<n>m`, `<n>h`, `<n>d` or `<n>w`
+fn parse_age(s: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("invalid age \"{}\" (expected e.g. 30d, 12h or 2w)", s);
+    let split = s.len().checked_sub(1).filter(|&i| s.is_char_boundary(i)).ok_or_else(invalid)?;
+    let (n, unit) = s.split_at(split);
+    let n: u64 = n.parse().map_err(|_| invalid())?;
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    n.checked_mul(secs).map(Duration::from_secs).ok_or_else(invalid)
+}
+
+fn parse_hook(s: &str) -> std::result::Result<(HookStage, String), String> {
+    let (stage, command) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid hook \"{}\" (expected STAGE=COMMAND)", s))?;
+    if command.trim().is_empty() {
+        return Err(format!("invalid hook \"{}\" (empty command)", s));
+    }
+    Ok((stage.parse()?, command.to_string()))
+}
+
+/// Run a `--hook` command through the shell, passing the app path as an argument
+fn run_hook_command(command: &str, stage: HookStage, app: &Path) -> Result<()> {
+    #[cfg(unix)]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(format!("{} \"$@\"", command)).arg("sh").arg(app);
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(format!("{} \"{}\"", command, app.display()));
+        cmd
+    };
+
+    log::debug!("running {} hook: {}", stage, command);
+    let status = cmd
+        .env("RUZULE_APP", app)
+        .env("RUZULE_STAGE", stage.as_str())
+        .status()?;
+    if !status.success() {
+        return Err(RuzuleError::ToolFailed(format!("`{}` exited with {}", command, status)));
+    }
+    Ok(())
+}
+
+/// The sideload command with `{udid}` filled in, checked before the run so a
+/// missing command or device doesn't surface only after the app is written
+fn sideload_command(
+    command: Option<String>,
+    udid: Option<String>,
+    injector: &Injector,
+) -> Result<String> {
+    let command = command
+        .or_else(|| std::env::var("RUZULE_SIDELOAD_COMMAND").ok())
+        .filter(|c| !c.trim().is_empty())
+        .ok_or_else(|| {
+            RuzuleError::InvalidInput(
+                "--sideload needs a command (--sideload-command or RUZULE_SIDELOAD_COMMAND)"
+                    .to_string(),
+            )
+        })?;
+    let output = injector.output_path();
+    if output == Path::new("-") || output.extension().is_some_and(|e| e == "app") {
+        return Err(RuzuleError::InvalidInput(
+            "--sideload needs an .ipa or .tipa output".to_string(),
+        ));
+    }
+
+    if !command.contains("{udid}") {
+        return Ok(command);
+    }
+    let udid = udid.or_else(|| std::env::var("RUZULE_UDID").ok()).ok_or_else(|| {
+        RuzuleError::InvalidInput(
+            "the sideload command uses {udid}; pass --udid or set RUZULE_UDID".to_string(),
+        )
+    })?;
+    Ok(command.replace("{udid}", &shell_quote(&udid)))
+}
+
+/// Hand the written .ipa to the sideload command, `{ipa}` replaced by its path
+fn run_sideload(command: &str, ipa: &Path) -> Result<()> {
+    let command = command.replace("{ipa}", &shell_quote(&ipa.to_string_lossy()));
+
+    #[cfg(unix)]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(&command);
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(&command);
+        cmd
+    };
+
+    log::info!("sideloading: {}", command);
+    let status = cmd.env("RUZULE_IPA", ipa).status()?;
+    if !status.success() {
+        return Err(RuzuleError::ToolFailed(format!(
+            "sideloading failed: `{}` exited with {}",
+            command, status
+        )));
+    }
+    log::info!("sideloaded \x1b[96m{}\x1b[0m", ipa.display());
+    Ok(())
+}
+
+/// Quote a value for the shell the sideload command runs in
+fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+fn main() {
+    match run() {
+        Ok(()) => {}
+        Err(RuzuleError::Cancelled) => {
+            log::warn!("cancelled.");
+            std::process::exit(130);
+        }
+        Err(e) => {
+            eprintln!("[!] {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Cancel the running pipeline on Ctrl-C; a second Ctrl-C exits immediately
+fn cancel_on_ctrlc() -> CancellationToken {
+    let token = CancellationToken::new();
+    let handler_token = token.clone();
+    let _ = ctrlc::set_handler(move || {
+        if handler_token.is_cancelled() {
+            std::process::exit(130);
+        }
+        log::warn!("cancelling, press Ctrl-C again to quit immediately");
+        handler_token.cancel();
+    });
+    token
+}
+
+fn run() -> Result<()> {
+    let cli = Cli::parse();
+
+    let level = if cli.quiet {
+        log::LevelFilter::Warn
+    } else if cli.verbose {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+    let prompter: Box<dyn Prompter> = if cli.yes {
+        Box::new(AssumeYes)
+    } else if cli.no_input || cli.input.as_deref() == Some(Path::new("-")) {
+        // An input read from stdin leaves nothing there to answer with
+        Box::new(NoInput)
+    } else {
+        Box::new(TerminalPrompter)
+    };
+    let _ = PROMPTER.set(prompter);
+
+    match cli.command {
+        Some(Commands::Cgen {
+            output,
+            files,
+            localizations,
+            name,
+            version,
+            bundle_id,
+            minimum,
+            icon,
+            alternate_icons,
+            plist,
+            plist_merge,
+            entitlements,
+            binary_entitlements,
+            remove_supported_devices,
+            no_watch,
+            enable_documents,
+            fakesign,
+            thin,
+            remove_extensions,
+            remove_encrypted,
+            patch_plugins,
+            cyan_compress,
+            dedup,
+            overwrite,
+        }) => {
+            run_cgen(
+                output,
+                with_localizations(files, localizations)?,
+                name,
+                version,
+                bundle_id,
+                minimum,
+                icon,
+                alternate_icons,
+                plist,
+                plist_merge,
+                entitlements,
+                binary_entitlements,
+                remove_supported_devices,
+                no_watch,
+                enable_documents,
+                fakesign,
+                thin,
+                remove_extensions,
+                remove_encrypted,
+                patch_plugins,
+                cyan_compress,
+                dedup,
+                overwrite,
+            )
+        }
+        Some(Commands::Cdiff { old, new }) => run_cdiff(old, new),
+        Some(Commands::Lipo {
+            inputs,
+            output,
+            overwrite,
+        }) => run_lipo(inputs, output, overwrite),
+        Some(Commands::Frameworks { action: None }) => run_frameworks(),
+        Some(Commands::Frameworks {
+            action: Some(FrameworksAction::Update { check }),
+        }) => run_frameworks_update(check),
+        Some(Commands::Cache { action: CacheAction::Stats }) => run_cache_stats(),
+        Some(Commands::Cache {
+            action: CacheAction::Clean { older_than },
+        }) => {
+            let cleaned = ruzule_core::cache::clean(older_than)?;
+            log::info!(
+                "removed {} file(s), {}",
+                cleaned.files,
+                format_size(cleaned.bytes)
+            );
+            Ok(())
+        }
+        Some(Commands::Identities { dir, json }) => run_identities(dir, json),
+        Some(Commands::Optimize {
+            input,
+            output,
+            keep_locale,
+            no_thin,
+            no_bitcode,
+            no_strip,
+            no_png,
+            no_dedupe,
+            compress,
+            json,
+        }) => {
+            let options = OptimizeOptions {
+                thin: !no_thin,
+                strip_bitcode: !no_bitcode,
+                strip_symbols: !no_strip,
+                keep_localizations: keep_locale,
+                recompress_png: !no_png,
+                dedupe_frameworks: !no_dedupe,
+                compress,
+                jobs: None,
+            };
+            run_optimize(input, output, options, json)
+        }
+        Some(Commands::Report { input, json }) => run_report(input, json),
+        Some(Commands::Deps { input, all, json }) => run_deps(input, all, json),
+        Some(Commands::Preset { action: PresetAction::List }) => run_preset_list(),
+        Some(Commands::Preset {
+            action:
+                PresetAction::Apply {
+                    name,
+                    input,
+                    output,
+                    overwrite,
+                    dry_run,
+                },
+        }) => {
+            let options = Preset::load(&name)?.merge(InjectOptions {
+                output,
+                ..Default::default()
+            });
+            let injector = Injector::with_options(input, options).cancel_token(cancel_on_ctrlc());
+            if dry_run {
+                run_dry(injector)
+            } else {
+                run_inject(injector, overwrite, false).map(|_| ())
+            }
+        }
+        Some(Commands::Daemon) => {
+            // stdout carries the protocol; progress goes out as events instead
+            log::set_max_level(log::LevelFilter::Off);
+            ruzule_core::daemon::serve(std::io::stdin().lock(), std::io::stdout())
+        }
+        Some(Commands::Bench {
+            input,
+            files,
+            cyan,
+            runs,
+            jobs,
+            compress,
+        }) => run_bench(
+            input,
+            files.unwrap_or_default(),
+            cyan.unwrap_or_default(),
+            runs as usize,
+            jobs,
+            compress,
+        ),
+        Some(Commands::Dupe {
+            input,
+            output,
+            seed,
+            bundle,
+            overwrite,
+            keep_extensions,
+            stable,
+            count,
+            url_scheme_suffix,
+            add_url_schemes,
+            files,
+            cyan,
+        }) => run_dupe(
+            input,
+            output,
+            seed,
+            bundle,
+            stable,
+            count as usize,
+            files.unwrap_or_default(),
+            cyan.unwrap_or_default(),
+            overwrite,
+            keep_extensions,
+            url_scheme_suffix,
+            add_url_schemes,
+            cancel_on_ctrlc(),
+        ),
+        None => {
+            // Default inject behavior
+            let input = cli.input.ok_or_else(|| {
+                RuzuleError::InvalidInput("Input is required".to_string())
+            })?;
+            // Lives until the run is over
+            let stdin_dir;
+            let input = if input == Path::new("-") {
+                if cli.output.is_none() {
+                    return Err(RuzuleError::InvalidInput(
+                        "-o is required when the input is read from stdin".to_string(),
+                    ));
+                }
+                stdin_dir = TempDir::new()?;
+                spool_stdin(stdin_dir.path(), &cli.input_format)?
+            } else {
+                input
+            };
+            let options = InjectOptions {
+                output: cli.output,
+                cyans: cli.cyan.unwrap_or_default(),
+                cyan_conflict: cli.cyan_conflict,
+                defines: cli.defines.into_iter().collect(),
+                files: with_localizations(cli.files, cli.localizations)?.unwrap_or_default(),
+                name: cli.name,
+                version: cli.version,
+                bundle_id: cli.bundle_id,
+                minimum: cli.minimum,
+                minimum_check: if cli.strict {
+                    MinimumCheck::Strict
+                } else if cli.bump_minimum {
+                    MinimumCheck::Bump
+                } else {
+                    MinimumCheck::Warn
+                },
+                auto_minimum: cli.auto_minimum,
+                icon: cli.icon,
+                alternate_icons: cli.alternate_icons.into_iter().collect(),
+                plist: cli.plist,
+                plist_merge: cli.plist_merge,
+                plist_format: cli.plist_format,
+                remove_plist_keys: cli.remove_plist_keys,
+                entitlements: cli.entitlements,
+                remove_supported_devices: cli.remove_supported_devices,
+                add_url_schemes: cli.add_url_schemes,
+                remove_url_schemes: cli.remove_url_schemes,
+                no_watch: cli.no_watch,
+                watch_app: cli.watch_app,
+                stub_watch: cli.stub_watch,
+                remove_frameworks: cli.remove_frameworks,
+                uninject: cli.uninject,
+                remove_rpaths: cli.remove_rpaths,
+                enable_documents: cli.enable_documents,
+                fakesign: cli.fakesign,
+                certificate: cli.cert,
+                certificate_password: cli.cert_password,
+                profile: cli.profile,
+                remove_entitlements: cli.remove_entitlements,
+                strip_risky_entitlements: cli.strip_risky_entitlements,
+                thin: cli.thin,
+                arch: cli.arch,
+                thin_tweaks: cli.no_thin_tweaks.then_some(false),
+                remove_extensions: cli.remove_extensions,
+                remove_encrypted: cli.remove_encrypted,
+                compress: cli.compress,
+                jobs: cli.jobs.map(|n| n as usize),
+                ignore_encrypted: cli.ignore_encrypted,
+                use_frameworks_dir: cli.use_frameworks_dir,
+                inject_into: cli.inject_into,
+                patch_plugins: cli.patch_plugins,
+                patch_plugins_dylib: cli.patch_plugins_dylib,
+                substrate_backend: cli.substrate_backend,
+                swift_libs: cli.swift_libs,
+                keep_temp: cli.keep_temp,
+                resume: cli.resume,
+                export: cli.export,
+                export_certificate: cli.export_cert,
+                export_profile: cli.export_profile,
+            };
+            let options = match cli.preset {
+                Some(name) => Preset::load(&name)?.merge(options),
+                None => options,
+            };
+            let mut injector =
+                Injector::with_options(input, options).cancel_token(cancel_on_ctrlc());
+            for (stage, command) in cli.hooks {
+                injector = injector
+                    .hook(stage, move |app: &Path| run_hook_command(&command, stage, app));
+            }
+            let sideload = if cli.sideload {
+                Some(sideload_command(cli.sideload_command, cli.udid, &injector)?)
+            } else {
+                None
+            };
+            if cli.dry_run {
+                run_dry(injector)
+            } else {
+                // Where the ipa ended up, which an esign export moves into a folder
+                let output = run_inject(injector, cli.overwrite || cli.yes, cli.json)?;
+                match (sideload, output) {
+                    (Some(command), Some(output)) => run_sideload(&command, &output),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_cgen(
+    mut output: PathBuf,
+    files: Option<Vec<PathBuf>>,
+    name: Option<String>,
+    version: Option<String>,
+    bundle_id: Option<String>,
+    minimum: Option<String>,
+    icon: Option<PathBuf>,
+    alternate_icons: Vec<(String, PathBuf)>,
+    plist: Option<PathBuf>,
+    plist_merge: Option<PlistMerge>,
+    entitlements: Option<PathBuf>,
+    binary_entitlements: Option<PathBuf>,
+    remove_supported_devices: bool,
+    no_watch: bool,
+    enable_documents: bool,
+    fakesign: bool,
+    thin: bool,
+    remove_extensions: bool,
+    remove_encrypted: bool,
+    patch_plugins: bool,
+    cyan_compress: Option<CyanCompression>,
+    dedup: bool,
+    overwrite: bool,
+) -> Result<()> {
+    // Validate inputs
+    if let Some(ref m) = minimum {
+        m.parse::<Version>()?;
+    }
+
+    if let Some(ref k) = icon {
+        if !k.is_file() {
+            return Err(RuzuleError::FileNotFound(k.clone()));
+        }
+    }
+
+    for (_, k) in &alternate_icons {
+        if !k.is_file() {
+            return Err(RuzuleError::FileNotFound(k.clone()));
+        }
+    }
+
+    if let Some(ref l) = plist {
+        if !l.is_file() {
+            return Err(RuzuleError::FileNotFound(l.clone()));
+        }
+    }
+
+    if let Some(ref x) = entitlements {
+        if !x.is_file() {
+            return Err(RuzuleError::FileNotFound(x.clone()));
+        }
+    }
+
+    if let Some(ref files) = files {
+        for f in files {
+            if !f.exists() {
+                return Err(RuzuleError::FileNotFound(f.clone()));
+            }
+        }
+    }
+
+    let binary_entitlements: BTreeMap<String, EntitlementOps> = match binary_entitlements {
+        Some(ref path) => {
+            if !path.is_file() {
+                return Err(RuzuleError::FileNotFound(path.clone()));
+            }
+            serde_json::from_str(&fs::read_to_string(path)?)?
+        }
+        None => BTreeMap::new(),
+    };
+
+    // Ensure .cyan extension, unless it's wrapped in zstd
+    let zst = output.to_string_lossy().ends_with(".cyan.zst");
+    if !zst && output.extension().map(|e| e != "cyan").unwrap_or(true) {
+        log::warn!("appended .cyan extension to output");
+        output = output.with_extension("cyan");
+    }
+
+    // Check if output exists
+    if output.exists()
+        && !overwrite
+        && !confirm(&format!("{} already exists. overwrite?", output.display()))?
+    {
+        log::info!("quitting.");
+        return Ok(());
+    }
+
+    // Entitlements the files declare in sidecars, kept as metadata
+    let mut required = EntitlementOps::default();
+    for f in files.iter().flatten() {
+        if let Some(require) = ruzule_core::entitlements::read_sidecar(f)? {
+            required.extend(EntitlementOps {
+                require,
+                ..Default::default()
+            });
+        }
+    }
+
+    // Build config
+    let config = CyanConfig {
+        f: files.is_some(),
+        n: name,
+        v: version,
+        b: bundle_id,
+        m: minimum,
+        k: icon.is_some(),
+        alternate_icons: alternate_icons.iter().map(|(name, _)| name.clone()).collect(),
+        l: plist.is_some(),
+        plist_merge,
+        x: entitlements.is_some(),
+        remove_supported_devices,
+        no_watch,
+        enable_documents,
+        fakesign,
+        thin,
+        remove_extensions,
+        remove_encrypted,
+        patch_plugins,
+        requires_entitlements: required.require,
+        binary_entitlements,
+        ..Default::default()
+    };
+
+    log::info!("generating...");
+
+    // The outer zstd compresses across members, so they're stored by default
+    let cyan_compress = cyan_compress.unwrap_or(if zst {
+        CyanCompression::Deflate(0)
+    } else {
+        CyanCompression::default()
+    });
+    let zip_path = if zst {
+        let name = output.file_name().unwrap_or_default().to_string_lossy();
+        output.with_file_name(format!(".{}.zip", name))
+    } else {
+        output.clone()
+    };
+    let file = File::create(&zip_path)?;
+    let mut cyan = CyanWriter::new(file, cyan_compress, dedup);
+
+    // Add files to inject
+    if let Some(ref files) = files {
+        for f in files {
+            if f.is_file() {
+                let name = f.file_name().unwrap().to_string_lossy();
+                cyan.add_file(&format!("inject/{}", name), f)?;
+            } else if f.is_dir() {
+                cyan.add_dir(f, "inject")?;
+            }
+        }
+    }
+
+    // Add icon
+    if let Some(ref icon) = icon {
+        cyan.add_file("icon.idk", icon)?;
+    }
+    for (name, icon) in &alternate_icons {
+        cyan.add_file(&format!("alternate-icons/{}.idk", name), icon)?;
+    }
+
+    // Add plist
+    if let Some(ref plist) = plist {
+        cyan.add_file("merge.plist", plist)?;
+    }
+
+    // Add entitlements
+    if let Some(ref entitlements) = entitlements {
+        cyan.add_file("new.entitlements", entitlements)?;
+    }
+
+    // Write config.json
+    let deduped = cyan.finish(config)?;
+    if deduped > 0 {
+        log::info!("deduplicated \x1b[96m{}\x1b[0m file(s)", deduped);
+    }
+    if zst {
+        let compressed = compress_cyan(&zip_path, &output);
+        fs::remove_file(&zip_path)?;
+        compressed?;
+    }
+    log::info!("generated {}", output.display());
+
+    Ok(())
+}
+
+fn run_cdiff(old: PathBuf, new: PathBuf) -> Result<()> {
+    for path in [&old, &new] {
+        if !path.is_file() {
+            return Err(RuzuleError::FileNotFound(path.clone()));
+        }
+    }
+
+    let diff = diff_cyans(&old, &new)?;
+    if diff.is_empty() {
+        println!("no differences");
+        return Ok(());
+    }
+
+    let show = |v: &Option<serde_json::Value>| match v {
+        Some(v) => v.to_string(),
+        None => "(unset)".to_string(),
+    };
+    for change in &diff.config {
+        println!("{}: {} -> {}", change.key, show(&change.old), show(&change.new));
+    }
+    for name in &diff.added {
+        println!("added: {}", name);
+    }
+    for name in &diff.removed {
+        println!("removed: {}", name);
+    }
+    for name in &diff.changed {
+        println!("changed: {}", name);
+    }
+
+    Ok(())
+}
+
+fn run_lipo(inputs: Vec<PathBuf>, output: PathBuf, overwrite: bool) -> Result<()> {
+    for input in &inputs {
+        if !input.is_file() {
+            return Err(RuzuleError::FileNotFound(input.clone()));
+        }
+    }
+    if output.exists()
+        && !overwrite
+        && !confirm(&format!("{} already exists. overwrite?", output.display()))?
+    {
+        log::info!("quitting.");
+        return Ok(());
+    }
+
+    let archs = ruzule_core::macho::merge_slices(&inputs, &output)?;
+    log::info!("wrote {} ({})", output.display(), archs.join(", "));
+    Ok(())
+}
+
+fn run_frameworks() -> Result<()> {
+    println!("{:<26} {:<8} {:>14}  SHA-256", "NAME", "VERSION", "SIZE");
+    for info in ruzule_core::frameworks::payload_infos() {
+        let size = match info.size {
+            Some(size) => format!("{:.1} KiB", size as f64 / 1024.0),
+            None => "not downloaded".to_string(),
+        };
+        println!(
+            "{:<26} {:<8} {:>14}  {}",
+            info.name,
+            info.version.as_deref().unwrap_or("-"),
+            size,
+            info.sha256
+        );
+    }
+    Ok(())
+}
+
+fn run_frameworks_update(check: bool) -> Result<()> {
+    let updates = ruzule_core::frameworks::update_frameworks(check, &Events::none())?;
+    if updates.is_empty() {
+        log::info!("bundled frameworks are up to date");
+        return Ok(());
+    }
+
+    for update in &updates {
+        let verb = if update.installed { "updated" } else { "available" };
+        log::info!(
+            "{} {} -> {} ({})",
+            update.name,
+            update.from.as_deref().unwrap_or("?"),
+            update.to,
+            verb
+        );
+    }
+    if check {
+        log::info!("run `ruzule frameworks update` to download them");
+    }
+    Ok(())
+}
+
+fn run_cache_stats() -> Result<()> {
+    let Some(dir) = ruzule_core::cache::cache_dir() else {
+        log::error!("no cache directory (set RUZULE_CACHE_DIR)");
+        return Ok(());
+    };
+    log::info!("cache: {}", dir.display());
+
+    let usage = ruzule_core::cache::stats()?;
+    if usage.is_empty() {
+        log::info!("empty");
+        return Ok(());
+    }
+    println!("{:<16} {:>6} {:>10}  LAST MODIFIED", "NAME", "FILES", "SIZE");
+    for item in &usage {
+        let age = item
+            .newest
+            .and_then(|t| t.elapsed().ok())
+            .map(|d| format!("{} day(s) ago", d.as_secs() / (24 * 60 * 60)))
+            .unwrap_or_else(|| "-".to_string());
+        println!("{:<16} {:>6} {:>10}  {}", item.name, item.files, format_size(item.bytes), age);
+    }
+    let total: u64 = usage.iter().map(|item| item.bytes).sum();
+    log::info!("total {}", format_size(total));
+    Ok(())
+}
+
+fn run_identities(dir: Option<PathBuf>, json: bool) -> Result<()> {
+    let identities = ruzule_core::identity::list(dir.as_deref())?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&identities)?);
+        return Ok(());
+    }
+    if identities.is_empty() {
+        let dir = dir.or_else(ruzule_core::identity::identities_dir);
+        match dir {
+            Some(dir) => log::info!("no identities (add .p12 files to {})", dir.display()),
+            None => log::info!("no identities (set RUZULE_IDENTITIES_DIR)"),
+        }
+        return Ok(());
+    }
+
+    let width = identities.iter().map(|i| i.name.len()).max().unwrap_or(0).max(4);
+    println!("{:<width$}  {:<10}  {:<20}  FINGERPRINT", "NAME", "TEAM", "EXPIRES");
+    for identity in &identities {
+        let expires = if identity.expired {
+            format!("{} (expired)", identity.expires)
+        } else {
+            identity.expires.clone()
+        };
+        println!(
+            "{:<width$}  {:<10}  {:<20}  {}",
+            identity.name,
+            identity.team_id.as_deref().unwrap_or("-"),
+            expires,
+            identity.fingerprint
+        );
+        if identity.source != "keychain" {
+            println!("{:<width$}  from {}", "", identity.source);
+        }
+    }
+    Ok(())
+}
+
+fn run_optimize(
+    input: PathBuf,
+    output: PathBuf,
+    options: OptimizeOptions,
+    json: bool,
+) -> Result<()> {
+    let cancel = cancel_on_ctrlc();
+    let report =
+        ruzule_core::optimize::optimize(&input, &output, &options, &Events::none(), &cancel)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("{:<14} {:>10}", "STEP", "SAVED");
+    for step in &report.steps {
+        match &step.skipped {
+            Some(why) => println!("{:<14} {:>10}  skipped: {}", step.name, "-", why),
+            None => println!("{:<14} {:>10}", step.name, format_size(step.saved)),
+        }
+    }
+    let saved = report.input_size.saturating_sub(report.output_size);
+    log::info!(
+        "{} -> {} ({} saved)",
+        format_size(report.input_size),
+        format_size(report.output_size),
+        format_size(saved)
+    );
+    Ok(())
+}
+
+fn run_deps(input: PathBuf, all: bool, json: bool) -> Result<()> {
+    if !input.exists() {
+        return Err(RuzuleError::FileNotFound(input));
+    }
+    let binaries = ruzule_core::deps::app_linkage(&input, all)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&binaries)?);
+        return Ok(());
+    }
+
+    for (i, binary) in binaries.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}:", binary.binary);
+        let linkage = &binary.linkage;
+        if let Some(ref id) = linkage.install_name {
+            println!("\tid {}", id);
+        }
+        for dylib in &linkage.dylibs {
+            let kind = match dylib.kind {
+                LinkKind::Load => "",
+                LinkKind::Weak => " weak",
+                LinkKind::Reexport => " reexport",
+                LinkKind::Lazy => " lazy",
+                LinkKind::Upward => " upward",
+            };
+            println!(
+                "\t{} (compatibility version {}, current version {}){}",
+                dylib.path, dylib.compatibility_version, dylib.current_version, kind
+            );
+        }
+        for rpath in &linkage.rpaths {
+            println!("\trpath {}", rpath);
+        }
+    }
+    Ok(())
+}
+
+fn run_report(input: PathBuf, json: bool) -> Result<()> {
+    if !input.exists() {
+        return Err(RuzuleError::FileNotFound(input));
+    }
+    let report = AppReport::from_path(&input)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "{} {} ({}), {}",
+        report.name.as_deref().unwrap_or("?"),
+        report.version.as_deref().unwrap_or("?"),
+        report.bundle_id.as_deref().unwrap_or("?"),
+        report.platform
+    );
+    let section = |title: &str, lines: Vec<String>| {
+        println!("\n{} ({}):", title, lines.len());
+        if lines.is_empty() {
+            println!("    none");
+        }
+        for line in lines {
+            println!("    {}", line);
+        }
+    };
+
+    section(
+        "privacy prompts",
+        report
+            .usage_descriptions
+            .iter()
+            .map(|(key, text)| format!("{}: {}", key, text))
+            .collect(),
+    );
+    section("background modes", report.background_modes.clone());
+    section(
+        "entitlements",
+        report
+            .entitlements
+            .iter()
+            .map(|(key, value)| format!("{} = {}", key, value))
+            .collect(),
+    );
+    section(
+        "entitlements ad-hoc signing can't honor",
+        report
+            .entitlement_risks
+            .iter()
+            .map(|(key, reason)| format!("{}: {}", key, reason))
+            .collect(),
+    );
+    if !report.entitlement_risks.is_empty() {
+        println!("    (remove them with --strip-risky-entitlements when fakesigning)");
+    }
+    section("queried URL schemes", report.queried_schemes.clone());
+    section(
+        "App Transport Security exceptions",
+        report
+            .ats_exceptions
+            .iter()
+            .map(|e| format!("{}: {}", e.domain.as_deref().unwrap_or("all"), e.keys.join(", ")))
+            .collect(),
+    );
+    Ok(())
+}
+
+fn run_preset_list() -> Result<()> {
+    let presets = Preset::list()?;
+    if presets.is_empty() {
+        let dir = ruzule_core::preset::presets_dir();
+        let dir = dir.as_deref().map(Path::display);
+        match dir {
+            Some(dir) => log::info!("no presets in {}", dir),
+            None => log::info!("no presets directory (set RUZULE_PRESETS_DIR)"),
+        }
+        return Ok(());
+    }
+
+    for preset in presets {
+        println!("{:<24} {}", preset.name, preset.description.as_deref().unwrap_or("-"));
+    }
+    Ok(())
+}
+
+/// Run `injector`, asking before replacing an existing output unless `overwrite`.
+/// With `json`, stdout only gets the [`InjectReport`](ruzule_core::InjectReport)
+/// (or the error) as JSON and everything else goes to stderr.
+/// Run `injector`, returning where the output was written, or `None` if the
+/// user chose not to overwrite
+fn run_inject(injector: Injector, overwrite: bool, json: bool) -> Result<Option<PathBuf>> {
+    if json {
+        LOGGER.stderr.store(true, Ordering::Relaxed);
+        let result = injector.validate().and_then(|()| {
+            let output = injector.output_path();
+            if output == Path::new("-") {
+                return Err(RuzuleError::InvalidInput(
+                    "--json needs stdout, write the ipa to a file".to_string(),
+                ));
+            }
+            if output.exists() && !overwrite {
+                return Err(RuzuleError::InvalidInput(format!(
+                    "{} already exists, pass --overwrite to replace it",
+                    output.display()
+                )));
+            }
+            injector.run()
+        });
+        let summary = match result {
+            Ok(ref report) => serde_json::to_value(report)?,
+            Err(ref e) => serde_json::json!({ "error": e }),
+        };
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return result.map(|report| Some(report.output));
+    }
+
+    injector.validate()?;
+
+    // Check if output exists
+    let output = injector.output_path();
+    if output == Path::new("-") {
+        if std::io::stdout().is_terminal() {
+            return Err(RuzuleError::InvalidInput(
+                "refusing to write an ipa to a terminal, pipe or redirect stdout".to_string(),
+            ));
+        }
+        LOGGER.stderr.store(true, Ordering::Relaxed);
+    } else if output.exists() && !overwrite {
+        let msg = if output != injector.input() {
+            format!("{} already exists, overwrite it?", output.display())
+        } else {
+            "no output was specified. overwrite the input?".to_string()
+        };
+
+        if !confirm(&msg)? {
+            log::info!("quitting.");
+            return Ok(None);
+        }
+    }
+
+    let report = injector.run()?;
+    log::info!(
+        "injected \x1b[96m{}\x1b[0m, signed \x1b[96m{}\x1b[0m, thinned \x1b[96m{}\x1b[0m, \
+         \x1b[96m{}\x1b[0m plist change(s); {} -> {}",
+        report.injected.len(),
+        report.signed,
+        report.thinned,
+        report.plist_changes.len(),
+        format_size(report.input_size),
+        format_size(report.output_size),
+    );
+    let phases: Vec<String> = report
+        .phases
+        .iter()
+        .map(|p| format!("{} {:.1}s", p.name, p.elapsed.as_secs_f64()))
+        .collect();
+    log::debug!("timings: {}", phases.join(", "));
+    if !report.warnings.is_empty() {
+        log::error!("finished with \x1b[96m{}\x1b[0m warning(s):", report.warnings.len());
+        for warning in &report.warnings {
+            log::error!("  {}", warning);
+        }
+    }
+    Ok(Some(report.output))
+}
+
+/// Stages shown by `bench`, as named in `InjectReport::phases`
+const BENCH_PHASES: [&str; 5] = ["extract", "inject", "sign", "thin", "pack"];
+
+fn run_bench(
+    input: PathBuf,
+    files: Vec<PathBuf>,
+    cyans: Vec<PathBuf>,
+    runs: usize,
+    mut jobs: Vec<usize>,
+    compress: Vec<u32>,
+) -> Result<()> {
+    if jobs.is_empty() {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        jobs = if cores > 1 { vec![1, cores] } else { vec![1] };
+    }
+    if jobs.contains(&0) {
+        return Err(RuzuleError::InvalidInput("jobs must be at least 1".to_string()));
+    }
+    let ext = if input.extension().is_some_and(|e| e == "app") { "app" } else { "ipa" };
+    let tmpdir = TempDir::new()?;
+    let cancel = cancel_on_ctrlc();
+
+    // Runs report through the logger; only their errors are worth seeing here
+    let level = log::max_level();
+    log::set_max_level(level.min(log::LevelFilter::Error));
+
+    let mut rows = Vec::new();
+    for &j in &jobs {
+        for &c in &compress {
+            log::set_max_level(level);
+            log::info!("jobs {}, compression {}: {} run(s)", j, c, runs);
+            log::set_max_level(level.min(log::LevelFilter::Error));
+            let mut reports = Vec::new();
+            for run in 0..runs {
+                let output = tmpdir.path().join(format!("bench-{}.{}", run, ext));
+                let options = InjectOptions {
+                    output: Some(output.clone()),
+                    cyans: cyans.clone(),
+                    files: files.clone(),
+                    fakesign: true,
+                    thin: true,
+                    compress: c,
+                    jobs: Some(j),
+                    ..Default::default()
+                };
+                let result = Injector::with_options(&input, options)
+                    .cancel_token(cancel.clone())
+                    .run();
+                if output.is_dir() {
+                    fs::remove_dir_all(&output)?;
+                } else if output.exists() {
+                    fs::remove_file(&output)?;
+                }
+                match result {
+                    Ok(report) => reports.push(report),
+                    Err(e) => {
+                        log::set_max_level(level);
+                        return Err(e);
+                    }
+                }
+            }
+            rows.push((j, c, reports));
+        }
+    }
+    log::set_max_level(level);
+
+    let median = |mut times: Vec<f64>| -> f64 {
+        times.sort_by(|a, b| a.total_cmp(b));
+        times[times.len() / 2]
+    };
+    print!("{:>4} {:>8}", "JOBS", "COMPRESS");
+    for phase in BENCH_PHASES {
+        print!(" {:>8}", phase.to_uppercase());
+    }
+    println!(" {:>8} {:>10}", "TOTAL", "SIZE");
+    for (j, c, reports) in &rows {
+        print!("{:>4} {:>8}", j, c);
+        for phase in BENCH_PHASES {
+            let times = reports
+                .iter()
+                .map(|r| {
+                    r.phases
+                        .iter()
+                        .filter(|p| p.name == phase)
+                        .map(|p| p.elapsed.as_secs_f64())
+                        .sum()
+                })
+                .collect();
+            print!(" {:>7.2}s", median(times));
+        }
+        let totals = reports
+            .iter()
+            .map(|r| r.phases.iter().map(|p| p.elapsed.as_secs_f64()).sum())
+            .collect();
+        let size = reports.first().map_or(0, |r| r.output_size);
+        println!(" {:>7.2}s {:>10}", median(totals), format_size(size));
+    }
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    format!("{:.1} MB", bytes as f64 / MB)
+}
+
+/// Copy stdin to `dir` as `stdin.<format>`, for `-i -`
+fn spool_stdin(dir: &Path, format: &str) -> Result<PathBuf> {
+    let mut stdin = std::io::stdin().lock();
+    if stdin.is_terminal() {
+        return Err(RuzuleError::InvalidInput(
+            "-i - reads the app from stdin, but nothing is piped in".to_string(),
+        ));
+    }
+    let path = dir.join(format!("stdin.{}", format));
+    let size = std::io::copy(&mut stdin, &mut File::create(&path)?)?;
+    log::debug!("read {} from stdin", format_size(size));
+    Ok(path)
+}
+
+fn run_dry(injector: Injector) -> Result<()> {
+    let plan = injector.plan()?;
+    println!("dry run, would:");
+    for op in plan.operations() {
+        println!("    - {}", op);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_dupe(
+    input: PathBuf,
+    mut output: PathBuf,
+    seed: Option<String>,
+    bundle: Option<String>,
+    stable: bool,
+    count: usize,
+    files: Vec<PathBuf>,
+    cyans: Vec<PathBuf>,
+    overwrite: bool,
+    keep_extensions: bool,
+    url_scheme_suffix: Option<String>,
+    add_url_schemes: Vec<String>,
+    cancel: CancellationToken,
+) -> Result<()> {
+    // Validate input
+    if !input.exists() {
+        return Err(RuzuleError::FileNotFound(input));
+    }
+
+    let input_ext = input
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+
+    if !matches!(input_ext.as_deref(), Some("ipa") | Some("tipa") | Some("app")) {
+        return Err(RuzuleError::InvalidInput(
+            "Input must be an .ipa, .tipa, or .app".to_string(),
+        ));
+    }
+
+    // Ensure output is an .ipa or .app
+    let output_ext = output.extension().map(|e| e.to_string_lossy().to_lowercase());
+    if !matches!(output_ext.as_deref(), Some("ipa") | Some("app")) {
+        log::warn!("ipa file extension not detected, appending manually");
+        output = output.with_extension("ipa");
+    }
+
+    if count == 0 {
+        return Err(RuzuleError::InvalidInput("--count must be at least 1".to_string()));
+    }
+    if count > 1 && bundle.is_some() {
+        return Err(RuzuleError::InvalidInput(
+            "-b can't be used with --count, every copy needs its own suffix".to_string(),
+        ));
+    }
+
+    // out.ipa -> out-1.ipa, out-2.ipa, ...
+    let outputs: Vec<PathBuf> = if count > 1 {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let ext = output.extension().unwrap_or_default().to_string_lossy().to_string();
+        (1..=count)
+            .map(|i| output.with_file_name(format!("{}-{}.{}", stem, i, ext)))
+            .collect()
+    } else {
+        vec![output]
+    };
+
+    // Validate bundle suffix if provided
+    if let Some(ref b) = bundle {
+        if b.len() != 10 {
+            return Err(RuzuleError::InvalidInput(
+                "-b argument has invalid length (must be 10 hex chars)".to_string(),
+            ));
+        }
+        if !b.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(RuzuleError::InvalidInput(
+                "-b argument is invalid (must be hex chars only)".to_string(),
+            ));
+        }
+    }
+
+    if let Some(ref suffix) = url_scheme_suffix {
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.');
+        if suffix.is_empty() || !suffix.chars().all(valid) {
+            return Err(RuzuleError::InvalidInput(format!(
+                "Invalid URL scheme suffix: {} (letters, digits, '+', '-' and '.' only)",
+                suffix
+            )));
+        }
+    }
+
+    if let Some(scheme) = add_url_schemes.iter().find(|s| !is_valid_url_scheme(s)) {
+        return Err(RuzuleError::InvalidInput(format!(
+            "Invalid URL scheme: {} (a letter, then letters, digits, '+', '-' and '.')",
+            scheme
+        )));
+    }
+    if count > 1 && !add_url_schemes.is_empty() {
+        log::warn!("every copy registers the same URL schemes; iOS opens only one of them");
+    }
+
+    // Check if output exists
+    if let Some(existing) = outputs.iter().find(|o| o.exists()).filter(|_| !overwrite) {
+        if !confirm(&format!("{} already exists. overwrite?", existing.display()))? {
+            log::info!("quitting.");
+            return Ok(());
+        }
+    }
+
+    if stable && seed.is_none() {
+        log::warn!("--stable without --seed; pass the seed below next time to get the same ids");
+    }
+
+    // Generate or use provided seed
+    let seed = seed.unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    // Derive team ID from seed (last 10 chars of SHA256 hash, uppercase)
+    let mut hasher = Sha256::new();
+    hasher.update(seed.as_bytes());
+    let hash = hasher.finalize();
+    let hash_hex = hex::encode_upper(hash);
+    let team_id = &hash_hex[hash_hex.len() - 10..];
+
+    // Bundle ID components
+    let bundle_ti = format!("fyi.zxcvbn.appdupe.{}", team_id);
+
+    log::info!("seed: \"{}\"", seed);
+    log::info!("team id: {}", team_id);
+
+    // Copies share the team id and differ in bundle suffix (and URL scheme suffix)
+    let ids_for = |copy: Option<usize>| DupeIds {
+        team_id: team_id.to_string(),
+        bundle_ti: bundle_ti.clone(),
+        // A stable suffix depends on the app's bundle id, which is only known once it's extracted
+        bundle_suffix: if stable {
+            None
+        } else {
+            Some(
+                bundle
+                    .clone()
+                    .unwrap_or_else(|| Uuid::new_v4().to_string()[..10].replace('-', "")),
+            )
+        },
+        seed: seed.clone(),
+        copy,
+    };
+
+    if count == 1 {
+        // Injection and duplication share one extract/pack cycle; the dupe
+        // changes go last so they sign over anything injected
+        let ids = ids_for(None);
+        let options = InjectOptions {
+            output: outputs.into_iter().next(),
+            files,
+            cyans,
+            ..Default::default()
+        };
+        let injector = Injector::with_options(input, options)
+            .cancel_token(cancel)
+            .hook(HookStage::PrePack, move |app: &Path| {
+                let url_suffix = url_scheme_suffix.as_deref();
+                dupe_app(app, &ids, keep_extensions, url_suffix, &add_url_schemes)
+            });
+        injector.run()?;
+        return Ok(());
+    }
+
+    // Extract (and inject) once into a base .app, then copy it for each duplicate
+    let workdir = TempDir::new()?;
+    let app_name = format!(
+        "{}.app",
+        input.file_stem().unwrap_or_default().to_string_lossy()
+    );
+    let base = workdir.path().join(&app_name);
+    let options = InjectOptions {
+        output: Some(base.clone()),
+        files,
+        cyans,
+        ..Default::default()
+    };
+    Injector::with_options(input, options).cancel_token(cancel.clone()).run()?;
+
+    for (i, output) in outputs.iter().enumerate() {
+        cancel.check()?;
+        let copy = i + 1;
+        let staging = TempDir::new()?;
+        let app_path = staging.path().join("Payload").join(&app_name);
+        RealFs.copy_dir(&base, &app_path)?;
+
+        let url_suffix = url_scheme_suffix.as_ref().map(|s| format!("{}{}", s, copy));
+        let ids = ids_for(Some(copy));
+        dupe_app(&app_path, &ids, keep_extensions, url_suffix.as_deref(), &add_url_schemes)?;
+
+        log::info!("generating copy {}...", copy);
+        if output.extension().is_some_and(|e| e == "app") {
+            install_app(&app_path, output)?;
+        } else {
+            create_ipa(staging.path(), output, 6, None, &cancel, &Events::none())?;
+        }
+        log::info!("done: {}", output.display());
+    }
+
+    Ok(())
+}
+
+/// Identifiers a duplicate is moved to
+struct DupeIds {
+    team_id: String,
+    /// Prefix of the keychain and app groups
+    bundle_ti: String,
+    /// `None` to derive it from the seed and the original bundle id
+    bundle_suffix: Option<String>,
+    seed: String,
+    /// Which of several copies this is
+    copy: Option<usize>,
+}
+
+impl DupeIds {
+    fn bundle_id(&self, original: &str) -> String {
+        let suffix = self.bundle_suffix.clone().unwrap_or_else(|| {
+            let mut hasher = Sha256::new();
+            hasher.update(self.seed.as_bytes());
+            hasher.update(original.as_bytes());
+            if let Some(copy) = self.copy {
+                hasher.update(copy.to_string().as_bytes());
+            }
+            hex::encode(hasher.finalize())[..10].to_string()
+        });
+        format!("fyi.zxcvbn.appdupe.{}", suffix)
+    }
+}
+
+fn dupe_app(
+    app_path: &Path,
+    ids: &DupeIds,
+    keep_extensions: bool,
+    url_scheme_suffix: Option<&str>,
+    add_url_schemes: &[String],
+) -> Result<()> {
+    let tmpdir = TempDir::new()?;
+    let ent_path = tmpdir.path().join("entitlements.plist");
+
+    // Load app bundle
+    let mut app = AppBundle::new(app_path)?;
+    let old_bundle_id = app.plist.get_string("CFBundleIdentifier").unwrap_or_default().to_string();
+    let bundle_id = ids.bundle_id(&old_bundle_id);
+    log::info!("bundle id: {}", bundle_id);
+
+    // Modify plist
+    app.plist.set("CFBundleIdentifier", plist::Value::String(bundle_id.clone()));
+    app.plist.remove("UISupportedDevices");
+    let renamed = url_scheme_suffix
+        .zip(app.plist.get("CFBundleURLTypes"))
+        .map(|(suffix, url_types)| rename_url_schemes(url_types, suffix));
+    match renamed {
+        Some(url_types) => app.plist.set("CFBundleURLTypes", url_types),
+        None => {
+            app.plist.remove("CFBundleURLTypes");
+        }
+    }
+    app.plist.add_url_schemes(add_url_schemes);
+
+    if keep_extensions {
+        // Extensions are signed before the app that contains them
+        for appex in find_extensions(app_path)? {
+            let mut ext = AppBundle::new(&appex)?;
+            let old_id = ext.plist.get_string("CFBundleIdentifier").unwrap_or_default();
+            let new_id = match old_id.strip_prefix(old_bundle_id.as_str()) {
+                Some(rest) if rest.starts_with('.') => format!("{}{}", bundle_id, rest),
+                _ => format!("{}.{}", bundle_id, ext.executable.inner.name),
+            };
+            ext.plist.set("CFBundleIdentifier", plist::Value::String(new_id.clone()));
+            ext.plist.save()?;
+
+            write_dupe_entitlements(&ext, &ent_path, ids, &new_id)?;
+            ext.executable.sign_with_entitlements(&ent_path)?;
+            log::info!("moved {} to {}", ext.executable.inner.name, new_id);
+        }
+    } else {
+        // Remove app extensions (PlugIns and Extensions)
+        app.remove_all_extensions();
+    }
+
+    // Sign with new entitlements
+    write_dupe_entitlements(&app, &ent_path, ids, &bundle_id)?;
+    app.executable.sign_with_entitlements(&ent_path)?;
+
+    // Save plist changes
+    app.plist.save()?;
+    Ok(())
+}
+
+/// `CFBundleURLTypes` with every scheme renamed to `<scheme>-<suffix>`
+fn rename_url_schemes(url_types: &plist::Value, suffix: &str) -> plist::Value {
+    let mut url_types = url_types.clone();
+    for url_type in url_types.as_array_mut().into_iter().flatten() {
+        let schemes = url_type
+            .as_dictionary_mut()
+            .and_then(|d| d.get_mut("CFBundleURLSchemes"))
+            .and_then(plist::Value::as_array_mut);
+        for scheme in schemes.into_iter().flatten() {
+            if let plist::Value::String(scheme) = scheme {
+                *scheme = format!("{}-{}", scheme, suffix);
+            }
+        }
+    }
+    url_types
+}
+
+/// App extensions directly inside an app's PlugIns/ and Extensions/
+fn find_extensions(app_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for dir in ["PlugIns", "Extensions"] {
+        let dir = app_path.join(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "appex") {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Write a bundle's entitlements, moved to the dupe's team and `bundle_id`, to `ent_path`
+fn write_dupe_entitlements(
+    bundle: &AppBundle,
+    ent_path: &Path,
+    ids: &DupeIds,
+    bundle_id: &str,
+) -> Result<()> {
+    // Get and modify entitlements
+    let has_entitlements = bundle.executable.write_entitlements(ent_path)?;
+
+    let mut entitlements: plist::Dictionary = if has_entitlements {
+        let ent_data = fs::read(ent_path)?;
+        plist::from_bytes(&ent_data).unwrap_or_default()
+    } else {
+        plist::Dictionary::new()
+    };
+
+    // Set required entitlements
+    entitlements.insert(
+        "application-identifier".to_string(),
+        plist::Value::String(format!("{}.{}", ids.team_id, bundle_id)),
+    );
+    entitlements.insert(
+        "com.apple.developer.team-identifier".to_string(),
+        plist::Value::String(ids.team_id.clone()),
+    );
+    entitlements.insert(
+        "keychain-access-groups".to_string(),
+        plist::Value::Array(vec![plist::Value::String(ids.bundle_ti.clone())]),
+    );
+    entitlements.insert(
+        "com.apple.security.application-groups".to_string(),
+        plist::Value::Array(vec![plist::Value::String(format!("group.{}", ids.bundle_ti))]),
+    );
+
+    // Remove associated domains (prevents URL conflicts)
+    entitlements.remove("com.apple.developer.associated-domains");
+
+    // Write modified entitlements
+    let mut ent_file = File::create(ent_path)?;
+    plist::to_writer_xml(&mut ent_file, &entitlements)?;
+    Ok(())
+}
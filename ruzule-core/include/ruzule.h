@@ -0,0 +1,85 @@
+/* C interface to ruzule, available when built with the `ffi` feature:
+ *
+ *   cargo rustc -p ruzule-core --profile ffi --lib --features ffi --crate-type cdylib
+ *
+ * The ffi profile is the release one with panic = "unwind". Built with
+ * panic = "abort" (plain --release), a panic aborts the host process instead
+ * of returning RUZULE_ERR_PANIC.
+ */
+#ifndef RUZULE_H
+#define RUZULE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RUZULE_OK              0
+#define RUZULE_ERR_ARGUMENT   -1 /* null pointer or invalid UTF-8 */
+#define RUZULE_ERR_FAILED     -2 /* see ruzule_last_error() */
+#define RUZULE_ERR_CANCELLED  -3
+#define RUZULE_ERR_PANIC      -4 /* internal bug; needs the ffi profile, see above */
+
+typedef struct RuzuleCancel RuzuleCancel;
+
+/* level: 0 info, 1 notice, 2 warning. message is only valid during the call.
+ * Called from ruzule's worker threads as well as the one calling ruzule_inject,
+ * but never from two threads at once. */
+typedef void (*RuzuleProgressCallback)(int32_t level, const char *message, void *user_data);
+
+typedef struct RuzuleOptions {
+    const char *input;  /* required: .ipa, .tipa or .app */
+    const char *output; /* NULL overwrites the input */
+    const char *const *files;
+    size_t files_len;
+    const char *const *cyans;
+    size_t cyans_len;
+    const char *name;
+    const char *version;
+    const char *bundle_id;
+    const char *minimum;
+    const char *icon;
+    const char *plist;
+    const char *entitlements;
+    bool remove_supported_devices;
+    bool no_watch;
+    bool enable_documents;
+    bool fakesign;
+    bool thin;
+    bool remove_extensions;
+    bool remove_encrypted;
+    uint32_t compress; /* 0-9 */
+    bool ignore_encrypted;
+    bool use_frameworks_dir;
+    bool patch_plugins;
+    const char *substrate_backend; /* "substrate", "ellekit", "libhooker" or NULL */
+    RuzuleProgressCallback progress;
+    void *user_data;
+    const RuzuleCancel *cancel; /* may be NULL */
+} RuzuleOptions;
+
+/* Set every field to its default (all off, compression level 6). */
+void ruzule_options_init(RuzuleOptions *options);
+
+/* Run the inject pipeline; returns RUZULE_OK or a RUZULE_ERR_* code. */
+int32_t ruzule_inject(const RuzuleOptions *options);
+
+/* Last error message on the calling thread, or NULL if the last ruzule_inject
+ * call on it succeeded. Valid until the next call. */
+const char *ruzule_last_error(void);
+
+/* Stable code of the last error (e.g. "E_ENCRYPTED", see README), or NULL. */
+const char *ruzule_last_error_code(void);
+
+RuzuleCancel *ruzule_cancel_new(void);
+void ruzule_cancel(const RuzuleCancel *cancel);
+void ruzule_cancel_free(RuzuleCancel *cancel);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RUZULE_H */
@@ -0,0 +1,1326 @@
+use crate::buffer::Arch;
+use crate::deb;
+use crate::entitlements::{self, EntitlementOps};
+use crate::error::{Result, RuzuleError};
+use crate::events::{Event, Events, Progress, ProgressStage};
+use crate::executable::{Executable, MainExecutable};
+use crate::frameworks::SubstrateBackend;
+use crate::icon;
+use crate::macho;
+use crate::plan::disk_size;
+use crate::platform::{self, Layout, Platform};
+use crate::plist_ext::PlistFile;
+use crate::sign;
+use crate::version::Version;
+use crate::vfs::{self, Fs};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// App icon point sizes and the scales each is rendered at, as in an Xcode icon set
+const IPHONE_ICONS: &[(f32, &[u32])] = &[(20.0, &[2, 3]), (29.0, &[2, 3]), (40.0, &[2, 3]), (60.0, &[2, 3])];
+const IPAD_ICONS: &[(f32, &[u32])] =
+    &[(20.0, &[1, 2]), (29.0, &[1, 2]), (40.0, &[1, 2]), (76.0, &[1, 2]), (83.5, &[2])];
+/// The App Store size, which some launchers pick up as the largest listed
+const MARKETING_ICON: u32 = 1024;
+
+/// Prefix of the file names of a rendered icon set, unique so sets don't collide
+fn icon_uid() -> String {
+    format!("ruzule_{}a", &uuid::Uuid::new_v4().simple().to_string()[..7])
+}
+
+/// An injected dylib copied to the temp directory with its load commands fixed
+struct PreparedDylib {
+    temp_path: PathBuf,
+    needed: HashSet<String>,
+    needed_swift: Vec<String>,
+    thinned: bool,
+}
+
+pub struct AppBundle {
+    pub path: PathBuf,
+    pub plist: PlistFile,
+    pub executable: MainExecutable,
+    pub platform: Platform,
+    pub layout: Layout,
+    executables: OnceLock<Vec<PathBuf>>,
+    events: Events,
+    fs: Arc<dyn Fs>,
+    jobs: Option<usize>,
+    thin_injected: bool,
+}
+
+impl AppBundle {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_events(path, Events::none())
+    }
+
+    /// Open a bundle whose changes are reported to `events`
+    pub fn with_events<P: AsRef<Path>>(path: P, events: Events) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let layout = Layout::detect(&path);
+        let plist_path = layout.info_plist(&path);
+
+        let plist = PlistFile::open_with_app_path(&plist_path, &path)?.with_events(events.clone());
+        let platform = Platform::detect(&plist);
+
+        let exec_name = plist
+            .get_string("CFBundleExecutable")
+            .ok_or_else(|| RuzuleError::InvalidAppBundle("No CFBundleExecutable".to_string()))?;
+
+        let exec_path = layout.executable_dir(&path).join(exec_name);
+        let executable = MainExecutable::with_events(&exec_path, &path, events.clone())?;
+
+        Ok(Self {
+            path,
+            plist,
+            executable,
+            platform,
+            layout,
+            executables: OnceLock::new(),
+            events,
+            fs: vfs::real(),
+            jobs: None,
+            thin_injected: false,
+        })
+    }
+
+    /// Do file copies, removals and lookups through `fs`
+    pub fn with_fs(mut self, fs: Arc<dyn Fs>) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    /// Sign and thin on at most `jobs` threads (default: one per core)
+    pub fn with_jobs(mut self, jobs: Option<usize>) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Thin injected dylibs and frameworks to the slices the main executable can load
+    pub fn with_thin_injected(mut self, enabled: bool) -> Self {
+        self.thin_injected = enabled;
+        self
+    }
+
+    /// Re-read the Info.plist and executables after something else changed the bundle
+    pub fn reload(&mut self) -> Result<()> {
+        *self = Self::with_events(&self.path, self.events.clone())?
+            .with_fs(self.fs.clone())
+            .with_jobs(self.jobs)
+            .with_thin_injected(self.thin_injected);
+        Ok(())
+    }
+
+    /// `name` (e.g. `Frameworks`) inside the bundle's contents directory
+    fn contents(&self, name: &str) -> PathBuf {
+        self.path.join(self.layout.rel(name))
+    }
+
+    pub fn remove<P: AsRef<Path>>(&self, names: &[P]) -> bool {
+        let mut existed = false;
+
+        for name in names {
+            let name = name.as_ref();
+            let path = if name.starts_with(&self.path) {
+                name.to_path_buf()
+            } else {
+                self.path.join(name)
+            };
+
+            if !self.fs.exists(&path) {
+                continue;
+            }
+
+            match self.fs.remove(&path) {
+                Ok(()) => existed = true,
+                Err(e) => self.events.warn(format!("couldn't remove {}: {}", path.display(), e)),
+            }
+        }
+
+        existed
+    }
+
+    pub fn remove_watch_apps(&self) {
+        let names = ["Watch", "WatchKit", "com.apple.WatchPlaceholder"];
+        if self.remove(&names.map(Path::new)) {
+            self.events.emit(Event::Removed("watch app".to_string()));
+        }
+    }
+
+    /// Cut each watch app in `Watch/` down to its Info.plist and executable,
+    /// dropping its extension, frameworks and resources. Unlike
+    /// [`remove_watch_apps`](Self::remove_watch_apps), the bundle and its ids stay,
+    /// for installers that check `WKCompanionAppBundleIdentifier` pairs.
+    pub fn stub_watch_apps(&self) -> Result<()> {
+        let pattern = format!("{}/Watch/*.app", self.path.display());
+        let mut stubbed = false;
+        for watch_app in self.fs.glob(&pattern)? {
+            let mut plist = PlistFile::open(watch_app.join("Info.plist"))?;
+            let executable = plist.get_string("CFBundleExecutable").unwrap_or_default().to_string();
+
+            let contents = self.fs.glob(&format!("{}/*", watch_app.display()))?;
+            let heavy: Vec<PathBuf> = contents
+                .into_iter()
+                .filter(|path| {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    !matches!(name.as_ref(), "Info.plist" | "PkgInfo") && name != executable
+                })
+                .collect();
+            stubbed |= self.remove(&heavy);
+
+            // Keys naming files that are gone
+            let mut changed = false;
+            for key in ["CFBundleIcons", "CFBundleIconFiles", "UIMainStoryboardFile"] {
+                changed |= plist.remove(key);
+            }
+            if changed {
+                plist.save()?;
+            }
+        }
+        if stubbed {
+            let what = "watch app contents, leaving a placeholder";
+            self.events.emit(Event::Removed(what.to_string()));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get_executables(&self) -> Vec<PathBuf> {
+        let mut executables = Vec::new();
+        let patterns = [
+            format!("{}/**/*.dylib", self.path.display()),
+            format!("{}/**/*.appex", self.path.display()),
+            format!("{}/**/*.framework", self.path.display()),
+        ];
+
+        for pattern in patterns {
+            if let Ok(paths) = self.fs.glob(&pattern) {
+                executables.extend(paths);
+            }
+        }
+
+        executables
+    }
+
+    /// Sign the app and everything in it with `certificate`, embedding `profile`
+    /// first when given. The profile must be issued to the certificate's team and
+    /// cover the app's bundle id; the main binary gets the entitlements it grants.
+    pub fn sign(&self, certificate: &sign::Certificate, profile: Option<&Path>) -> Result<()> {
+        let mut entitlements = None;
+        if let Some(profile) = profile {
+            sign::check_profile(profile, certificate)?;
+            let bundle_id = self.plist.get_string("CFBundleIdentifier").unwrap_or_default();
+            entitlements = Some(sign::profile_entitlements(profile, bundle_id)?);
+            let name = match self.layout {
+                Layout::Shallow => "embedded.mobileprovision",
+                Layout::Deep => "embedded.provisionprofile",
+            };
+            self.fs.copy(profile, &self.path.join(self.layout.rel(name)))?;
+        }
+        sign::sign_bundle(&self.path, certificate, entitlements.as_ref())?;
+
+        self.events.emit(Event::SignedWith(certificate.name()));
+        Ok(())
+    }
+
+    pub fn fakesign_all(&self) -> Result<()> {
+        let executables = self.nested_executables()?;
+        let total = 1 + executables.len() as u64;
+        let progress = Progress::new(&self.events, ProgressStage::Sign, total);
+        let mut count = usize::from(self.executable.fakesign()?);
+        progress.add(1);
+        count += self.for_each_of(&executables, "fakesign", |e| {
+            let signed = e.fakesign();
+            progress.add(1);
+            signed
+        })?;
+
+        self.events.emit(Event::Signed(count));
+        Ok(())
+    }
+
+    /// Thin every binary to arm64, see [`thin_all_to`](Self::thin_all_to)
+    pub fn thin_all(&self) -> Result<()> {
+        self.thin_all_to(Arch::Arm64)
+    }
+
+    /// Thin the main binary and every nested one to `arch`; binaries without
+    /// that slice are left as they are, with a warning
+    pub fn thin_all_to(&self, arch: Arch) -> Result<()> {
+        let executables = self.nested_executables()?;
+        let total = 1 + executables.len() as u64;
+        let progress = Progress::new(&self.events, ProgressStage::Thin, total);
+        let mut count = 0;
+
+        match self.executable.thin_to(arch) {
+            Ok(true) => count += 1,
+            Ok(false) => {}
+            Err(e) => {
+                let name = &self.executable.inner.name;
+                self.events.warn(format!("couldn't thin {}: {}", name, e))
+            }
+        }
+        progress.add(1);
+        count += self.for_each_of(&executables, "thin", |e| {
+            let thinned = e.thin_to(arch);
+            progress.add(1);
+            thinned
+        })?;
+
+        self.events.emit(Event::Thinned(count));
+        Ok(())
+    }
+
+    /// Binaries of every nested dylib, framework and extension, found on first use
+    pub(crate) fn nested_executables(&self) -> Result<Vec<Executable>> {
+        let mut executables = Vec::new();
+
+        for path in self.executables.get_or_init(|| self.get_executables()) {
+            let exec_path = if path.extension().is_some_and(|e| e == "dylib") {
+                path.clone()
+            } else {
+                // It's a bundle, get its executable
+                let Some(exec_path) = platform::bundle_executable(path) else {
+                    continue;
+                };
+                exec_path
+            };
+            executables.push(Executable::new(exec_path)?);
+        }
+
+        Ok(executables)
+    }
+
+    /// How many threads per-binary work is spread over: `jobs`, or one per CPU
+    fn threads(&self) -> usize {
+        self.jobs
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+            .max(1)
+    }
+
+    /// Run `op` on every nested binary, spread over the available cores (or `jobs` threads).
+    /// Failures become warnings; returns how many binaries `op` changed.
+    fn for_each_executable<F>(&self, verb: &str, op: F) -> Result<usize>
+    where
+        F: Fn(&Executable) -> Result<bool> + Sync,
+    {
+        self.for_each_of(&self.nested_executables()?, verb, op)
+    }
+
+    /// [`for_each_executable`](Self::for_each_executable) over `executables`
+    fn for_each_of<F>(&self, executables: &[Executable], verb: &str, op: F) -> Result<usize>
+    where
+        F: Fn(&Executable) -> Result<bool> + Sync,
+    {
+        let chunk_size = executables.len().div_ceil(self.threads()).max(1);
+        let count = AtomicUsize::new(0);
+        let (events, op, count_ref) = (&self.events, &op, &count);
+
+        std::thread::scope(|scope| {
+            for chunk in executables.chunks(chunk_size) {
+                scope.spawn(move || {
+                    for exec in chunk {
+                        match op(exec) {
+                            Ok(true) => {
+                                count_ref.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Ok(false) => {}
+                            Err(e) => events.warn(format!(
+                                "couldn't {} {}: {}",
+                                verb,
+                                exec.path.display(),
+                                e
+                            )),
+                        }
+                    }
+                });
+            }
+        });
+
+        Ok(count.into_inner())
+    }
+
+    /// Delete `Frameworks/<name>.framework` for each of `names` and weak-link
+    /// them wherever they're loaded, so the app still launches without them.
+    /// Code that calls into a removed framework without checking for it will
+    /// crash, so this is for SDKs the app can live without (analytics, ads).
+    pub fn remove_frameworks(&mut self, names: &[String]) -> Result<()> {
+        let mut removed = Vec::new();
+        let mut sizes = Vec::new();
+        for name in names {
+            let name = name.strip_suffix(".framework").unwrap_or(name);
+            let path = self.contents("Frameworks").join(format!("{}.framework", name));
+            if !self.fs.is_dir(&path) {
+                self.events.warn(format!("no {}.framework in Frameworks/ to remove", name));
+                continue;
+            }
+            let bytes = disk_size(&path);
+            if self.remove(&[&path]) {
+                removed.push(name.to_string());
+                sizes.push(bytes);
+            }
+        }
+        if removed.is_empty() {
+            return Ok(());
+        }
+
+        // Found again on next use, without the removed frameworks
+        self.executables = OnceLock::new();
+        for (name, bytes) in removed.iter().zip(sizes) {
+            let frameworks = std::slice::from_ref(name);
+            let mut weakened = usize::from(self.executable.inner.weaken_frameworks(frameworks)?);
+            weakened += self.for_each_executable("weak-link", |e| e.weaken_frameworks(frameworks))?;
+            self.events.emit(Event::FrameworkRemoved {
+                name: name.clone(),
+                bytes,
+                weakened,
+            });
+        }
+        Ok(())
+    }
+
+    /// Undo an earlier injection: unlink each of `names` (`Tweak.dylib`,
+    /// `Tweak.framework`, or a full load path like `@rpath/Tweak.dylib`) from every
+    /// binary and delete the file it pointed to
+    pub fn uninject(&mut self, names: &[String]) -> Result<()> {
+        let mut removed = Vec::new();
+        for name in names {
+            let matches = |path: &str| match name.strip_suffix(".framework") {
+                _ if name.contains('/') => path == name,
+                Some(framework) => path.ends_with(&format!("/{0}.framework/{0}", framework)),
+                None => path.ends_with(&format!("/{}", name)),
+            };
+            let paths: Vec<String> = self
+                .executable
+                .inner
+                .get_dependencies()?
+                .into_iter()
+                .filter(|path| matches(path))
+                .collect();
+            if paths.is_empty() {
+                self.events.warn(format!("{} isn't linked from the main binary", name));
+                continue;
+            }
+
+            for path in &paths {
+                self.executable.inner.remove_dylib(path)?;
+                self.for_each_executable("unlink", |e| e.remove_dylib(path))?;
+                match self.injected_file(path) {
+                    Some(file) => {
+                        self.remove(&[&file]);
+                    }
+                    None => self.events.warn(format!("no file for {} in the app", path)),
+                }
+            }
+            removed.push(name.as_str());
+        }
+
+        if !removed.is_empty() {
+            // Found again on next use, without the removed files
+            self.executables = OnceLock::new();
+            self.events.emit(Event::Removed(removed.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// Delete `rpaths` (every copy of each) from the main binary, before
+    /// injection adds back the one it needs
+    pub fn remove_rpaths(&self, rpaths: &[String]) -> Result<()> {
+        let mut removed = Vec::new();
+        for rpath in rpaths {
+            if self.executable.inner.remove_rpath(rpath)? {
+                removed.push(rpath.as_str());
+            } else {
+                self.events.warn(format!("the main binary has no rpath {}", rpath));
+            }
+        }
+        if !removed.is_empty() {
+            self.events.emit(Event::Removed(format!("rpath {}", removed.join(", "))));
+        }
+        Ok(())
+    }
+
+    /// The file or `.framework` a load path of the main binary refers to
+    fn injected_file(&self, load_path: &str) -> Option<PathBuf> {
+        let exec_dir = self.layout.executable_dir(&self.path);
+        let candidates = if let Some(rest) = load_path.strip_prefix("@rpath/") {
+            vec![self.contents("Frameworks").join(rest), exec_dir.join(rest)]
+        } else if let Some(rest) = load_path
+            .strip_prefix("@executable_path/")
+            .or_else(|| load_path.strip_prefix("@loader_path/"))
+        {
+            vec![exec_dir.join(rest)]
+        } else {
+            return None;
+        };
+        let file = candidates.into_iter().find(|c| c.is_file())?;
+        match file.parent() {
+            Some(dir) if dir.extension().is_some_and(|e| e == "framework") => {
+                Some(dir.to_path_buf())
+            }
+            _ => Some(file),
+        }
+    }
+
+    pub fn remove_all_extensions(&self) {
+        let names = ["Extensions", "PlugIns"].map(|name| self.layout.rel(name));
+        if self.remove(&names) {
+            self.events.emit(Event::Removed("app extensions".to_string()));
+        }
+    }
+
+    pub fn remove_encrypted_extensions(&self) -> Result<()> {
+        let mut removed = Vec::new();
+
+        let contents = self.layout.contents_dir(&self.path);
+        let pattern = format!("{}/*/*.appex", contents.display());
+        if let Ok(paths) = self.fs.glob(&pattern) {
+            for plugin_path in paths {
+                if let Ok(bundle) = AppBundle::new(&plugin_path) {
+                    if bundle.executable.is_encrypted().unwrap_or(false)
+                        && self.remove(&[&plugin_path])
+                    {
+                        removed.push(bundle.executable.inner.name);
+                    }
+                }
+            }
+        }
+
+        if !removed.is_empty() {
+            let what = format!("encrypted plugins: {}", removed.join(", "));
+            self.events.emit(Event::Removed(what));
+        }
+
+        Ok(())
+    }
+
+    /// Resolve a path relative to the app to a Mach-O, following bundle
+    /// directories (e.g. `PlugIns/Share.appex`) to their CFBundleExecutable.
+    /// `rel` comes from user configs, so it must stay inside the app.
+    pub fn resolve_executable(&self, rel: &str) -> Result<PathBuf> {
+        if !crate::cyan_config::is_safe_member(rel) {
+            return Err(RuzuleError::InvalidInput(format!(
+                "{} is not a path inside the app",
+                rel
+            )));
+        }
+
+        let path = self.path.join(rel);
+        let path = if self.fs.is_dir(&path) {
+            platform::bundle_executable(&path).ok_or_else(|| {
+                RuzuleError::InvalidAppBundle(format!("No CFBundleExecutable in {}", rel))
+            })?
+        } else {
+            path
+        };
+        if !path.is_file() {
+            return Err(RuzuleError::FileNotFound(path));
+        }
+
+        // Symlinks in the bundle can still point outside it
+        if !path.canonicalize()?.starts_with(self.path.canonicalize()?) {
+            return Err(RuzuleError::InvalidInput(format!(
+                "{} resolves to a file outside the app",
+                rel
+            )));
+        }
+        Ok(path)
+    }
+
+    /// Apply entitlement edits to the binary at `rel` (relative to the app)
+    pub fn edit_entitlements(&self, rel: &str, ops: &EntitlementOps) -> Result<()> {
+        let exec_path = self.resolve_executable(rel)?;
+        Executable::new(exec_path)?
+            .with_events(self.events.clone())
+            .edit_entitlements(ops)
+    }
+
+    /// The main binary's entitlements that ad-hoc signing can't honor ([`ADHOC_RISKY`])
+    ///
+    /// [`ADHOC_RISKY`]: crate::entitlements::ADHOC_RISKY
+    pub fn risky_entitlements(&self) -> Result<Vec<String>> {
+        let data = sign::extract_entitlements(&self.executable.inner.path)?;
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        let current: plist::Dictionary = plist::from_bytes(&data)?;
+        Ok(current
+            .keys()
+            .filter(|k| entitlements::adhoc_risk(k).is_some())
+            .cloned()
+            .collect())
+    }
+
+    /// Remove [`risky_entitlements`](Self::risky_entitlements) from the main binary
+    pub fn strip_risky_entitlements(&self) -> Result<()> {
+        let remove = self.risky_entitlements()?;
+        if remove.is_empty() {
+            return Ok(());
+        }
+        self.events.emit(Event::Notice(format!("removing entitlements {}", remove.join(", "))));
+        let ops = EntitlementOps {
+            remove,
+            ..Default::default()
+        };
+        self.executable.inner.edit_entitlements(&ops)
+    }
+
+    /// Remove `keys` from the main binary's entitlements; keys it doesn't have are skipped
+    pub fn remove_entitlements(&self, keys: &[String]) -> Result<()> {
+        let ops = EntitlementOps {
+            remove: keys.to_vec(),
+            ..Default::default()
+        };
+        self.executable.inner.edit_entitlements(&ops)
+    }
+
+    /// Replace the app icon with a PNG, JPEG, ICO, ICNS, SVG or HEIC image,
+    /// centered on a transparent square if it isn't one. SVG needs
+    /// `rsvg-convert` and HEIC `sips` (macOS) or `heif-convert`.
+    pub fn change_icon<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, icon_path: P, tmpdir: Q) -> Result<()> {
+        if let Some(reason) = self.icons_unsupported() {
+            self.events.warn(format!("{} and can't be replaced", reason));
+            return Ok(());
+        }
+
+        let uid = icon_uid();
+        let (iphone, ipad) = self.save_icon_set(icon_path.as_ref(), tmpdir.as_ref(), &uid)?;
+        let primary = |files: Vec<String>| {
+            let mut d = plist::Dictionary::new();
+            d.insert(
+                "CFBundleIconFiles".to_string(),
+                plist::Value::Array(files.into_iter().map(plist::Value::String).collect()),
+            );
+            d.insert(
+                "CFBundleIconName".to_string(),
+                plist::Value::String(uid.clone()),
+            );
+            plist::Value::Dictionary(d)
+        };
+
+        self.edit_icons("CFBundleIcons", |icons| {
+            icons.insert("CFBundlePrimaryIcon".to_string(), primary(iphone));
+        });
+        self.edit_icons("CFBundleIcons~ipad", |icons| {
+            icons.insert("CFBundlePrimaryIcon".to_string(), primary(ipad));
+        });
+
+        self.plist.save()?;
+        self.events.emit(Event::IconChanged);
+
+        Ok(())
+    }
+
+    /// Add an icon the app can switch to with `setAlternateIconName(name)`,
+    /// rendered from an image like [`change_icon`](Self::change_icon)'s and
+    /// listed under `CFBundleAlternateIcons`. An alternate icon the app already
+    /// has under `name` is replaced.
+    pub fn add_alternate_icon<P: AsRef<Path>, Q: AsRef<Path>>(
+        &mut self,
+        name: &str,
+        icon_path: P,
+        tmpdir: Q,
+    ) -> Result<()> {
+        if let Some(reason) = self.icons_unsupported() {
+            self.events.warn(format!("{}, skipping alternate icon {}", reason, name));
+            return Ok(());
+        }
+
+        let uid = icon_uid();
+        let (iphone, ipad) = self.save_icon_set(icon_path.as_ref(), tmpdir.as_ref(), &uid)?;
+        let alternate = |files: Vec<String>| {
+            let mut d = plist::Dictionary::new();
+            d.insert(
+                "CFBundleIconFiles".to_string(),
+                plist::Value::Array(files.into_iter().map(plist::Value::String).collect()),
+            );
+            plist::Value::Dictionary(d)
+        };
+
+        let mut replaced = false;
+        for (key, files) in [("CFBundleIcons", iphone), ("CFBundleIcons~ipad", ipad)] {
+            self.edit_icons(key, |icons| {
+                let mut alternates = match icons.remove("CFBundleAlternateIcons") {
+                    Some(plist::Value::Dictionary(d)) => d,
+                    _ => plist::Dictionary::new(),
+                };
+                replaced |= alternates.insert(name.to_string(), alternate(files)).is_some();
+                icons.insert(
+                    "CFBundleAlternateIcons".to_string(),
+                    plist::Value::Dictionary(alternates),
+                );
+            });
+        }
+        if replaced {
+            self.events.warn(format!("replaced the app's alternate icon {}", name));
+        }
+
+        self.plist.save()?;
+        self.events.emit(Event::Notice(format!("added alternate icon {}", name)));
+
+        Ok(())
+    }
+
+    /// Why icons can't be set through Info.plist on this platform, if they can't
+    fn icons_unsupported(&self) -> Option<&'static str> {
+        match self.platform {
+            // Layered brand assets in Assets.car, no CFBundleIcons to point elsewhere
+            Platform::Tvos => Some("tvOS icons come from the asset catalog"),
+            Platform::Macos | Platform::MacCatalyst => {
+                Some("macOS icons are .icns files or come from the asset catalog")
+            }
+            // Layered image stacks in Assets.car
+            Platform::Visionos => Some("visionOS icons come from the asset catalog"),
+            Platform::Watchos => Some("watchOS icons come from the asset catalog"),
+            Platform::Ios => None,
+        }
+    }
+
+    /// Get or create the icons dictionary at `key` of Info.plist and edit it
+    fn edit_icons(&mut self, key: &str, edit: impl FnOnce(&mut plist::Dictionary)) {
+        let mut icons = if let Some(plist::Value::Dictionary(d)) = self.plist.get(key) {
+            d.clone()
+        } else {
+            plist::Dictionary::new()
+        };
+        edit(&mut icons);
+        self.plist.set(key, plist::Value::Dictionary(icons));
+    }
+
+    /// Render `icon_path` at every iPhone and iPad size into the bundle, with
+    /// file names starting with `uid`, and return the `CFBundleIconFiles` of the
+    /// iPhone and the iPad layouts
+    fn save_icon_set(
+        &self,
+        icon_path: &Path,
+        tmpdir: &Path,
+        uid: &str,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        // Render each pixel size once, then save it under every name that uses it
+        let pixels = |pt: f32, scale: u32| (pt * scale as f32).round() as u32;
+        let mut sizes: BTreeSet<u32> = BTreeSet::from([MARKETING_ICON]);
+        for (pt, scales) in IPHONE_ICONS.iter().chain(IPAD_ICONS) {
+            sizes.extend(scales.iter().map(|&scale| pixels(*pt, scale)));
+        }
+        let sizes: Vec<u32> = sizes.into_iter().collect();
+        let rendered = icon::render(icon_path, &sizes, tmpdir)?;
+        let image = |size: u32| &rendered[sizes.binary_search(&size).unwrap_or_default()];
+
+        let marketing = format!("{0}{1}x{1}", uid, MARKETING_ICON);
+        image(MARKETING_ICON).save(self.path.join(format!("{}.png", marketing)))?;
+
+        let save_set = |set: &[(f32, &[u32])], idiom: &str| -> Result<Vec<String>> {
+            let mut names = Vec::new();
+            for &(pt, scales) in set {
+                let name = format!("{0}{1}x{1}", uid, pt);
+                for &scale in scales {
+                    let file = match scale {
+                        1 => format!("{}{}.png", name, idiom),
+                        _ => format!("{}@{}x{}.png", name, scale, idiom),
+                    };
+                    image(pixels(pt, scale)).save(self.path.join(file))?;
+                }
+                names.push(name);
+            }
+            Ok(names)
+        };
+        let mut iphone = save_set(IPHONE_ICONS, "")?;
+        let ipad = save_set(IPAD_ICONS, "~ipad")?;
+
+        // iPads also run the iPhone layout, so they list both
+        let mut ipad_files = iphone.clone();
+        ipad_files.extend(ipad.into_iter().filter(|name| !iphone.contains(name)));
+        iphone.push(marketing.clone());
+        ipad_files.push(marketing);
+        Ok((iphone, ipad_files))
+    }
+
+    /// Warns when an injected binary was built only for other platforms than the app
+    fn check_build_platform(&self, name: &str, binary: &Path) {
+        let Ok(platforms) = macho::build_platforms(binary) else {
+            return;
+        };
+        let compatible = |p: Platform| {
+            p == self.platform
+                // zippered frameworks carry the macOS platform and load in Catalyst apps
+                || (self.platform == Platform::MacCatalyst && p == Platform::Macos)
+        };
+        let built_for: Vec<Platform> =
+            platforms.into_iter().filter_map(Platform::from_build_platform).collect();
+        if !built_for.is_empty() && !built_for.iter().copied().any(compatible) {
+            self.events.warn(format!(
+                "{} is built for {} and may not load on {}",
+                name, built_for[0], self.platform
+            ));
+        }
+    }
+
+    /// Refuses a binary with no slice of a CPU type the main executable has, which
+    /// would get injected fine and crash the app at launch
+    fn check_architectures(&self, name: &str, binary: &Path) -> Result<()> {
+        let required = macho::architectures(&self.executable.inner.path)?;
+        let found = macho::architectures(binary)?;
+        if found.iter().any(|(cputype, _)| required.iter().any(|(r, _)| r == cputype)) {
+            return Ok(());
+        }
+        let names = |archs: Vec<(u32, String)>| archs.into_iter().map(|(_, n)| n).collect();
+        Err(RuzuleError::ArchMismatch {
+            name: name.to_string(),
+            found: names(found),
+            required: names(required),
+        })
+    }
+
+    /// Thins an injected binary when [`with_thin_injected`](Self::with_thin_injected)
+    /// is on. Returns whether it dropped any slices.
+    fn thin_injected_binary(&self, name: &str, binary: &Path) -> bool {
+        if !self.thin_injected {
+            return false;
+        }
+        let thinned = macho::architectures(&self.executable.inner.path)
+            .and_then(|archs| macho::thin_to_architectures(binary, &archs));
+        thinned.unwrap_or_else(|e| {
+            self.events.warn(format!("couldn't thin {}: {}", name, e));
+            false
+        })
+    }
+
+    /// Warns when an injected binary refers to jailbreak-only paths, tools or daemons
+    fn check_jailbreak_only(&self, name: &str, binary: &Path) {
+        let Ok(markers) = macho::jailbreak_markers(binary) else {
+            return;
+        };
+        if !markers.is_empty() {
+            self.events.warn(format!(
+                "{} refers to {}, it likely needs a jailbreak and may not work when sideloaded",
+                name,
+                markers.join(", ")
+            ));
+        }
+    }
+
+    /// Copy `bn` to `tmpdir` and fix its load commands. Only the copy is changed,
+    /// so dylibs can be prepared side by side.
+    fn prepare_dylib(
+        &self,
+        bn: &str,
+        path: &Path,
+        tmpdir: &Path,
+        tweaks: &HashMap<String, PathBuf>,
+        use_frameworks_dir: bool,
+        backend: SubstrateBackend,
+    ) -> Result<PreparedDylib> {
+        self.check_architectures(bn, path)?;
+        let temp_path = tmpdir.join(bn);
+        self.fs.copy(path, &temp_path)?;
+        let thinned = self.thin_injected_binary(bn, &temp_path);
+
+        self.check_build_platform(bn, &temp_path);
+        let exec = Executable::new(&temp_path)?.with_events(self.events.clone());
+        let mut needed = HashSet::new();
+        exec.fix_common_dependencies(&mut needed, backend)?;
+        let needed_swift = exec.swift_dependencies()?;
+        exec.fix_dependencies(tweaks)?;
+        if use_frameworks_dir {
+            exec.fix_install_name(tweaks)?;
+        }
+        // After the fixes, so rewritten load commands don't count
+        self.check_jailbreak_only(bn, &temp_path);
+
+        Ok(PreparedDylib {
+            temp_path,
+            needed,
+            needed_swift,
+            thinned,
+        })
+    }
+
+    /// [`prepare_dylib`](Self::prepare_dylib) for every injected .dylib, over
+    /// [`threads`](Self::threads) threads
+    fn prepare_dylibs(
+        &self,
+        tweaks: &HashMap<String, PathBuf>,
+        tmpdir: &Path,
+        use_frameworks_dir: bool,
+        backend: SubstrateBackend,
+    ) -> Result<HashMap<String, PreparedDylib>> {
+        let dylibs: Vec<(&String, &PathBuf)> = tweaks
+            .iter()
+            .filter(|(bn, path)| bn.ends_with(".dylib") && !path.is_symlink())
+            .collect();
+        let chunk_size = dylibs.len().div_ceil(self.threads()).max(1);
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = dylibs
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|(bn, path)| {
+                                let prepared = self.prepare_dylib(
+                                    bn,
+                                    path,
+                                    tmpdir,
+                                    tweaks,
+                                    use_frameworks_dir,
+                                    backend,
+                                )?;
+                                Ok(((*bn).clone(), prepared))
+                            })
+                            .collect::<Vec<Result<_>>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("dylib preparation panicked"))
+                .collect()
+        })
+    }
+
+    /// Copy and link `tweaks` into the app. Returns the name and minimum OS
+    /// version of each injected dylib and framework (bundled ones included)
+    /// that declares one.
+    ///
+    /// Load commands go into the main binary, or into the binary at `inject_into`
+    /// (relative to the app, e.g. `Frameworks/SDK.framework`), which is then fakesigned.
+    pub fn inject(
+        &self,
+        tweaks: &mut HashMap<String, PathBuf>,
+        tmpdir: &Path,
+        use_frameworks_dir: bool,
+        backend: SubstrateBackend,
+        swift_libs: Option<&Path>,
+        inject_into: Option<&str>,
+    ) -> Result<Vec<(String, Version)>> {
+        let ent_path = self.path.join("ruzule.entitlements");
+        let plugins_dir = self.contents("PlugIns");
+        let frameworks_dir = self.contents("Frameworks");
+        let exec_dir = self.layout.executable_dir(&self.path);
+        let resources_dir = self.layout.resources_dir(&self.path);
+
+        let has_entitlements = self.executable.write_entitlements(&ent_path)?;
+
+        // Remove signature before injecting
+        self.executable.inner.remove_signature()?;
+        let target = match inject_into {
+            Some(rel) => {
+                let target = Executable::new(self.resolve_executable(rel)?)?
+                    .with_events(self.events.clone());
+                target.remove_signature()?;
+                Some(target)
+            }
+            None => None,
+        };
+        let link = |inject_path: &str| match target {
+            Some(ref target) => target.inject_dylib(inject_path),
+            None => self.executable.inject_dylib(inject_path),
+        };
+
+        // Create directories if needed
+        let has_appex = tweaks.keys().any(|k| k.ends_with(".appex"));
+        let has_injectable = tweaks
+            .keys()
+            .any(|k| k.ends_with(".deb") || k.ends_with(".dylib") || k.ends_with(".framework"));
+
+        if has_appex {
+            self.fs.create_dir_all(&plugins_dir)?;
+        }
+
+        if has_injectable && use_frameworks_dir {
+            self.fs.create_dir_all(&frameworks_dir)?;
+            self.executable.add_rpath(self.layout.frameworks_rpath())?;
+        }
+
+        // Extract .deb files first (modifies tweaks)
+        let deb_keys: Vec<String> = tweaks
+            .keys()
+            .filter(|k| k.ends_with(".deb"))
+            .cloned()
+            .collect();
+
+        for deb_name in deb_keys {
+            if let Some(deb_path) = tweaks.get(&deb_name).cloned() {
+                deb::extract_deb(&deb_path, tweaks, tmpdir, &self.events)?;
+            }
+        }
+
+        let mut needed: HashSet<String> = HashSet::new();
+        let mut needed_swift: BTreeSet<String> = BTreeSet::new();
+        let mut min_os = Vec::new();
+        let mut thinned = 0;
+        let mut written: Vec<PathBuf> = Vec::new();
+        let mut record_min_os = |name: &str, binary: &Path| {
+            if let Ok(Some(version)) = macho::min_os_version(binary) {
+                min_os.push((name.to_string(), version));
+            }
+        };
+
+        // Dylibs are independent until they're linked into the main binary
+        let tweaks: &HashMap<String, PathBuf> = tweaks;
+        let mut prepared = self.prepare_dylibs(tweaks, tmpdir, use_frameworks_dir, backend)?;
+
+        // Process each tweak
+        for (bn, path) in tweaks.iter() {
+            // Skip symlinks
+            if path.is_symlink() {
+                continue;
+            }
+
+            if bn.ends_with(".appex") {
+                let fpath = plugins_dir.join(bn);
+                delete_if_exists(&*self.fs, &fpath, bn, &self.events);
+                self.fs.copy_dir(path, &fpath)?;
+                written.push(fpath.clone());
+                scan_bundle_dependencies(
+                    &fpath,
+                    &mut needed,
+                    &mut needed_swift,
+                    backend,
+                    &self.events,
+                )?;
+                self.events.emit(Event::Injected(bn.clone()));
+            } else if let Some(dylib) = prepared.remove(bn) {
+                // Prepared in temp, link and move to destination
+                needed.extend(dylib.needed);
+                needed_swift.extend(dylib.needed_swift);
+                thinned += usize::from(dylib.thinned);
+                let temp_path = dylib.temp_path;
+                record_min_os(bn, &temp_path);
+
+                let (fpath, inject_path) = if use_frameworks_dir {
+                    (frameworks_dir.join(bn), format!("@rpath/{}", bn))
+                } else {
+                    (exec_dir.join(bn), format!("@executable_path/{}", bn))
+                };
+                delete_if_exists(&*self.fs, &fpath, bn, &self.events);
+
+                link(&inject_path)?;
+                self.fs.rename(&temp_path, &fpath)?;
+                written.push(fpath);
+                self.events.emit(Event::Injected(bn.clone()));
+            } else if bn.ends_with(".framework") {
+                let framework_name = bn.strip_suffix(".framework").unwrap();
+                if let Some(binary) = platform::bundle_executable(path) {
+                    self.check_build_platform(bn, &binary);
+                    record_min_os(bn, &binary);
+                }
+                let (fpath, inject_path) = if use_frameworks_dir {
+                    (frameworks_dir.join(bn), format!("@rpath/{}/{}", bn, framework_name))
+                } else {
+                    (exec_dir.join(bn), format!("@executable_path/{}/{}", bn, framework_name))
+                };
+                delete_if_exists(&*self.fs, &fpath, bn, &self.events);
+
+                link(&inject_path)?;
+                self.fs.copy_dir(path, &fpath)?;
+                written.push(fpath.clone());
+                scan_bundle_dependencies(
+                    &fpath,
+                    &mut needed,
+                    &mut needed_swift,
+                    backend,
+                    &self.events,
+                )?;
+                if let Some(binary) = platform::bundle_executable(&fpath) {
+                    thinned += usize::from(self.thin_injected_binary(bn, &binary));
+                    self.check_jailbreak_only(bn, &binary);
+                }
+                self.events.emit(Event::Injected(bn.clone()));
+            } else if bn.ends_with(".lproj") && self.fs.is_dir(path) {
+                // Merged into the app's own .lproj so its other strings files stay
+                let fpath = resources_dir.join(bn);
+                self.fs.create_dir_all(&fpath)?;
+                for src in self.fs.glob(&format!("{}/*", path.display()))? {
+                    let Some(name) = src.file_name() else {
+                        continue;
+                    };
+                    let dest = fpath.join(name);
+                    let label = format!("{}/{}", bn, name.to_string_lossy());
+                    delete_if_exists(&*self.fs, &dest, &label, &self.events);
+                    if self.fs.is_dir(&src) {
+                        self.fs.copy_dir(&src, &dest)?;
+                    } else {
+                        self.fs.copy(&src, &dest)?;
+                    }
+                }
+                self.events.emit(Event::Injected(bn.clone()));
+            } else if bn.ends_with(".bundle") {
+                let fpath = resources_dir.join(bn);
+                delete_if_exists(&*self.fs, &fpath, bn, &self.events);
+                self.fs.copy_dir(path, &fpath)?;
+                written.push(fpath);
+                self.events.emit(Event::Injected(bn.clone()));
+            } else {
+                // Unknown file type, copy to app root (Resources/ on macOS)
+                let fpath = resources_dir.join(bn);
+                delete_if_exists(&*self.fs, &fpath, bn, &self.events);
+                if self.fs.is_dir(path) {
+                    self.fs.copy_dir(path, &fpath)?;
+                } else {
+                    self.fs.copy(path, &fpath)?;
+                }
+                written.push(fpath);
+                self.events.emit(Event::Injected(bn.clone()));
+            }
+        }
+
+        // Orion has a weak dependency to substrate
+        if needed.contains("orion.") {
+            needed.insert("substrate.".to_string());
+        }
+
+        // Auto-inject needed common dependencies (ElleKit, etc.)
+        for missing in &needed {
+            if let Some(framework) = crate::frameworks::get_framework_for_dep(missing) {
+                let name = if missing == "substrate." {
+                    match backend.framework_name() {
+                        Some(name) => name,
+                        None => {
+                            if !tweaks.keys().any(|k| k == "libsubstrate.dylib") {
+                                self.events.emit(Event::Warning(
+                                    "libhooker backend selected but libsubstrate.dylib \
+                                     was not injected"
+                                        .to_string(),
+                                ));
+                            }
+                            continue;
+                        }
+                    }
+                } else {
+                    framework.name
+                };
+
+                let framework_name = format!("{}.framework", name);
+                let dest_dir = if use_frameworks_dir { &frameworks_dir } else { &exec_dir };
+                let fpath = dest_dir.join(&framework_name);
+
+                if !delete_if_exists(&*self.fs, &fpath, &framework_name, &self.events) {
+                    self.events.emit(Event::AutoInjected(framework_name.clone()));
+                }
+                if !self.platform.is_ios() {
+                    self.events.warn(format!(
+                        "bundled {} is built for iOS and may not load on {}",
+                        framework_name, self.platform
+                    ));
+                }
+
+                framework.extract_as(dest_dir, name, &self.events)?;
+                written.push(fpath.clone());
+                let binary = fpath.join(name);
+                record_min_os(&framework_name, &binary);
+                thinned += usize::from(self.thin_injected_binary(&framework_name, &binary));
+
+                // Bundled frameworks (e.g. Orion) link the default substrate path
+                if backend != SubstrateBackend::Substrate {
+                    macho::replace_dylib(
+                        &binary,
+                        SubstrateBackend::Substrate.install_path(),
+                        backend.install_path(),
+                    )?;
+                }
+            }
+        }
+
+        if thinned > 0 {
+            self.events.emit(Event::Thinned(thinned));
+        }
+
+        // Without the executable bit, launching fails with "operation not permitted"
+        let mut made_executable = 0;
+        for path in &written {
+            made_executable += macho::make_executable(path)?;
+        }
+        if made_executable > 0 {
+            log::debug!("added the executable bit to {} injected binaries", made_executable);
+        }
+
+        if !needed_swift.is_empty() {
+            self.bundle_swift_libs(needed_swift, tweaks, swift_libs)?;
+        }
+
+        if let Some(ref target) = target {
+            target.fakesign()?;
+        }
+
+        // Restore entitlements
+        if has_entitlements {
+            self.executable.sign_with_entitlements(&ent_path)?;
+            self.events.emit(Event::EntitlementsRestored);
+            self.fs.remove(&ent_path)?;
+        }
+
+        Ok(min_os)
+    }
+
+    /// Copy `@rpath/libswift*` libraries the injected tweaks need into Frameworks/,
+    /// unless the app (or the tweak list) already provides them
+    fn bundle_swift_libs(
+        &self,
+        mut pending: BTreeSet<String>,
+        tweaks: &HashMap<String, PathBuf>,
+        swift_libs: Option<&Path>,
+    ) -> Result<()> {
+        let frameworks_dir = self.contents("Frameworks");
+        let dirs = crate::swift::search_dirs(swift_libs);
+        let mut seen: HashSet<String> = HashSet::new();
+
+        while let Some(name) = pending.pop_first() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+
+            let dest = frameworks_dir.join(&name);
+            if self.fs.exists(&dest) || tweaks.contains_key(&name) {
+                continue;
+            }
+
+            let Some(src) = crate::swift::find_lib(&name, &dirs) else {
+                self.events.emit(Event::Notice(format!(
+                    "{} is needed by an injected tweak but was not found \
+                     (ruzule doesn't ship it), pass --swift-libs",
+                    name
+                )));
+                continue;
+            };
+
+            self.fs.create_dir_all(&frameworks_dir)?;
+            self.executable.add_rpath(self.layout.frameworks_rpath())?;
+            self.fs.copy(&src, &dest)?;
+            self.events.emit(Event::AutoInjected(name.clone()));
+
+            pending.extend(Executable::new(&dest)?.swift_dependencies()?);
+        }
+
+        Ok(())
+    }
+
+    /// Patch the main executable and all plugins to fix share sheet, widgets, VPNs, etc.
+    /// Injects zxPluginsInject.dylib (or a custom `dylib`) into all executables.
+    pub fn patch_plugins(&self, dylib: Option<&Path>) -> Result<()> {
+        let count = match dylib {
+            Some(path) => {
+                let name = path
+                    .file_name()
+                    .ok_or_else(|| RuzuleError::InvalidInput(format!("{}", path.display())))?
+                    .to_string_lossy()
+                    .into_owned();
+                self.inject_into_all(&name, &self.fs.read(path)?)?
+            }
+            None => {
+                if !self.platform.is_ios() {
+                    self.events.warn(format!(
+                        "zxPluginsInject.dylib is built for iOS and may not load on {}",
+                        self.platform
+                    ));
+                }
+                self.inject_into_all(
+                    "zxPluginsInject.dylib",
+                    crate::frameworks::zx_plugins_inject()?,
+                )?
+            }
+        };
+
+        self.events.emit(Event::PluginsPatched(count));
+        Ok(())
+    }
+
+    /// Write `data` to Frameworks/`name` and weakly link `@rpath/name` from the
+    /// main executable and every app extension.
+    /// Returns the number of executables patched.
+    pub fn inject_into_all(&self, name: &str, data: &[u8]) -> Result<usize> {
+        // Ensure Frameworks directory exists
+        let frameworks_dir = self.contents("Frameworks");
+        self.fs.create_dir_all(&frameworks_dir)?;
+        self.fs.write(&frameworks_dir.join(name), data)?;
+
+        // Add rpath if needed
+        self.executable.add_rpath(self.layout.frameworks_rpath())?;
+
+        // Inject into main executable
+        let inject_path = format!("@rpath/{}", name);
+        self.executable.inject_dylib(&inject_path)?;
+        sign::fakesign(&self.executable.inner.path)?;
+
+        let mut count = 1; // main executable
+
+        // Plugins usually live in PlugIns/, some apps also use Extensions/
+        for dir in ["PlugIns", "Extensions"] {
+            let pattern = format!("{}/*.appex", self.contents(dir).display());
+            for path in self.fs.glob(&pattern)? {
+                let Some(exec_path) = platform::bundle_executable(&path) else {
+                    continue;
+                };
+                if !exec_path.exists() {
+                    continue;
+                }
+                let injected = Executable::new(&exec_path)
+                    .map(|e| e.with_events(self.events.clone()))
+                    .and_then(|e| e.inject_dylib(&inject_path));
+                match injected {
+                    Ok(()) => {
+                        sign::fakesign(&exec_path)?;
+                        count += 1;
+                    }
+                    Err(e) => self.events.warn(format!(
+                        "couldn't inject {} into {}: {}",
+                        name,
+                        exec_path.display(),
+                        e
+                    )),
+                }
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+/// Fix common dependencies of an injected bundle's (.framework/.appex) own executable
+fn scan_bundle_dependencies(
+    bundle: &Path,
+    needed: &mut HashSet<String>,
+    needed_swift: &mut BTreeSet<String>,
+    backend: SubstrateBackend,
+    events: &Events,
+) -> Result<()> {
+    let exec_path = platform::bundle_executable(bundle)
+        .or_else(|| bundle.file_stem().map(|s| bundle.join(s)));
+
+    let Some(exec_path) = exec_path else {
+        return Ok(());
+    };
+    if !exec_path.is_file() {
+        return Ok(());
+    }
+
+    let exec = Executable::new(&exec_path)?.with_events(events.clone());
+    exec.fix_common_dependencies(needed, backend)?;
+    needed_swift.extend(exec.swift_dependencies()?);
+
+    // Helper dylibs shipped inside the bundle link substrate too
+    let dylibs = walkdir::WalkDir::new(bundle)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|x| x == "dylib"));
+    for entry in dylibs {
+        let exec = Executable::new(entry.path())?.with_events(events.clone());
+        exec.fix_common_dependencies(needed, backend)?;
+    }
+    Ok(())
+}
+
+fn delete_if_exists(fs: &dyn Fs, path: &Path, bn: &str, events: &Events) -> bool {
+    if fs.exists(path) {
+        match fs.remove(path) {
+            Ok(()) => {
+                events.emit(Event::Replacing(bn.to_string()));
+                return true;
+            }
+            Err(e) => events.warn(format!("couldn't remove existing {}: {}", bn, e)),
+        }
+    }
+    false
+}
@@ -0,0 +1,1033 @@
+//! The pure parts of ruzule, on byte buffers.
+//!
+//! Nothing here touches the filesystem, spawns processes or needs a temp
+//! directory, so it can back tools that don't have one (e.g. an in-browser
+//! .cyan inspector built for wasm32). The path-based functions in
+//! [`macho`](crate::macho), [`plist_ext`](crate::plist_ext) and
+//! [`cyan_config`](crate::cyan_config) read or map the file, call into this
+//! module and write the result back.
+
+use crate::cyan_config::{self, CyanConfig, CyanSummary};
+use crate::error::{Result, RuzuleError};
+use crate::version::Version;
+#[cfg(feature = "native")]
+use apple_codesign::{MachFile, UniversalBinaryBuilder};
+use goblin::mach::cputype::{get_arch_name_from_types, CPU_TYPE_ARM64, CPU_TYPE_X86_64};
+use goblin::mach::load_command::{
+    CommandVariant, LC_DATA_IN_CODE, LC_DYLIB_CODE_SIGN_DRS, LC_FUNCTION_STARTS, LC_ID_DYLIB,
+    LC_LAZY_LOAD_DYLIB, LC_LINKER_OPTIMIZATION_HINT, LC_LOAD_DYLIB, LC_LOAD_UPWARD_DYLIB,
+    LC_LOAD_WEAK_DYLIB, LC_REEXPORT_DYLIB, LC_RPATH, LC_SEGMENT_SPLIT_INFO, LC_SOURCE_VERSION,
+};
+use goblin::mach::{Mach, MachO};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Cursor;
+#[cfg(feature = "native")]
+use std::io::Write;
+use std::ops::Range;
+use std::str::FromStr;
+
+const DYLIB_COMMANDS: &[u32] = &[
+    LC_LOAD_DYLIB,
+    LC_LOAD_WEAK_DYLIB,
+    LC_REEXPORT_DYLIB,
+    LC_LAZY_LOAD_DYLIB,
+    LC_LOAD_UPWARD_DYLIB,
+];
+
+/// Load commands dyld doesn't read when launching an app, only the linker,
+/// debuggers and the shared cache builder do. When a new command doesn't fit
+/// in the header padding, these are dropped, in this order, until it does.
+///
+/// `LC_CODE_SIGNATURE` is kept: signing rewrites it in place, but has to add
+/// one back if it's missing, which needs as much room as dropping it freed.
+const DISPENSABLE_COMMANDS: &[(u32, &str)] = &[
+    (LC_SOURCE_VERSION, "LC_SOURCE_VERSION"),
+    (LC_DYLIB_CODE_SIGN_DRS, "LC_DYLIB_CODE_SIGN_DRS"),
+    (LC_SEGMENT_SPLIT_INFO, "LC_SEGMENT_SPLIT_INFO"),
+    (LC_LINKER_OPTIMIZATION_HINT, "LC_LINKER_OPTIMIZATION_HINT"),
+    (LC_DATA_IN_CODE, "LC_DATA_IN_CODE"),
+    (LC_FUNCTION_STARTS, "LC_FUNCTION_STARTS"),
+];
+
+/// Bytes to write at an offset into a binary.
+///
+/// Load command edits never change a binary's size, so they're worked out
+/// first and then written over the original, in a copy or in place in a
+/// mapped file, without holding a second copy of the whole binary.
+pub(crate) type Patch = (usize, Vec<u8>);
+
+/// Whether `data` starts like a Mach-O or fat binary, without parsing it
+pub fn is_macho(data: &[u8]) -> bool {
+    matches!(
+        data.get(..4),
+        // MH_MAGIC_64, MH_MAGIC (little-endian), FAT_MAGIC, FAT_MAGIC_64 (big-endian)
+        Some([0xcf, 0xfa, 0xed, 0xfe] | [0xce, 0xfa, 0xed, 0xfe])
+            | Some([0xca, 0xfe, 0xba, 0xbe] | [0xca, 0xfe, 0xba, 0xbf])
+    )
+}
+
+pub fn is_encrypted(data: &[u8]) -> Result<bool> {
+    match Mach::parse(data)? {
+        Mach::Binary(macho) => Ok(check_encrypted(&macho)),
+        Mach::Fat(fat) => {
+            for arch in fat.iter_arches() {
+                let arch = arch?;
+                let slice = &data[arch.offset as usize..(arch.offset + arch.size) as usize];
+                if let Ok(macho) = MachO::parse(slice, 0) {
+                    if check_encrypted(&macho) {
+                        return Ok(true);
+                    }
+                }
+            }
+            Ok(false)
+        }
+    }
+}
+
+fn check_encrypted(macho: &MachO) -> bool {
+    macho.load_commands.iter().any(|cmd| match cmd.command {
+        CommandVariant::EncryptionInfo32(info) => info.cryptid != 0,
+        CommandVariant::EncryptionInfo64(info) => info.cryptid != 0,
+        _ => false,
+    })
+}
+
+/// Install roots of rootless (`/var/jb`) and roothide (`.jbroot`) jailbreaks
+const JAILBREAK_ROOTS: &[&str] = &[
+    "/var/jb",
+    "/private/var/jb",
+    "@loader_path/.jbroot",
+    "@executable_path/.jbroot",
+];
+
+/// Strip a rootless/roothide prefix, so that
+/// `/var/jb/Library/Frameworks/X.framework/X` matches like `/Library/Frameworks/X.framework/X`
+pub fn strip_jailbreak_root(dep: &str) -> &str {
+    for root in JAILBREAK_ROOTS {
+        if let Some(rest) = dep.strip_prefix(root) {
+            if rest.starts_with('/') {
+                return rest;
+            }
+        }
+    }
+    dep
+}
+
+/// Linked libraries that could be bundled or rewritten (jailbreak paths and `@` paths)
+pub fn dependencies(data: &[u8]) -> Result<Vec<String>> {
+    let mut deps = Vec::new();
+
+    match Mach::parse(data)? {
+        Mach::Binary(macho) => collect_deps(&macho, &mut deps),
+        Mach::Fat(fat) => {
+            for arch in fat.iter_arches() {
+                let arch = arch?;
+                let slice = &data[arch.offset as usize..(arch.offset + arch.size) as usize];
+                if let Ok(macho) = MachO::parse(slice, 0) {
+                    collect_deps(&macho, &mut deps);
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(deps
+        .into_iter()
+        .filter(|d| {
+            let d = strip_jailbreak_root(d);
+            d.starts_with("/Library/") || d.starts_with("/usr/lib/") || d.starts_with('@')
+        })
+        .collect())
+}
+
+fn collect_deps(macho: &MachO, deps: &mut Vec<String>) {
+    for lib in &macho.libs {
+        if !lib.is_empty() {
+            deps.push(lib.to_string());
+        }
+    }
+}
+
+/// How a dylib load command links its library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// `LC_LOAD_DYLIB`
+    Load,
+    /// `LC_LOAD_WEAK_DYLIB`, may be missing at launch
+    Weak,
+    /// `LC_REEXPORT_DYLIB`
+    Reexport,
+    /// `LC_LAZY_LOAD_DYLIB`
+    Lazy,
+    /// `LC_LOAD_UPWARD_DYLIB`
+    Upward,
+}
+
+/// One dylib load command, as `otool -L` lists it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LinkedDylib {
+    pub path: String,
+    pub kind: LinkKind,
+    pub compatibility_version: String,
+    pub current_version: String,
+}
+
+/// A binary's install name, the libraries it links and its rpaths, from the first slice
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Linkage {
+    /// `LC_ID_DYLIB`, for dylibs and frameworks
+    pub install_name: Option<String>,
+    /// Every dylib load command, system libraries included, in load order
+    pub dylibs: Vec<LinkedDylib>,
+    /// `LC_RPATH` entries, searched in order for `@rpath/` libraries
+    pub rpaths: Vec<String>,
+}
+
+pub fn linkage(data: &[u8]) -> Result<Linkage> {
+    for range in slices(data)? {
+        let Some(slice) = data.get(range) else {
+            continue;
+        };
+        let Ok(macho) = MachO::parse(slice, 0) else {
+            continue;
+        };
+
+        let mut linkage = Linkage::default();
+        let commands = [&[LC_ID_DYLIB, LC_RPATH][..], DYLIB_COMMANDS].concat();
+        for (offset, name) in command_names(slice, &macho, &commands) {
+            let cmd = read_u32(slice, offset)?;
+            let kind = match cmd {
+                LC_ID_DYLIB => {
+                    linkage.install_name = Some(name.to_string());
+                    continue;
+                }
+                LC_RPATH => {
+                    linkage.rpaths.push(name.to_string());
+                    continue;
+                }
+                LC_LOAD_WEAK_DYLIB => LinkKind::Weak,
+                LC_REEXPORT_DYLIB => LinkKind::Reexport,
+                LC_LAZY_LOAD_DYLIB => LinkKind::Lazy,
+                LC_LOAD_UPWARD_DYLIB => LinkKind::Upward,
+                _ => LinkKind::Load,
+            };
+            linkage.dylibs.push(LinkedDylib {
+                path: name.to_string(),
+                kind,
+                current_version: dylib_version(read_u32(slice, offset + 16)?),
+                compatibility_version: dylib_version(read_u32(slice, offset + 20)?),
+            });
+        }
+        return Ok(linkage);
+    }
+    Err(RuzuleError::MachO("No readable slice".to_string()))
+}
+
+/// A dylib command's packed `xxxx.yy.zz` version
+fn dylib_version(packed: u32) -> String {
+    format!("{}.{}.{}", packed >> 16, (packed >> 8) & 0xff, packed & 0xff)
+}
+
+/// Whether any slice carries embedded bitcode (an `__LLVM` segment)
+pub fn has_bitcode(data: &[u8]) -> Result<bool> {
+    let is_llvm = |name: &[u8; 16]| name.starts_with(b"__LLVM\0");
+    for range in slices(data)? {
+        let slice = data.get(range).ok_or_else(|| {
+            RuzuleError::MachO("Slice extends past the end of the file".to_string())
+        })?;
+        let macho = MachO::parse(slice, 0)?;
+        let found = macho.load_commands.iter().any(|cmd| match &cmd.command {
+            CommandVariant::Segment64(seg) => is_llvm(&seg.segname),
+            CommandVariant::Segment32(seg) => is_llvm(&seg.segname),
+            _ => false,
+        });
+        if found {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// CPU type and name (`arm64`, `armv7`, `x86_64`...) of each slice
+pub fn architectures(data: &[u8]) -> Result<Vec<(u32, String)>> {
+    let name = |cputype: u32, subtype: u32| {
+        get_arch_name_from_types(cputype, subtype)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("cputype {:#x}", cputype))
+    };
+    match Mach::parse(data)? {
+        Mach::Binary(macho) => {
+            let header = macho.header;
+            Ok(vec![(header.cputype(), name(header.cputype(), header.cpusubtype()))])
+        }
+        Mach::Fat(fat) => fat
+            .iter_arches()
+            .map(|arch| {
+                let arch = arch?;
+                Ok((arch.cputype(), name(arch.cputype(), arch.cpusubtype())))
+            })
+            .collect(),
+    }
+}
+
+/// The `LC_BUILD_VERSION` platform of every slice (`PLATFORM_IOS` = 2, `PLATFORM_XROS` = 11, ...),
+/// falling back to the older `LC_VERSION_MIN_*` commands. Slices that say neither are skipped.
+pub fn build_platforms(data: &[u8]) -> Result<Vec<u32>> {
+    let mut platforms: Vec<u32> = build_versions(data)?.into_iter().map(|(p, _)| p).collect();
+    platforms.dedup();
+    Ok(platforms)
+}
+
+/// The highest OS version any slice requires (`minos`)
+pub fn min_os_version(data: &[u8]) -> Result<Option<Version>> {
+    let highest = build_versions(data)?.into_iter().map(|(_, minos)| minos).max();
+    Ok(highest.map(Version::from_packed))
+}
+
+/// Set `minos` in every slice's build load command (the code signature is left stale)
+pub fn set_min_os_version(data: &[u8], version: Version) -> Result<Vec<u8>> {
+    Ok(patched(data, &min_os_patches(data, version)?))
+}
+
+pub(crate) fn min_os_patches(data: &[u8], version: Version) -> Result<Vec<Patch>> {
+    patch_slices(data, |_, macho| {
+        let bytes = if macho.little_endian {
+            version.to_packed().to_le_bytes()
+        } else {
+            version.to_packed().to_be_bytes()
+        };
+        let patches = macho.load_commands.iter().filter_map(|cmd| {
+            // minos follows cmd, cmdsize and platform; version follows cmd and cmdsize
+            let field = match cmd.command {
+                CommandVariant::BuildVersion(_) => 12,
+                CommandVariant::VersionMinMacosx(_)
+                | CommandVariant::VersionMinIphoneos(_)
+                | CommandVariant::VersionMinTvos(_)
+                | CommandVariant::VersionMinWatchos(_) => 8,
+                _ => return None,
+            };
+            Some((cmd.offset + field, bytes.to_vec()))
+        });
+        Ok(patches.collect())
+    })
+}
+
+/// `(platform, minos)` of every slice that has a build or version-min load command
+fn build_versions(data: &[u8]) -> Result<Vec<(u32, u32)>> {
+    let mut versions = Vec::new();
+
+    match Mach::parse(data)? {
+        Mach::Binary(macho) => versions.extend(build_version(&macho)),
+        Mach::Fat(fat) => {
+            for arch in fat.iter_arches() {
+                let arch = arch?;
+                let slice = &data[arch.offset as usize..(arch.offset + arch.size) as usize];
+                if let Ok(macho) = MachO::parse(slice, 0) {
+                    versions.extend(build_version(&macho));
+                }
+            }
+        }
+    }
+
+    Ok(versions)
+}
+
+fn build_version(macho: &MachO) -> Option<(u32, u32)> {
+    macho.load_commands.iter().find_map(|cmd| match cmd.command {
+        CommandVariant::BuildVersion(build) => Some((build.platform, build.minos)),
+        CommandVariant::VersionMinMacosx(min) => Some((1, min.version)),
+        CommandVariant::VersionMinIphoneos(min) => Some((2, min.version)),
+        CommandVariant::VersionMinTvos(min) => Some((3, min.version)),
+        CommandVariant::VersionMinWatchos(min) => Some((4, min.version)),
+        _ => None,
+    })
+}
+
+/// Strings in a binary that only mean something on a jailbroken device
+const JAILBREAK_MARKERS: &[&str] = &[
+    "/var/jb/",
+    "/.jbroot",
+    "substrated",
+    "launchctl",
+    "/usr/libexec/",
+    "libjailbreak",
+    "jbctl",
+    "/.bootstrapped",
+];
+
+/// Which jailbreak-only paths, tools and daemons `data` refers to, e.g. `/var/jb/` or `launchctl`
+pub fn jailbreak_markers(data: &[u8]) -> Vec<&'static str> {
+    JAILBREAK_MARKERS
+        .iter()
+        .copied()
+        .filter(|marker| data.windows(marker.len()).any(|w| w == marker.as_bytes()))
+        .collect()
+}
+
+/// Weakly link `dylib_path`. Also returns the load commands dropped to make
+/// room for it (see [`add_rpath`]).
+pub fn add_weak_dylib(data: &[u8], dylib_path: &str) -> Result<(Vec<u8>, Vec<&'static str>)> {
+    let mut dropped = Vec::new();
+    let patches = add_weak_dylib_patches(data, dylib_path, &mut dropped)?;
+    Ok((patched(data, &patches), dropped))
+}
+
+pub(crate) fn add_weak_dylib_patches(
+    data: &[u8],
+    dylib_path: &str,
+    dropped: &mut Vec<&'static str>,
+) -> Result<Vec<Patch>> {
+    patch_slices(data, |slice, macho| {
+        if command_names(slice, macho, DYLIB_COMMANDS).any(|(_, name)| name == dylib_path) {
+            log::warn!("dylib already exists in binary: {}", dylib_path);
+            return Ok(Vec::new());
+        }
+        // name offset, timestamp, current and compatibility version
+        let fields = [24, 2, 0x10000, 0x10000];
+        let command = load_command(LC_LOAD_WEAK_DYLIB, &fields, dylib_path);
+        append_load_command(slice, macho, "load command", command, dropped)
+    })
+}
+
+pub fn replace_dylib(data: &[u8], old_path: &str, new_path: &str) -> Result<Vec<u8>> {
+    Ok(patched(data, &replace_dylib_patches(data, old_path, new_path)?))
+}
+
+pub(crate) fn replace_dylib_patches(
+    data: &[u8],
+    old_path: &str,
+    new_path: &str,
+) -> Result<Vec<Patch>> {
+    patch_slices(data, |slice, macho| {
+        let offsets = command_names(slice, macho, DYLIB_COMMANDS)
+            .filter(|(_, name)| *name == old_path)
+            .map(|(offset, _)| offset);
+        rename_commands(slice, offsets, "dylib path", new_path)
+    })
+}
+
+/// Delete the `LC_LOAD_DYLIB` and `LC_LOAD_WEAK_DYLIB` commands for `dylib_path`,
+/// moving the load commands after them up
+pub fn remove_dylib(data: &[u8], dylib_path: &str) -> Result<Vec<u8>> {
+    Ok(patched(data, &remove_dylib_patches(data, dylib_path)?))
+}
+
+pub(crate) fn remove_dylib_patches(data: &[u8], dylib_path: &str) -> Result<Vec<Patch>> {
+    patch_slices(data, |slice, macho| {
+        let removed: Vec<usize> = command_names(slice, macho, &[LC_LOAD_DYLIB, LC_LOAD_WEAK_DYLIB])
+            .filter(|(_, name)| *name == dylib_path)
+            .map(|(offset, _)| offset)
+            .collect();
+        let Some(&first) = removed.first() else {
+            return Ok(Vec::new());
+        };
+
+        // Symbols are bound through a library's position among the dylib
+        // commands, so only one nothing is bound through, followed by no other
+        // dylib command, can go (an injected tweak, which comes last)
+        let ordinals: Vec<u16> = macho
+            .load_commands
+            .iter()
+            .filter(|cmd| DYLIB_COMMANDS.contains(&cmd.command.cmd()))
+            .zip(1..)
+            .filter(|(cmd, _)| removed.contains(&cmd.offset))
+            .map(|(_, ordinal)| ordinal)
+            .collect();
+        let bound = macho
+            .imports()
+            .is_ok_and(|imports| imports.iter().any(|import| import.dylib == dylib_path))
+            || chained_import_ordinals(slice, macho)?
+                .iter()
+                .any(|ordinal| ordinals.contains(ordinal));
+        let dylib_after = macho.load_commands.iter().any(|cmd| {
+            cmd.offset > first
+                && !removed.contains(&cmd.offset)
+                && DYLIB_COMMANDS.contains(&cmd.command.cmd())
+        });
+        if bound || dylib_after {
+            return Err(RuzuleError::MachO(format!(
+                "can't remove {}: the binary may bind symbols through it",
+                dylib_path
+            )));
+        }
+
+        rewrite_commands(slice, macho, &removed, &[])
+    })
+}
+
+/// Library ordinals of the imports in `LC_DYLD_CHAINED_FIXUPS`, which goblin's
+/// `imports()` doesn't read. Special ordinals (self, main executable, flat and
+/// weak lookups) are left out.
+fn chained_import_ordinals(slice: &[u8], macho: &MachO) -> Result<Vec<u16>> {
+    let Some(fixups) = macho.load_commands.iter().find_map(|cmd| match cmd.command {
+        CommandVariant::DyldChainedFixups(fixups) => Some(fixups),
+        _ => None,
+    }) else {
+        return Ok(Vec::new());
+    };
+
+    // dyld_chained_fixups_header
+    let header = fixups.dataoff as usize;
+    let imports_offset = read_u32(slice, header + 8)? as usize;
+    let imports_count = read_u32(slice, header + 16)? as usize;
+    let imports_format = read_u32(slice, header + 20)?;
+    let imports = header + imports_offset;
+
+    let mut ordinals = Vec::with_capacity(imports_count);
+    for i in 0..imports_count {
+        let ordinal = match imports_format {
+            // DYLD_CHAINED_IMPORT and DYLD_CHAINED_IMPORT_ADDEND: 8-bit ordinal
+            1 | 2 => {
+                let stride = if imports_format == 1 { 4 } else { 8 };
+                let ordinal = read_u32(slice, imports + i * stride)? & 0xff;
+                (ordinal < 0xf0).then_some(ordinal as u16)
+            }
+            // DYLD_CHAINED_IMPORT_ADDEND64: 16-bit ordinal
+            3 => {
+                let ordinal = read_u32(slice, imports + i * 16)? & 0xffff;
+                (ordinal < 0xfff0).then_some(ordinal as u16)
+            }
+            format => {
+                return Err(RuzuleError::MachO(format!(
+                    "Unknown chained fixups import format {}",
+                    format
+                )))
+            }
+        };
+        ordinals.extend(ordinal);
+    }
+    Ok(ordinals)
+}
+
+/// Every `LC_RPATH` of a binary, in search order (from the first slice)
+pub fn rpaths(data: &[u8]) -> Result<Vec<String>> {
+    Ok(linkage(data)?.rpaths)
+}
+
+/// Delete every `LC_RPATH` command for `rpath`, duplicates included
+pub fn remove_rpath(data: &[u8], rpath: &str) -> Result<Vec<u8>> {
+    Ok(patched(data, &remove_rpath_patches(data, rpath)?))
+}
+
+pub(crate) fn remove_rpath_patches(data: &[u8], rpath: &str) -> Result<Vec<Patch>> {
+    patch_slices(data, |slice, macho| {
+        let removed: Vec<usize> = command_names(slice, macho, &[LC_RPATH])
+            .filter(|(_, name)| *name == rpath)
+            .map(|(offset, _)| offset)
+            .collect();
+        rewrite_commands(slice, macho, &removed, &[])
+    })
+}
+
+/// Drop the load commands at `removed` (offsets, in order) and add `appended`
+/// (one command, or none) after the rest, moving the ones after the first
+/// removed up and zeroing whatever space is left at the end. The caller makes
+/// sure the result fits before the segment data.
+fn rewrite_commands(
+    slice: &[u8],
+    macho: &MachO,
+    removed: &[usize],
+    appended: &[u8],
+) -> Result<Vec<Patch>> {
+    let header_size = if macho.is_64 { 32 } else { 28 };
+    let ncmds = read_u32(slice, 16)?;
+    let sizeofcmds = read_u32(slice, 20)?;
+    let end = header_size + sizeofcmds as usize;
+    let first = removed.first().copied().unwrap_or(end);
+    if first == end && appended.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut commands = Vec::with_capacity(end - first + appended.len());
+    let mut freed = 0;
+    for cmd in macho.load_commands.iter().filter(|cmd| cmd.offset >= first) {
+        let size = read_u32(slice, cmd.offset + 4)?;
+        if removed.contains(&cmd.offset) {
+            freed += size;
+            continue;
+        }
+        let bytes = slice.get(cmd.offset..cmd.offset + size as usize).ok_or_else(|| {
+            RuzuleError::MachO("Load command extends past the end of the file".to_string())
+        })?;
+        commands.extend_from_slice(bytes);
+    }
+    commands.extend_from_slice(appended);
+    commands.resize(commands.len().max(end - first), 0);
+
+    let added = u32::from(!appended.is_empty());
+    Ok(vec![
+        (16, (ncmds - removed.len() as u32 + added).to_le_bytes().to_vec()),
+        (20, (sizeofcmds - freed + appended.len() as u32).to_le_bytes().to_vec()),
+        (first, commands),
+    ])
+}
+
+/// Turn every `LC_LOAD_DYLIB` of `framework` (`.../<framework>.framework/<framework>`)
+/// into `LC_LOAD_WEAK_DYLIB`, so the binary still loads once the framework is gone
+pub fn weaken_framework(data: &[u8], framework: &str) -> Result<Vec<u8>> {
+    Ok(patched(data, &weaken_framework_patches(data, framework)?))
+}
+
+pub(crate) fn weaken_framework_patches(data: &[u8], framework: &str) -> Result<Vec<Patch>> {
+    let binary = format!("{0}.framework/{0}", framework);
+    let matches = |name: &str| {
+        name.strip_suffix(binary.as_str()).is_some_and(|dir| dir.is_empty() || dir.ends_with('/'))
+    };
+    patch_slices(data, |slice, macho| {
+        Ok(command_names(slice, macho, &[LC_LOAD_DYLIB])
+            .filter(|(_, name)| matches(name))
+            .map(|(offset, _)| (offset, LC_LOAD_WEAK_DYLIB.to_le_bytes().to_vec()))
+            .collect())
+    })
+}
+
+pub fn change_install_name(data: &[u8], new_name: &str) -> Result<Vec<u8>> {
+    Ok(patched(data, &install_name_patches(data, new_name)?))
+}
+
+pub(crate) fn install_name_patches(data: &[u8], new_name: &str) -> Result<Vec<Patch>> {
+    patch_slices(data, |slice, macho| {
+        let offsets = command_names(slice, macho, &[LC_ID_DYLIB]).map(|(offset, _)| offset);
+        rename_commands(slice, offsets.take(1), "install name", new_name)
+    })
+}
+
+/// Add `rpath`. When it doesn't fit in the header padding, load commands only
+/// tools read are dropped to make room; their names are returned, once per slice.
+pub fn add_rpath(data: &[u8], rpath: &str) -> Result<(Vec<u8>, Vec<&'static str>)> {
+    let mut dropped = Vec::new();
+    let patches = add_rpath_patches(data, rpath, &mut dropped)?;
+    Ok((patched(data, &patches), dropped))
+}
+
+pub(crate) fn add_rpath_patches(
+    data: &[u8],
+    rpath: &str,
+    dropped: &mut Vec<&'static str>,
+) -> Result<Vec<Patch>> {
+    patch_slices(data, |slice, macho| {
+        if command_names(slice, macho, &[LC_RPATH]).any(|(_, path)| path == rpath) {
+            return Ok(Vec::new());
+        }
+        let command = load_command(LC_RPATH, &[12], rpath);
+        append_load_command(slice, macho, "rpath command", command, dropped)
+    })
+}
+
+/// Re-emit the binary without its code signature
+#[cfg(feature = "native")]
+pub fn remove_code_signature(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_without_signature(data, &mut out)?;
+    Ok(out)
+}
+
+/// [`remove_code_signature`], streamed to `out`
+#[cfg(feature = "native")]
+pub(crate) fn write_without_signature(data: &[u8], mut out: &mut dyn Write) -> Result<()> {
+    let mach_file = MachFile::parse(data)
+        .map_err(|e| RuzuleError::MachO(format!("Failed to parse Mach-O: {}", e)))?;
+
+    let mut builder = UniversalBinaryBuilder::default();
+    for binary in mach_file.iter_macho() {
+        let _ = builder.add_binary(binary.data);
+    }
+    builder
+        .write(&mut out)
+        .map_err(|e| RuzuleError::MachO(format!("Failed to write Mach-O: {}", e)))?;
+    Ok(())
+}
+
+/// Combine the slices of `binaries` (thin or fat) into one fat binary streamed
+/// to `out`, like `lipo -create`. Returns the architecture of each slice;
+/// two slices of the same architecture are refused.
+#[cfg(feature = "native")]
+pub(crate) fn write_universal(binaries: &[&[u8]], mut out: &mut dyn Write) -> Result<Vec<String>> {
+    let mut builder = UniversalBinaryBuilder::default();
+    let mut found: Vec<(u32, String)> = Vec::new();
+    for data in binaries {
+        for (range, arch) in slices(data)?.into_iter().zip(architectures(data)?) {
+            if found.iter().any(|(_, name)| *name == arch.1) {
+                return Err(RuzuleError::MachO(format!("More than one {} slice", arch.1)));
+            }
+            let slice = data.get(range).ok_or_else(|| {
+                RuzuleError::MachO("Slice extends past the end of the file".to_string())
+            })?;
+            builder
+                .add_binary(slice)
+                .map_err(|e| RuzuleError::MachO(format!("Failed to add slice: {}", e)))?;
+            found.push(arch);
+        }
+    }
+    builder
+        .write(&mut out)
+        .map_err(|e| RuzuleError::MachO(format!("Failed to write Mach-O: {}", e)))?;
+    Ok(found.into_iter().map(|(_, name)| name).collect())
+}
+
+/// The slices of `data` a binary built for `archs` (its [`architectures`]) can
+/// load, streamed to `out`: those of the same architecture, or failing that of
+/// the same CPU type. Returns false, writing nothing, when that's every slice or none.
+#[cfg(feature = "native")]
+pub(crate) fn write_thinned(
+    data: &[u8],
+    archs: &[(u32, String)],
+    mut out: &mut dyn Write,
+) -> Result<bool> {
+    let found = architectures(data)?;
+    let matching = |same: fn(&(u32, String), &(u32, String)) -> bool| -> Vec<usize> {
+        (0..found.len()).filter(|&i| archs.iter().any(|arch| same(arch, &found[i]))).collect()
+    };
+    let mut keep = matching(|a, b| a.1 == b.1);
+    if keep.is_empty() {
+        keep = matching(|a, b| a.0 == b.0);
+    }
+    if keep.is_empty() || keep.len() == found.len() {
+        return Ok(false);
+    }
+
+    let ranges = slices(data)?;
+    let slice = |i: usize| {
+        data.get(ranges[i].clone()).ok_or_else(|| {
+            RuzuleError::MachO("Slice extends past the end of the file".to_string())
+        })
+    };
+    if let [only] = keep[..] {
+        out.write_all(slice(only)?)?;
+        return Ok(true);
+    }
+    let mut builder = UniversalBinaryBuilder::default();
+    for i in keep {
+        builder
+            .add_binary(slice(i)?)
+            .map_err(|e| RuzuleError::MachO(format!("Failed to add slice: {}", e)))?;
+    }
+    builder
+        .write(&mut out)
+        .map_err(|e| RuzuleError::MachO(format!("Failed to write Mach-O: {}", e)))?;
+    Ok(true)
+}
+
+/// An architecture binaries can be thinned to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Arch {
+    /// Every 64-bit iPhone and iPad, and Apple silicon simulators
+    #[default]
+    #[serde(rename = "arm64")]
+    Arm64,
+    /// A12 and later with pointer authentication, for binaries built with it
+    #[serde(rename = "arm64e")]
+    Arm64e,
+    /// Intel simulators
+    #[serde(rename = "x86_64")]
+    X86_64,
+}
+
+impl Arch {
+    pub const ALL: [Arch; 3] = [Arch::Arm64, Arch::Arm64e, Arch::X86_64];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Arch::Arm64 => "arm64",
+            Arch::Arm64e => "arm64e",
+            Arch::X86_64 => "x86_64",
+        }
+    }
+
+    /// Whether a slice of `cputype`/`cpusubtype` is this architecture
+    fn matches(self, cputype: u32, cpusubtype: u32) -> bool {
+        // The top byte of the subtype holds capability bits (e.g. the arm64e ABI version)
+        const CPU_SUBTYPE_ARM64E: u32 = 2;
+        let subtype = cpusubtype & 0x00ff_ffff;
+        match self {
+            Arch::Arm64 => cputype == CPU_TYPE_ARM64 && subtype != CPU_SUBTYPE_ARM64E,
+            Arch::Arm64e => cputype == CPU_TYPE_ARM64 && subtype == CPU_SUBTYPE_ARM64E,
+            Arch::X86_64 => cputype == CPU_TYPE_X86_64,
+        }
+    }
+}
+
+impl fmt::Display for Arch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Arch {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Arch::ALL
+            .into_iter()
+            .find(|arch| arch.as_str() == s.to_lowercase())
+            .ok_or_else(|| {
+                format!("unknown architecture \"{}\" (expected arm64, arm64e or x86_64)", s)
+            })
+    }
+}
+
+/// Whether `data` has a slice of `arch`
+pub fn has_arch(data: &[u8], arch: Arch) -> Result<bool> {
+    match Mach::parse(data)? {
+        Mach::Binary(macho) => {
+            let header = macho.header;
+            Ok(arch.matches(header.cputype(), header.cpusubtype()))
+        }
+        Mach::Fat(fat) => {
+            for fat_arch in fat.iter_arches() {
+                let fat_arch = fat_arch?;
+                if arch.matches(fat_arch.cputype(), fat_arch.cpusubtype()) {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+    }
+}
+
+/// The arm64 slice of a fat binary, or `None` if `data` already is a thin arm64 binary
+pub fn thin_to_arm64(data: &[u8]) -> Result<Option<Vec<u8>>> {
+    thin_to_arch(data, Arch::Arm64)
+}
+
+/// The `arch` slice of a fat binary, or `None` if `data` already is a thin `arch` binary
+pub fn thin_to_arch(data: &[u8], arch: Arch) -> Result<Option<Vec<u8>>> {
+    Ok(arch_slice(data, arch)?.map(|range| data[range].to_vec()))
+}
+
+/// Where [`thin_to_arch`]'s slice is in `data`
+pub(crate) fn arch_slice(data: &[u8], arch: Arch) -> Result<Option<Range<usize>>> {
+    match Mach::parse(data)? {
+        Mach::Binary(macho) => {
+            if arch.matches(macho.header.cputype(), macho.header.cpusubtype()) {
+                Ok(None)
+            } else {
+                Err(RuzuleError::MachO(format!("Binary is not {}", arch)))
+            }
+        }
+        Mach::Fat(fat) => {
+            for fat_arch in fat.iter_arches() {
+                let fat_arch = fat_arch?;
+                if arch.matches(fat_arch.cputype(), fat_arch.cpusubtype()) {
+                    let (offset, size) = (fat_arch.offset as usize, fat_arch.size as usize);
+                    return Ok(Some(offset..offset + size));
+                }
+            }
+            Err(RuzuleError::MachO(format!("No {} slice found in fat binary", arch)))
+        }
+    }
+}
+
+/// Byte range of each slice: the whole of a thin binary, or each arch of a fat one
+fn slices(data: &[u8]) -> Result<Vec<Range<usize>>> {
+    match Mach::parse(data)? {
+        Mach::Binary(_) => Ok(std::iter::once(0..data.len()).collect()),
+        Mach::Fat(fat) => fat
+            .iter_arches()
+            .map(|arch| {
+                let arch = arch?;
+                Ok(arch.offset as usize..(arch.offset + arch.size) as usize)
+            })
+            .collect(),
+    }
+}
+
+/// `edit`'s patches for every slice, with their offsets made relative to `data`
+fn patch_slices<F>(data: &[u8], mut edit: F) -> Result<Vec<Patch>>
+where
+    F: FnMut(&[u8], &MachO) -> Result<Vec<Patch>>,
+{
+    let mut patches = Vec::new();
+    for range in slices(data)? {
+        let start = range.start;
+        let slice = data.get(range).ok_or_else(|| {
+            RuzuleError::MachO("Slice extends past the end of the file".to_string())
+        })?;
+        let macho = MachO::parse(slice, 0)?;
+        patches.extend(edit(slice, &macho)?.into_iter().map(|(at, bytes)| (start + at, bytes)));
+    }
+    Ok(patches)
+}
+
+pub(crate) fn apply_patches(data: &mut [u8], patches: &[Patch]) {
+    for (at, bytes) in patches {
+        data[*at..at + bytes.len()].copy_from_slice(bytes);
+    }
+}
+
+fn patched(data: &[u8], patches: &[Patch]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    apply_patches(&mut out, patches);
+    out
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32> {
+    let bytes = data.get(at..at + 4).ok_or_else(|| {
+        RuzuleError::MachO("Load command extends past the end of the file".to_string())
+    })?;
+    Ok(u32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+}
+
+/// Offset and name (dylib path, rpath) of every load command of a type in `cmds`
+fn command_names<'a>(
+    slice: &'a [u8],
+    macho: &'a MachO,
+    cmds: &'a [u32],
+) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    macho
+        .load_commands
+        .iter()
+        .filter(move |cmd| cmds.contains(&cmd.command.cmd()))
+        .filter_map(move |cmd| {
+            let name_offset = read_u32(slice, cmd.offset + 8).ok()? as usize;
+            let name = slice.get(cmd.offset + name_offset..)?;
+            let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            Some((cmd.offset, std::str::from_utf8(&name[..end]).ok()?))
+        })
+}
+
+/// A load command of `fields` (after cmd and cmdsize) and `name`, NUL-padded to 8 bytes
+fn load_command(cmd: u32, fields: &[u32], name: &str) -> Vec<u8> {
+    let size = (8 + 4 * fields.len() + name.len() + 1).next_multiple_of(8);
+    let mut command = Vec::with_capacity(size);
+    command.extend_from_slice(&cmd.to_le_bytes());
+    command.extend_from_slice(&(size as u32).to_le_bytes());
+    for field in fields {
+        command.extend_from_slice(&field.to_le_bytes());
+    }
+    command.extend_from_slice(name.as_bytes());
+    command.resize(size, 0);
+    command
+}
+
+/// Add `command` after the last load command, if it fits before the segment
+/// data, dropping [`DISPENSABLE_COMMANDS`] to make room when it doesn't. The
+/// names of those dropped are added to `dropped`.
+fn append_load_command(
+    slice: &[u8],
+    macho: &MachO,
+    what: &'static str,
+    command: Vec<u8>,
+    dropped: &mut Vec<&'static str>,
+) -> Result<Vec<Patch>> {
+    let header_size = if macho.is_64 { 32 } else { 28 };
+    let sizeofcmds = read_u32(slice, 20)?;
+    let end = header_size + sizeofcmds as usize;
+
+    // __TEXT starts at offset 0, so its first section is where its data begins
+    let segments = macho.load_commands.iter().filter_map(|cmd| match &cmd.command {
+        CommandVariant::Segment64(seg) if seg.filesize > 0 && seg.fileoff > 0 => {
+            Some(seg.fileoff as usize)
+        }
+        CommandVariant::Segment32(seg) if seg.filesize > 0 && seg.fileoff > 0 => {
+            Some(seg.fileoff as usize)
+        }
+        _ => None,
+    });
+    let sections = macho
+        .segments
+        .iter()
+        .filter_map(|seg| seg.sections().ok())
+        .flatten()
+        .filter(|(section, _)| section.offset > 0 && section.size > 0)
+        .map(|(section, _)| section.offset as usize);
+    let data_start = segments.chain(sections).min().unwrap_or(slice.len());
+    let available = data_start.saturating_sub(end);
+    if command.len() <= available {
+        return rewrite_commands(slice, macho, &[], &command);
+    }
+
+    let mut removed = Vec::new();
+    let mut names = Vec::new();
+    let mut freed = 0;
+    for &(cmd, name) in DISPENSABLE_COMMANDS {
+        if available + freed >= command.len() {
+            break;
+        }
+        for dispensable in macho.load_commands.iter().filter(|c| c.command.cmd() == cmd) {
+            removed.push(dispensable.offset);
+            freed += read_u32(slice, dispensable.offset + 4)? as usize;
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    if available + freed < command.len() {
+        return Err(RuzuleError::NoLoadCommandSpace {
+            what,
+            needed: command.len(),
+            available,
+        });
+    }
+
+    dropped.extend(names);
+    removed.sort_unstable();
+    rewrite_commands(slice, macho, &removed, &command)
+}
+
+/// Overwrite the name of the load commands at `offsets` with `name`, zeroing the rest
+fn rename_commands(
+    slice: &[u8],
+    offsets: impl Iterator<Item = usize>,
+    what: &'static str,
+    name: &str,
+) -> Result<Vec<Patch>> {
+    offsets
+        .map(|offset| {
+            let cmdsize = read_u32(slice, offset + 4)? as usize;
+            let name_offset = read_u32(slice, offset + 8)? as usize;
+            let available = cmdsize.saturating_sub(name_offset);
+            if name.len() + 1 > available {
+                return Err(RuzuleError::NoLoadCommandSpace {
+                    what,
+                    needed: (name.len() + 1).next_multiple_of(8),
+                    available,
+                });
+            }
+            let mut bytes = name.as_bytes().to_vec();
+            bytes.resize(available, 0);
+            Ok((offset + name_offset, bytes))
+        })
+        .collect()
+}
+
+pub fn parse_plist(data: &[u8]) -> Result<plist::Dictionary> {
+    Ok(plist::from_bytes(data)?)
+}
+
+pub fn plist_to_xml(dict: &plist::Dictionary) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    plist::to_writer_xml(&mut out, dict)?;
+    Ok(out)
+}
+
+pub fn plist_to_binary(dict: &plist::Dictionary) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    plist::to_writer_binary(&mut out, dict)?;
+    Ok(out)
+}
+
+/// The zip inside a .cyan.zst, or `data` itself for a plain .cyan
+fn unwrap_cyan(data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if cyan_config::is_zstd(data) {
+        Ok(Cow::Owned(cyan_config::decode_zstd(data)?))
+    } else {
+        Ok(Cow::Borrowed(data))
+    }
+}
+
+/// The config.json of a .cyan or .cyan.zst
+pub fn read_cyan_config(data: &[u8]) -> Result<CyanConfig> {
+    let data = unwrap_cyan(data)?;
+    cyan_config::read_config(&mut zip::ZipArchive::new(Cursor::new(&*data))?)
+}
+
+/// Config and member hashes of a .cyan or .cyan.zst, as used by
+/// [`diff_cyans`](crate::diff_cyans)
+pub fn summarize_cyan(data: &[u8]) -> Result<CyanSummary> {
+    let data = unwrap_cyan(data)?;
+    cyan_config::summarize_archive(zip::ZipArchive::new(Cursor::new(&*data))?)
+}
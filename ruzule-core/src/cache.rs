@@ -0,0 +1,122 @@
+//! ruzule's on-disk cache: downloaded and updated frameworks, work directories
+//! of resumable runs and lock files, kept across runs.
+
+use crate::error::Result;
+use crate::lock::{lock_cache, LOCKS_DIR};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// Root of ruzule's on-disk cache.
+///
+/// `$RUZULE_CACHE_DIR` if set, otherwise `%LOCALAPPDATA%\ruzule` on Windows
+/// and `$XDG_CACHE_HOME/ruzule` (default `~/.cache/ruzule`) elsewhere.
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("RUZULE_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+
+    #[cfg(windows)]
+    let base = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+
+    #[cfg(not(windows))]
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+
+    base.map(|dir| dir.join("ruzule"))
+}
+
+/// Disk use of one top-level cache directory, e.g. `frameworks`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheUsage {
+    pub name: String,
+    pub files: usize,
+    pub bytes: u64,
+    /// Last modification of its newest file
+    #[serde(skip)]
+    pub newest: Option<SystemTime>,
+}
+
+/// What [`clean`] removed
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Cleaned {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Usage of each directory in [`cache_dir`], by name. Empty if there's no cache yet.
+pub fn stats() -> Result<Vec<CacheUsage>> {
+    let Some(root) = cache_dir().filter(|d| d.is_dir()) else {
+        return Ok(Vec::new());
+    };
+
+    let mut usage = Vec::new();
+    for entry in fs::read_dir(&root)? {
+        let entry = entry?;
+        if entry.file_name() == LOCKS_DIR {
+            continue;
+        }
+        let mut item = CacheUsage {
+            name: entry.file_name().to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        for (_, meta) in files(&entry.path()) {
+            item.files += 1;
+            item.bytes += meta.len();
+            item.newest = item.newest.max(meta.modified().ok());
+        }
+        usage.push(item);
+    }
+    usage.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(usage)
+}
+
+/// Delete cached files, or only those not modified for `older_than`, then
+/// any directories left empty. Missing files are fetched again when needed.
+/// Fails with [`RuzuleError::Locked`](crate::RuzuleError::Locked) while a run
+/// is using the cache.
+pub fn clean(older_than: Option<Duration>) -> Result<Cleaned> {
+    let Some(root) = cache_dir().filter(|d| d.is_dir()) else {
+        return Ok(Cleaned::default());
+    };
+    let cutoff = older_than.and_then(|age| SystemTime::now().checked_sub(age));
+    let _lock = lock_cache(true)?;
+
+    let mut cleaned = Cleaned::default();
+    let locks = root.join(LOCKS_DIR);
+    for (path, meta) in files(&root).into_iter().filter(|(p, _)| !p.starts_with(&locks)) {
+        let expired = match (cutoff, meta.modified()) {
+            (None, _) => true,
+            (Some(cutoff), Ok(modified)) => modified < cutoff,
+            (Some(_), Err(_)) => false,
+        };
+        if expired {
+            fs::remove_file(&path)?;
+            cleaned.files += 1;
+            cleaned.bytes += meta.len();
+        }
+    }
+
+    // Deepest first, so parents are empty by the time they're reached
+    for entry in WalkDir::new(&root).min_depth(1).contents_first(true) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            // Fails for directories that still have files, which is what we want
+            let _ = fs::remove_dir(entry.path());
+        }
+    }
+    Ok(cleaned)
+}
+
+/// Every file under `path` (or `path` itself) with its metadata
+fn files(path: &Path) -> Vec<(PathBuf, fs::Metadata)> {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_type().is_dir())
+        .filter_map(|e| Some((e.path().to_path_buf(), e.metadata().ok()?)))
+        .collect()
+}
@@ -0,0 +1,32 @@
+use crate::error::{Result, RuzuleError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag for aborting a run from another thread (a GUI, a Ctrl-C handler).
+///
+/// The pipeline checks it between stages and while extracting or packing,
+/// and fails with [`RuzuleError::Cancelled`] once it's set.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// `Err(Cancelled)` once [`CancellationToken::cancel`] has been called
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(RuzuleError::Cancelled);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,733 @@
+use crate::entitlements::EntitlementOps;
+use crate::error::{Result, RuzuleError};
+use crate::plist_ext::PlistMerge;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CyanConfig {
+    #[serde(default)]
+    pub f: bool,  // Has files to inject
+    #[serde(default)]
+    pub n: Option<String>,  // App name
+    #[serde(default)]
+    pub v: Option<String>,  // Version
+    #[serde(default)]
+    pub b: Option<String>,  // Bundle ID
+    #[serde(default)]
+    pub m: Option<String>,  // Minimum OS version
+    #[serde(default)]
+    pub k: bool,  // Has icon
+    /// Names of the alternate icons, stored as `alternate-icons/<name>.idk`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternate_icons: Vec<String>,
+    #[serde(default)]
+    pub l: bool,  // Has plist to merge
+    /// How merge.plist is merged (replace, deep or append)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plist_merge: Option<PlistMerge>,
+    #[serde(default)]
+    pub x: bool,  // Has entitlements
+    #[serde(default)]
+    pub remove_supported_devices: bool,
+    #[serde(default)]
+    pub no_watch: bool,
+    #[serde(default)]
+    pub enable_documents: bool,
+    #[serde(default)]
+    pub fakesign: bool,
+    #[serde(default)]
+    pub thin: bool,
+    #[serde(default)]
+    pub remove_extensions: bool,
+    #[serde(default)]
+    pub remove_encrypted: bool,
+    #[serde(default)]
+    pub patch_plugins: bool,
+    /// Entitlements the injected files need, added to the main binary's without
+    /// replacing any (see [`EntitlementOps::require`])
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub requires_entitlements: serde_json::Map<String, serde_json::Value>,
+    /// Entitlement edits keyed by binary path relative to the .app
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub binary_entitlements: BTreeMap<String, EntitlementOps>,
+    /// Deduplicated members: destination path -> stored path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub links: BTreeMap<String, String>,
+    /// Fail on `${VAR}` placeholders that aren't defined instead of leaving them as is
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict_vars: bool,
+}
+
+/// Frame magic of a zstd stream, which a .cyan.zst starts with
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Level of the outer compression of a .cyan.zst. Packs are built once and
+/// applied many times, so this favors size over speed.
+#[cfg(feature = "native")]
+const ZSTD_LEVEL: i32 = 19;
+
+pub(crate) fn is_zstd(data: &[u8]) -> bool {
+    data.starts_with(&ZSTD_MAGIC)
+}
+
+/// Unwrap a .cyan.zst; zstd is native code, so only with the `native` feature
+pub(crate) fn decode_zstd<R: Read>(data: R) -> Result<Vec<u8>> {
+    #[cfg(feature = "native")]
+    {
+        Ok(zstd::decode_all(data)?)
+    }
+
+    #[cfg(not(feature = "native"))]
+    {
+        let _ = data;
+        Err(RuzuleError::InvalidInput(
+            "reading a .cyan.zst needs a build with the native feature".to_string(),
+        ))
+    }
+}
+
+/// Compression used for the members of a generated .cyan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CyanCompression {
+    Deflate(u32),
+    #[cfg(feature = "native")]
+    Zstd,
+}
+
+impl Default for CyanCompression {
+    fn default() -> Self {
+        CyanCompression::Deflate(1)
+    }
+}
+
+impl FromStr for CyanCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        #[cfg(feature = "native")]
+        if s.eq_ignore_ascii_case("zstd") {
+            return Ok(CyanCompression::Zstd);
+        }
+        match s.parse::<u32>() {
+            Ok(level) if level <= 9 => Ok(CyanCompression::Deflate(level)),
+            _ => Err(format!("invalid compression \"{}\" (expected 0-9 or zstd)", s)),
+        }
+    }
+}
+
+impl CyanCompression {
+    fn options(&self) -> SimpleFileOptions {
+        match *self {
+            CyanCompression::Deflate(0) => {
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored)
+            }
+            CyanCompression::Deflate(level) => SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .compression_level(Some(level as i64)),
+            #[cfg(feature = "native")]
+            CyanCompression::Zstd => {
+                SimpleFileOptions::default().compression_method(CompressionMethod::Zstd)
+            }
+        }
+    }
+}
+
+/// Writes a .cyan archive, optionally storing identical payloads only once
+pub struct CyanWriter<W: Write + Seek> {
+    zip: zip::ZipWriter<W>,
+    options: SimpleFileOptions,
+    dedup: bool,
+    stored: HashMap<String, String>,
+    links: BTreeMap<String, String>,
+}
+
+impl<W: Write + Seek> CyanWriter<W> {
+    pub fn new(writer: W, compression: CyanCompression, dedup: bool) -> Self {
+        Self {
+            zip: zip::ZipWriter::new(writer),
+            options: compression.options(),
+            dedup,
+            stored: HashMap::new(),
+            links: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_file<P: AsRef<Path>>(&mut self, name: &str, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        // Only payload files can be restored from links
+        if self.dedup && name.starts_with("inject/") {
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut File::open(path)?, &mut hasher)?;
+            let hash = hex::encode(hasher.finalize());
+            if let Some(existing) = self.stored.get(&hash) {
+                self.links.insert(name.to_string(), existing.clone());
+                return Ok(());
+            }
+            self.stored.insert(hash, name.to_string());
+        }
+
+        let options = self.options_for(path)?;
+        self.zip.start_file(name, options)?;
+        std::io::copy(&mut File::open(path)?, &mut self.zip)?;
+        Ok(())
+    }
+
+    /// Recursively add `dir` under `base/<dir name>/`, keeping symlinks and empty directories
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P, base: &str) -> Result<()> {
+        let dir = dir.as_ref();
+        let dir_name = dir.file_name().unwrap().to_string_lossy();
+        let base = format!("{}/{}", base, dir_name);
+
+        let options = self.options_for(dir)?;
+        self.zip.add_directory(format!("{}/", base), options)?;
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let ty = entry.file_type()?;
+            let path = entry.path();
+            let rel_path = format!("{}/{}", base, entry.file_name().to_string_lossy());
+
+            if ty.is_symlink() {
+                let target = fs::read_link(&path)?;
+                let target = target.to_string_lossy().replace('\\', "/");
+                self.zip.add_symlink(&rel_path, target, self.options)?;
+            } else if ty.is_dir() {
+                self.add_dir(&path, &base)?;
+            } else if ty.is_file() {
+                self.add_file(&rel_path, &path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn options_for(&self, path: &Path) -> Result<SimpleFileOptions> {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)?.permissions().mode();
+        Ok(self.options.unix_permissions(mode & 0o7777))
+    }
+
+    #[cfg(not(unix))]
+    fn options_for(&self, _path: &Path) -> Result<SimpleFileOptions> {
+        Ok(self.options)
+    }
+
+    /// Write config.json (including any dedup links) and finish the archive.
+    /// Returns the number of deduplicated files.
+    pub fn finish(mut self, mut config: CyanConfig) -> Result<usize> {
+        config.links = std::mem::take(&mut self.links);
+
+        let config_json = serde_json::to_string(&config)?;
+        self.zip.start_file("config.json", self.options)?;
+        self.zip.write_all(config_json.as_bytes())?;
+
+        self.zip.finish()?;
+        Ok(config.links.len())
+    }
+}
+
+/// Wrap the finished .cyan at `cyan` in zstd, writing a .cyan.zst to `output`.
+///
+/// zstd compresses across members, so they're best stored uncompressed
+/// ([`CyanCompression::Deflate(0)`](CyanCompression::Deflate)).
+#[cfg(feature = "native")]
+pub fn compress_cyan<P: AsRef<Path>, Q: AsRef<Path>>(cyan: P, output: Q) -> Result<()> {
+    let mut input = File::open(cyan.as_ref())?;
+    let mut out = std::io::BufWriter::new(File::create(output.as_ref())?);
+    zstd::stream::copy_encode(&mut input, &mut out, ZSTD_LEVEL)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// A .cyan on disk, or a .cyan.zst decompressed into memory
+pub(crate) enum CyanFile {
+    Zip(File),
+    Unwrapped(Cursor<Vec<u8>>),
+}
+
+impl Read for CyanFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            CyanFile::Zip(file) => file.read(buf),
+            CyanFile::Unwrapped(data) => data.read(buf),
+        }
+    }
+}
+
+impl Seek for CyanFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            CyanFile::Zip(file) => file.seek(pos),
+            CyanFile::Unwrapped(data) => data.seek(pos),
+        }
+    }
+}
+
+/// Open a .cyan, decompressing it first if it's a .cyan.zst (by content, not name)
+pub(crate) fn open_archive(path: &Path) -> Result<zip::ZipArchive<CyanFile>> {
+    let mut file = File::open(path)?;
+    let mut magic = [0; 4];
+    let wrapped = file.read_exact(&mut magic).is_ok() && is_zstd(&magic);
+    file.rewind()?;
+
+    let file = if wrapped {
+        CyanFile::Unwrapped(Cursor::new(decode_zstd(file)?))
+    } else {
+        CyanFile::Zip(file)
+    };
+    Ok(zip::ZipArchive::new(file)?)
+}
+
+/// Config and payload hashes of a .cyan, read without extracting it
+pub struct CyanSummary {
+    pub config: CyanConfig,
+    /// Member name -> SHA-256 of its contents (symlinks hash their target)
+    pub files: BTreeMap<String, String>,
+}
+
+pub fn summarize_cyan<P: AsRef<Path>>(cyan_path: P) -> Result<CyanSummary> {
+    summarize_archive(open_archive(cyan_path.as_ref())?)
+}
+
+pub(crate) fn read_config<R: Read + Seek>(archive: &mut zip::ZipArchive<R>) -> Result<CyanConfig> {
+    let mut config_file = archive.by_name("config.json")?;
+    let mut contents = String::new();
+    config_file.read_to_string(&mut contents)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+pub(crate) fn summarize_archive<R: Read + Seek>(
+    mut archive: zip::ZipArchive<R>,
+) -> Result<CyanSummary> {
+    let config = read_config(&mut archive)?;
+
+    let mut files = BTreeMap::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+        if name == "config.json" || name.ends_with('/') {
+            continue;
+        }
+
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        files.insert(name, hex::encode(hasher.finalize()));
+    }
+
+    for (dest, src) in &config.links {
+        if let Some(hash) = files.get(src).cloned() {
+            files.insert(dest.clone(), hash);
+        }
+    }
+
+    Ok(CyanSummary { config, files })
+}
+
+/// A config field that differs between two .cyan files (`None` = unset)
+pub struct ConfigChange {
+    pub key: String,
+    pub old: Option<serde_json::Value>,
+    pub new: Option<serde_json::Value>,
+}
+
+#[derive(Default)]
+pub struct CyanDiff {
+    pub config: Vec<ConfigChange>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl CyanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.config.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+pub fn diff_cyans<P: AsRef<Path>, Q: AsRef<Path>>(old: P, new: Q) -> Result<CyanDiff> {
+    let old = summarize_cyan(old)?;
+    let new = summarize_cyan(new)?;
+    let mut diff = CyanDiff::default();
+
+    let config_map = |config: &CyanConfig| -> Result<serde_json::Map<String, serde_json::Value>> {
+        let mut value = serde_json::to_value(config)?;
+        let map = value.as_object_mut().map(std::mem::take).unwrap_or_default();
+        // Dedup links are an encoding detail, payload changes are reported below
+        Ok(map.into_iter().filter(|(k, v)| k != "links" && !v.is_null()).collect())
+    };
+    let old_config = config_map(&old.config)?;
+    let new_config = config_map(&new.config)?;
+
+    let keys: std::collections::BTreeSet<&String> =
+        old_config.keys().chain(new_config.keys()).collect();
+    for key in keys {
+        let (o, n) = (old_config.get(key), new_config.get(key));
+        if o != n {
+            diff.config.push(ConfigChange {
+                key: key.clone(),
+                old: o.cloned(),
+                new: n.cloned(),
+            });
+        }
+    }
+
+    for (name, hash) in &new.files {
+        match old.files.get(name) {
+            None => diff.added.push(name.clone()),
+            Some(old_hash) if old_hash != hash => diff.changed.push(name.clone()),
+            _ => {}
+        }
+    }
+    diff.removed = old
+        .files
+        .keys()
+        .filter(|name| !new.files.contains_key(*name))
+        .cloned()
+        .collect();
+
+    Ok(diff)
+}
+
+pub(crate) fn is_safe_member(name: &str) -> bool {
+    Path::new(name)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+}
+
+pub struct ParsedCyan {
+    pub config: CyanConfig,
+    pub files: HashMap<String, PathBuf>,
+    pub icon: Option<PathBuf>,
+    /// Alternate icon name -> extracted image
+    pub alternate_icons: BTreeMap<String, PathBuf>,
+    pub plist: Option<PathBuf>,
+    pub entitlements: Option<PathBuf>,
+}
+
+/// Expand `${VAR}` placeholders, looking in `defines` first and then the environment.
+/// `$${` is a literal `${`. Placeholders that aren't defined are left as they
+/// are, or are an error if `strict`.
+pub fn substitute_vars(
+    input: &str,
+    defines: &HashMap<String, String>,
+    strict: bool,
+) -> Result<String> {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            if strict {
+                return Err(RuzuleError::InvalidInput(format!(
+                    "Unterminated variable in \"{}\"",
+                    input
+                )));
+            }
+            out.push_str(&rest[start..]);
+            return Ok(out);
+        };
+
+        let var = &after[..end];
+        match defines.get(var).cloned().or_else(|| std::env::var(var).ok()) {
+            Some(value) => out.push_str(&value),
+            None if strict => {
+                return Err(RuzuleError::InvalidInput(format!(
+                    "Undefined variable ${{{}}} in cyan config",
+                    var
+                )))
+            }
+            None => out.push_str(&rest[start..start + 2 + end + 1]),
+        }
+        rest = &after[end + 1..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Expand placeholders in every string of `value`; returns whether any changed
+fn substitute_plist_value(
+    value: &mut plist::Value,
+    defines: &HashMap<String, String>,
+    strict: bool,
+) -> Result<bool> {
+    let mut changed = false;
+    match value {
+        plist::Value::String(s) => {
+            let substituted = substitute_vars(s, defines, strict)?;
+            changed = substituted != *s;
+            *s = substituted;
+        }
+        plist::Value::Array(arr) => {
+            for v in arr {
+                changed |= substitute_plist_value(v, defines, strict)?;
+            }
+        }
+        plist::Value::Dictionary(dict) => {
+            for v in dict.values_mut() {
+                changed |= substitute_plist_value(v, defines, strict)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(changed)
+}
+
+impl CyanConfig {
+    /// Expand `${VAR}` placeholders in the string fields
+    pub fn substitute(&mut self, defines: &HashMap<String, String>) -> Result<()> {
+        for field in [&mut self.n, &mut self.v, &mut self.b, &mut self.m].into_iter().flatten() {
+            *field = substitute_vars(field, defines, self.strict_vars)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_cyan<P: AsRef<Path>, Q: AsRef<Path>>(
+    cyan_path: P,
+    tmpdir: Q,
+    index: usize,
+    defines: &HashMap<String, String>,
+) -> Result<ParsedCyan> {
+    let cyan_path = cyan_path.as_ref();
+    let tmpdir = tmpdir.as_ref();
+
+    let mut archive = open_archive(cyan_path)?;
+
+    // Start empty, so nothing is written through a link left by an earlier run
+    let extract_dir = tmpdir.join(format!("cyan-{}", index));
+    if fs::symlink_metadata(&extract_dir).is_ok() {
+        fs::remove_dir_all(&extract_dir)?;
+    }
+    fs::create_dir_all(&extract_dir)?;
+
+    // Read config.json
+    let mut config = read_config(&mut archive)?;
+    config.substitute(defines)?;
+
+    let mut files = HashMap::new();
+    let mut icon = None;
+    let mut alternate_icons = BTreeMap::new();
+    let mut plist = None;
+    let mut entitlements = None;
+    // Created once every regular member is written, so nothing is written through one
+    let mut symlinks: Vec<(PathBuf, String)> = Vec::new();
+
+    // Extract relevant files
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+
+        if name.starts_with("inject/") && config.f {
+            if !is_safe_member(&name) {
+                return Err(RuzuleError::InvalidInput(format!(
+                    "Invalid path in cyan: {}",
+                    name
+                )));
+            }
+
+            let outpath = extract_dir.join(&name);
+            if name.ends_with('/') {
+                fs::create_dir_all(&outpath)?;
+                continue;
+            }
+            if let Some(p) = outpath.parent() {
+                fs::create_dir_all(p)?;
+            }
+
+            let mode = file.unix_mode();
+            if mode.map(|m| m & 0o170000 == 0o120000).unwrap_or(false) {
+                let mut target = String::new();
+                file.read_to_string(&mut target)?;
+                let parent = outpath.parent().unwrap_or(&extract_dir);
+                let inside = normalize(&parent.join(&target)).starts_with(normalize(&extract_dir));
+                if Path::new(&target).is_absolute() || !inside {
+                    return Err(RuzuleError::InvalidInput(format!(
+                        "Symlink in cyan points outside it: {} -> {}",
+                        name, target
+                    )));
+                }
+                symlinks.push((outpath, target));
+                continue;
+            }
+
+            let mut outfile = File::create(&outpath)?;
+            std::io::copy(&mut file, &mut outfile)?;
+
+            // Preserve Unix permissions (exec bits on framework binaries)
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if let Some(mode) = mode {
+                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode & 0o7777))?;
+                }
+            }
+        } else if name == "icon.idk" && config.k {
+            let outpath = extract_dir.join(&name);
+            let mut outfile = File::create(&outpath)?;
+            std::io::copy(&mut file, &mut outfile)?;
+            icon = Some(outpath);
+        } else if let Some(alternate) = alternate_icon_name(&name, &config) {
+            let outpath = extract_dir.join(&name);
+            if let Some(p) = outpath.parent() {
+                fs::create_dir_all(p)?;
+            }
+            let mut outfile = File::create(&outpath)?;
+            std::io::copy(&mut file, &mut outfile)?;
+            alternate_icons.insert(alternate.to_string(), outpath);
+        } else if name == "merge.plist" && config.l {
+            let outpath = extract_dir.join(&name);
+            let mut outfile = File::create(&outpath)?;
+            std::io::copy(&mut file, &mut outfile)?;
+            drop(outfile);
+
+            // Written back, in its own format, only if a placeholder was filled
+            let mut value = plist::Value::from_file(&outpath)?;
+            if substitute_plist_value(&mut value, defines, config.strict_vars)? {
+                if fs::read(&outpath)?.starts_with(b"bplist") {
+                    value.to_file_binary(&outpath)?;
+                } else {
+                    value.to_file_xml(&outpath)?;
+                }
+            }
+            plist = Some(outpath);
+        } else if name == "new.entitlements" && config.x {
+            let outpath = extract_dir.join(&name);
+            let mut outfile = File::create(&outpath)?;
+            std::io::copy(&mut file, &mut outfile)?;
+            entitlements = Some(outpath);
+        }
+    }
+
+    // Restore deduplicated files
+    if config.f {
+        for (dest, src) in &config.links {
+            if !dest.starts_with("inject/") || !is_safe_member(dest) || !is_safe_member(src) {
+                return Err(RuzuleError::InvalidInput(format!(
+                    "Invalid link in cyan: {} -> {}",
+                    dest, src
+                )));
+            }
+            let dest_path = extract_dir.join(dest);
+            if let Some(p) = dest_path.parent() {
+                fs::create_dir_all(p)?;
+            }
+            fs::copy(extract_dir.join(src), &dest_path)?;
+        }
+    }
+
+    for (link, target) in &symlinks {
+        create_symlink(target, link)?;
+    }
+    // A link can also escape through another one (`a -> b/..` with `b -> ..`)
+    let root = fs::canonicalize(&extract_dir)?;
+    for (link, target) in &symlinks {
+        if !resolves_inside(link, &root) {
+            fs::remove_dir_all(&extract_dir)?;
+            return Err(RuzuleError::InvalidInput(format!(
+                "Symlink in cyan points outside it: {} -> {}",
+                link.strip_prefix(&extract_dir).unwrap_or(link).display(),
+                target
+            )));
+        }
+    }
+
+    // Collect files from inject directory
+    if config.f {
+        let inject_dir = extract_dir.join("inject");
+        if inject_dir.exists() {
+            for entry in fs::read_dir(&inject_dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                files.insert(name, entry.path());
+            }
+        }
+    }
+
+    Ok(ParsedCyan {
+        config,
+        files,
+        icon,
+        alternate_icons,
+        plist,
+        entitlements,
+    })
+}
+
+/// `path` with `.` and `..` resolved without touching the filesystem
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Whether `link` resolves inside `root` (canonical), as far as its target exists
+pub(crate) fn resolves_inside(link: &Path, root: &Path) -> bool {
+    let Ok(target) = fs::read_link(link) else {
+        return false;
+    };
+    let joined = link.parent().unwrap_or(link).join(target);
+    joined
+        .ancestors()
+        .find(|p| p.exists())
+        .and_then(|p| fs::canonicalize(p).ok())
+        .is_some_and(|p| p.starts_with(root))
+}
+
+pub(crate) fn create_symlink(target: &str, link: &Path) -> Result<()> {
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link)?;
+    #[cfg(windows)]
+    {
+        let parent = link.parent().unwrap_or(Path::new(""));
+        if parent.join(target).is_dir() {
+            std::os::windows::fs::symlink_dir(target, link)?;
+        } else {
+            std::os::windows::fs::symlink_file(target, link)?;
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    Err::<(), _>(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("can't create symlink {} -> {}", link.display(), target),
+    ))?;
+    Ok(())
+}
+
+/// The alternate icon a member is the image of, if it's one the config lists
+fn alternate_icon_name<'a>(member: &'a str, config: &CyanConfig) -> Option<&'a str> {
+    let name = member.strip_prefix("alternate-icons/")?.strip_suffix(".idk")?;
+    let listed = config.alternate_icons.iter().any(|n| n == name);
+    (listed && is_safe_member(member) && !name.contains('/')).then_some(name)
+}
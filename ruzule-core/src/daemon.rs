@@ -0,0 +1,207 @@
+//! Line-delimited JSON protocol for GUI frontends (`ruzule daemon`).
+//!
+//! Each request is one JSON object per line:
+//!
+//! ```text
+//! {"id": 1, "method": "capabilities"}
+//! {"id": 2, "method": "submit", "params": {"input": "App.ipa", "options": {"files": [..]}}}
+//! {"id": 3, "method": "cancel", "params": {"job": 1}}
+//! {"id": 4, "method": "shutdown"}
+//! ```
+//!
+//! and is answered with `{"id": .., "result": ..}` or `{"id": .., "error": {"code", "message"}}`.
+//! `submit` returns `{"job": n}` right away; the job then reports through
+//! `{"job": n, "event": "progress", "level": .., "message": ..}` lines and ends
+//! with exactly one `done` (with the report), `planned` (for `"dry_run": true`)
+//! or `failed` (with an error) line.
+
+use crate::cancel::CancellationToken;
+use crate::error::{Result, RuzuleError};
+use crate::events::{strip_ansi, Event};
+use crate::injector::{is_stdout, InjectOptions, Injector};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct SubmitParams {
+    input: PathBuf,
+    #[serde(default)]
+    options: InjectOptions,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+struct CancelParams {
+    job: u64,
+}
+
+/// Serializes writes so responses and job events never interleave mid-line
+struct Output<W>(Arc<Mutex<W>>);
+
+impl<W> Clone for Output<W> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<W: Write> Output<W> {
+    fn send(&self, message: &Value) {
+        let mut out = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        // The frontend went away; jobs keep running until cancelled or done
+        let _ = writeln!(out, "{}", message).and_then(|()| out.flush());
+    }
+
+    fn error(&self, id: &Value, error: &RuzuleError) {
+        self.send(&json!({ "id": id, "error": error }));
+    }
+}
+
+/// Serve requests from `input` until it's closed or `shutdown` is received,
+/// then wait for running jobs
+pub fn serve<R, W>(input: R, output: W) -> Result<()>
+where
+    R: BufRead,
+    W: Write + Send + 'static,
+{
+    let out = Output(Arc::new(Mutex::new(output)));
+    let jobs: Arc<Mutex<HashMap<u64, CancellationToken>>> = Arc::default();
+    let mut handles: Vec<JoinHandle<()>> = Vec::new();
+    let mut next_job = 1;
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                out.error(&Value::Null, &RuzuleError::Json(e));
+                continue;
+            }
+        };
+        let id = request.id;
+
+        match request.method.as_str() {
+            "capabilities" => out.send(&json!({ "id": id, "result": capabilities() })),
+            "submit" => {
+                let params: SubmitParams = match serde_json::from_value(request.params) {
+                    Ok(params) => params,
+                    Err(e) => {
+                        out.error(&id, &RuzuleError::Json(e));
+                        continue;
+                    }
+                };
+                // stdout carries the protocol
+                if params.options.output.as_deref().is_some_and(is_stdout) {
+                    let message = "output \"-\" isn't available in daemon mode".to_string();
+                    out.error(&id, &RuzuleError::InvalidInput(message));
+                    continue;
+                }
+
+                let job = next_job;
+                next_job += 1;
+                let cancel = CancellationToken::new();
+                jobs.lock().unwrap_or_else(|e| e.into_inner()).insert(job, cancel.clone());
+                out.send(&json!({ "id": id, "result": { "job": job } }));
+
+                let (out, jobs) = (out.clone(), jobs.clone());
+                // Only running jobs need waiting for at the end
+                handles.retain(|h| !h.is_finished());
+                handles.push(std::thread::spawn(move || {
+                    run_job(job, params, cancel, &out);
+                    jobs.lock().unwrap_or_else(|e| e.into_inner()).remove(&job);
+                }));
+            }
+            "cancel" => match serde_json::from_value::<CancelParams>(request.params) {
+                Ok(params) => {
+                    let token = jobs
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .get(&params.job)
+                        .cloned();
+                    if let Some(ref token) = token {
+                        token.cancel();
+                    }
+                    out.send(&json!({ "id": id, "result": { "cancelled": token.is_some() } }));
+                }
+                Err(e) => out.error(&id, &RuzuleError::Json(e)),
+            },
+            "shutdown" => {
+                for token in jobs.lock().unwrap_or_else(|e| e.into_inner()).values() {
+                    token.cancel();
+                }
+                out.send(&json!({ "id": id, "result": null }));
+                break;
+            }
+            method => out.error(
+                &id,
+                &RuzuleError::InvalidInput(format!("Unknown method: {}", method)),
+            ),
+        }
+    }
+
+    for handle in handles {
+        let _ = handle.join();
+    }
+    Ok(())
+}
+
+fn run_job<W: Write + Send + 'static>(
+    job: u64,
+    params: SubmitParams,
+    cancel: CancellationToken,
+    out: &Output<W>,
+) {
+    let events = out.clone();
+    let injector = Injector::with_options(params.input, params.options)
+        .cancel_token(cancel)
+        .on_event(move |event: &Event| {
+            events.send(&json!({
+                "job": job,
+                "event": "progress",
+                "level": event.level(),
+                "message": strip_ansi(&event.to_string()),
+            }))
+        });
+
+    let message = if params.dry_run {
+        injector.plan().map(|plan| {
+            let operations: Vec<String> =
+                plan.operations().iter().map(ToString::to_string).collect();
+            json!({ "job": job, "event": "planned", "operations": operations })
+        })
+    } else {
+        injector.run().map(|report| json!({ "job": job, "event": "done", "report": report }))
+    };
+
+    out.send(&message.unwrap_or_else(|e| json!({ "job": job, "event": "failed", "error": e })));
+}
+
+fn capabilities() -> Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "methods": ["capabilities", "submit", "cancel", "shutdown"],
+        "substrate_backends": ["substrate", "ellekit", "libhooker"],
+        "features": {
+            "embedded_frameworks": cfg!(feature = "embedded-frameworks"),
+            "download_frameworks": cfg!(feature = "download-frameworks"),
+        },
+    })
+}
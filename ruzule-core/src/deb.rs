@@ -1,4 +1,5 @@
 use crate::error::{Result, RuzuleError};
+use crate::events::{Event, Events};
 use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::BufReader;
@@ -8,6 +9,7 @@ pub fn extract_deb(
     deb_path: &Path,
     tweaks: &mut HashMap<String, PathBuf>,
     tmpdir: &Path,
+    events: &Events,
 ) -> Result<()> {
     let deb_name = deb_path
         .file_name()
@@ -80,7 +82,7 @@ pub fn extract_deb(
         }
     }
 
-    println!("[*] extracted {}", deb_name);
+    events.emit(Event::DebExtracted(deb_name.clone()));
 
     // Remove the deb from tweaks
     tweaks.remove(&deb_name);
@@ -115,15 +117,13 @@ fn extract_data_tar<P: AsRef<Path>>(tar_path: P, dest: P) -> Result<()> {
         let mut archive = tar::Archive::new(decoder);
         archive.unpack(dest)?;
     } else if tar_name.ends_with(".tar.zst") || tar_name.ends_with(".tar.zstd") {
-        // zstd support would require adding the zstd crate
-        return Err(RuzuleError::InvalidInput(
-            "zstd compression not yet supported".to_string(),
-        ));
-    } else if tar_name.ends_with(".tar.bz2") {
-        // bz2 support would require adding the bzip2 crate
-        return Err(RuzuleError::InvalidInput(
-            "bz2 compression not yet supported".to_string(),
-        ));
+        let decoder = zstd::stream::read::Decoder::new(file)?;
+        let mut archive = tar::Archive::new(decoder);
+        archive.unpack(dest)?;
+    } else if tar_name.ends_with(".tar.bz2") || tar_name.ends_with(".tar.bzip2") {
+        let decoder = bzip2::read::BzDecoder::new(file);
+        let mut archive = tar::Archive::new(decoder);
+        archive.unpack(dest)?;
     } else {
         // Assume uncompressed tar
         let mut archive = tar::Archive::new(file);
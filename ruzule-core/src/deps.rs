@@ -0,0 +1,60 @@
+//! What each binary in an app links, the `otool -L` view behind `ruzule deps`:
+//! the first thing to look at when an injected tweak keeps an app from launching.
+
+use crate::app_bundle::AppBundle;
+use crate::buffer::Linkage;
+use crate::cancel::CancellationToken;
+use crate::error::Result;
+use crate::events::Events;
+use crate::injector::has_extension;
+use crate::ipa::extract_ipa;
+use crate::macho;
+use crate::temp::TempDir;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BinaryLinkage {
+    /// Path inside the .app
+    pub binary: String,
+    #[serde(flatten)]
+    pub linkage: Linkage,
+}
+
+/// Linkage of the main binary of an .ipa, .tipa or .app, and with `all` also
+/// of every dylib, framework and extension in it
+pub fn app_linkage<P: AsRef<Path>>(input: P, all: bool) -> Result<Vec<BinaryLinkage>> {
+    let input = input.as_ref();
+    if !has_extension(input, &["ipa", "tipa"]) {
+        return bundle_linkage(&AppBundle::new(input)?, all);
+    }
+
+    let tmpdir = TempDir::new()?;
+    let cancel = CancellationToken::new();
+    let app_path = extract_ipa(input, tmpdir.path(), &cancel, &Events::none())?;
+    bundle_linkage(&AppBundle::new(app_path)?, all)
+}
+
+pub fn bundle_linkage(app: &AppBundle, all: bool) -> Result<Vec<BinaryLinkage>> {
+    let mut binaries = vec![app.executable.inner.path.clone()];
+    if all {
+        let mut nested: Vec<_> = app
+            .nested_executables()?
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        nested.sort();
+        binaries.extend(nested);
+    }
+
+    binaries
+        .into_iter()
+        .map(|path| {
+            let binary = path.strip_prefix(&app.path).unwrap_or(&path);
+            Ok(BinaryLinkage {
+                binary: binary.to_string_lossy().to_string(),
+                linkage: macho::linkage(&path)?,
+            })
+        })
+        .collect()
+}
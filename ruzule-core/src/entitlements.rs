@@ -0,0 +1,161 @@
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Entitlement edits for a single binary
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntitlementOps {
+    /// Keys to add or overwrite
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub add: serde_json::Map<String, serde_json::Value>,
+    /// Keys to remove
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove: Vec<String>,
+    /// Keys an injected tweak needs, added without replacing what's there:
+    /// arrays gain the items they lack, other values are set only when missing
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub require: serde_json::Map<String, serde_json::Value>,
+}
+
+impl EntitlementOps {
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.remove.is_empty() && self.require.is_empty()
+    }
+
+    /// Combine with edits from a later source, which take precedence
+    pub fn extend(&mut self, other: EntitlementOps) {
+        for key in &other.remove {
+            self.add.remove(key);
+            self.require.remove(key);
+        }
+        self.remove.extend(other.remove);
+        self.add.extend(other.add);
+        for (key, value) in other.require {
+            match (self.require.get_mut(&key), value) {
+                (Some(serde_json::Value::Array(have)), serde_json::Value::Array(need)) => {
+                    for item in need {
+                        if !have.contains(&item) {
+                            have.push(item);
+                        }
+                    }
+                }
+                (_, value) => {
+                    self.require.insert(key, value);
+                }
+            }
+        }
+    }
+
+    /// Apply removals, then additions, then requirements, to an entitlements dictionary
+    pub fn apply(&self, entitlements: &mut plist::Dictionary) {
+        for key in &self.remove {
+            entitlements.remove(key);
+        }
+        for (key, value) in &self.add {
+            if let Some(value) = json_to_plist(value) {
+                entitlements.insert(key.clone(), value);
+            }
+        }
+        for (key, value) in &self.require {
+            let Some(value) = json_to_plist(value) else {
+                continue;
+            };
+            match (entitlements.get_mut(key), value) {
+                (Some(plist::Value::Array(have)), plist::Value::Array(need)) => {
+                    for item in need {
+                        if !have.contains(&item) {
+                            have.push(item);
+                        }
+                    }
+                }
+                (Some(_), _) => {}
+                (None, value) => {
+                    entitlements.insert(key.clone(), value);
+                }
+            }
+        }
+    }
+}
+
+/// Where an injected file declares the entitlements it needs: a plist named
+/// after it with `.entitlements` appended (`Tweak.dylib.entitlements`)
+pub fn sidecar_path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".entitlements");
+    file.with_file_name(name)
+}
+
+/// The entitlements in `file`'s [sidecar](sidecar_path), if it has one, as
+/// [`EntitlementOps::require`] takes them
+pub fn read_sidecar(file: &Path) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+    let path = sidecar_path(file);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let entitlements: plist::Dictionary = plist::from_file(&path)?;
+    match serde_json::to_value(entitlements)? {
+        serde_json::Value::Object(map) => Ok(Some(map)),
+        _ => Ok(None),
+    }
+}
+
+/// Entitlements tied to the original team's provisioning profile, with why they're a problem.
+/// Under ad-hoc signing (fakesign, TrollStore) they're ignored at best, and often make
+/// signing or installation fail.
+pub const ADHOC_RISKY: &[(&str, &str)] = &[
+    ("aps-environment", "push notifications need the original team's profile"),
+    (
+        "com.apple.developer.associated-domains",
+        "associated domains are verified against the original team id",
+    ),
+    (
+        "com.apple.developer.icloud-container-identifiers",
+        "iCloud containers belong to the original team",
+    ),
+    (
+        "com.apple.developer.icloud-container-environment",
+        "iCloud containers belong to the original team",
+    ),
+    ("com.apple.developer.icloud-services", "iCloud containers belong to the original team"),
+    (
+        "com.apple.developer.ubiquity-container-identifiers",
+        "iCloud containers belong to the original team",
+    ),
+    (
+        "com.apple.developer.ubiquity-kvstore-identifier",
+        "iCloud key-value storage belongs to the original team",
+    ),
+];
+
+/// Why `key` is in [`ADHOC_RISKY`], if it is
+pub fn adhoc_risk(key: &str) -> Option<&'static str> {
+    ADHOC_RISKY.iter().find(|(k, _)| *k == key).map(|(_, reason)| *reason)
+}
+
+/// Convert a JSON value to its plist equivalent (`null` has none)
+pub fn json_to_plist(value: &serde_json::Value) -> Option<plist::Value> {
+    use serde_json::Value as Json;
+
+    match value {
+        Json::Null => None,
+        Json::Bool(b) => Some(plist::Value::Boolean(*b)),
+        Json::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Some(plist::Value::Integer(i.into()))
+            } else if let Some(u) = n.as_u64() {
+                Some(plist::Value::Integer(u.into()))
+            } else {
+                n.as_f64().map(plist::Value::Real)
+            }
+        }
+        Json::String(s) => Some(plist::Value::String(s.clone())),
+        Json::Array(arr) => Some(plist::Value::Array(
+            arr.iter().filter_map(json_to_plist).collect(),
+        )),
+        Json::Object(obj) => Some(plist::Value::Dictionary(
+            obj.iter()
+                .filter_map(|(k, v)| json_to_plist(v).map(|v| (k.clone(), v)))
+                .collect(),
+        )),
+    }
+}
@@ -0,0 +1,195 @@
+use crate::hooks::HookStage;
+use crate::version::Version;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RuzuleError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("Plist error: {0}")]
+    Plist(#[from] plist::Error),
+
+    #[error("Image error: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("Goblin error: {0}")]
+    Goblin(#[from] goblin::error::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("UTF-8 error: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+
+    #[error("WalkDir error: {0}")]
+    WalkDir(#[from] walkdir::Error),
+
+    #[error("File not found: {0}")]
+    FileNotFound(PathBuf),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Invalid IPA: {0}")]
+    InvalidIpa(String),
+
+    #[error("Invalid app bundle: {0}")]
+    InvalidAppBundle(String),
+
+    #[error("Encrypted binary: {0}")]
+    EncryptedBinary(PathBuf),
+
+    #[error("Unsupported platform: {0}")]
+    UnsupportedPlatform(String),
+
+    #[error("External tool failed: {0}")]
+    ToolFailed(String),
+
+    #[error("Mach-O manipulation error: {0}")]
+    MachO(String),
+
+    /// The binary's header padding can't fit a new load command or a longer path
+    #[error("Not enough space for new {what} (need {needed}, have {available})")]
+    NoLoadCommandSpace {
+        what: &'static str,
+        needed: usize,
+        available: usize,
+    },
+
+    #[error("Signing error: {0}")]
+    Sign(String),
+
+    #[error("Download failed: {0}")]
+    Download(String),
+
+    #[error("Integrity check failed: {0}")]
+    Integrity(String),
+
+    /// An injected binary needs a newer OS than the app's minimum, with `MinimumCheck::Strict`
+    #[error("{name} requires OS {required}, but the app's minimum is {minimum}")]
+    NewerOsRequired {
+        name: String,
+        required: Version,
+        minimum: Version,
+    },
+
+    /// An injected binary has no slice the app's executable can load
+    #[error(
+        "{name} is built for {}, but the app needs {}",
+        .found.join(", "),
+        .required.join(" or ")
+    )]
+    ArchMismatch {
+        name: String,
+        found: Vec<String>,
+        required: Vec<String>,
+    },
+
+    /// Less free space than a run needs, found before starting it
+    #[error(
+        "Not enough space in {}: need about {} MB, {} MB free",
+        .path.display(),
+        .needed / 1_000_000,
+        .available / 1_000_000
+    )]
+    NoSpace {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+
+    /// A user hook returned an error
+    #[error("{stage} hook failed: {message}")]
+    Hook { stage: HookStage, message: String },
+
+    /// Another ruzule process holds the lock on an output, work directory or the cache
+    #[error("{0} is in use by another ruzule process")]
+    Locked(String),
+
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+impl RuzuleError {
+    /// Stable identifier for frontends. Codes are never reused or renamed;
+    /// match on these rather than on the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuzuleError::Io(_) | RuzuleError::WalkDir(_) => "E_IO",
+            RuzuleError::Zip(_) => "E_ZIP",
+            RuzuleError::Plist(_) => "E_PLIST",
+            RuzuleError::Image(_) => "E_IMAGE",
+            RuzuleError::Goblin(_) => "E_MACHO_PARSE",
+            RuzuleError::Json(_) => "E_JSON",
+            RuzuleError::Utf8(_) => "E_UTF8",
+            RuzuleError::FileNotFound(_) => "E_NOT_FOUND",
+            RuzuleError::InvalidInput(_) => "E_BAD_INPUT",
+            RuzuleError::InvalidIpa(_) => "E_BAD_IPA",
+            RuzuleError::InvalidAppBundle(_) => "E_BAD_APP",
+            RuzuleError::EncryptedBinary(_) => "E_ENCRYPTED",
+            RuzuleError::UnsupportedPlatform(_) => "E_UNSUPPORTED_PLATFORM",
+            RuzuleError::ToolFailed(_) => "E_TOOL_FAILED",
+            RuzuleError::MachO(_) => "E_MACHO",
+            RuzuleError::NoLoadCommandSpace { .. } => "E_NO_SPACE_LC",
+            RuzuleError::Sign(_) => "E_SIGN",
+            RuzuleError::Download(_) => "E_DOWNLOAD",
+            RuzuleError::Integrity(_) => "E_INTEGRITY",
+            RuzuleError::NewerOsRequired { .. } => "E_MIN_OS",
+            RuzuleError::ArchMismatch { .. } => "E_ARCH",
+            RuzuleError::NoSpace { .. } => "E_DISK_SPACE",
+            RuzuleError::Hook { .. } => "E_HOOK",
+            RuzuleError::Locked(_) => "E_LOCKED",
+            RuzuleError::Cancelled => "E_CANCELLED",
+        }
+    }
+
+    /// The file the error is about, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            RuzuleError::FileNotFound(path)
+            | RuzuleError::EncryptedBinary(path)
+            | RuzuleError::NoSpace { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// Serialized as `{"code": .., "message": ..}` plus any context fields
+/// (`path`, or `what`/`needed`/`available` for [`RuzuleError::NoLoadCommandSpace`],
+/// `needed`/`available` for [`RuzuleError::NoSpace`])
+impl Serialize for RuzuleError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("code", self.code())?;
+        map.serialize_entry("message", &self.to_string())?;
+        if let Some(path) = self.path() {
+            map.serialize_entry("path", path)?;
+        }
+        if let RuzuleError::NoLoadCommandSpace {
+            what,
+            needed,
+            available,
+        } = self
+        {
+            map.serialize_entry("what", what)?;
+            map.serialize_entry("needed", needed)?;
+            map.serialize_entry("available", available)?;
+        }
+        if let RuzuleError::NoSpace {
+            needed, available, ..
+        } = self
+        {
+            map.serialize_entry("needed", needed)?;
+            map.serialize_entry("available", available)?;
+        }
+        map.end()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, RuzuleError>;
@@ -0,0 +1,463 @@
+//! Progress reporting.
+//!
+//! Library code never prints; it emits [`Event`]s to whatever
+//! [`EventHandler`] the caller registered. Every event is also logged through
+//! the `log` crate under the `ruzule` target, so installing a logger is
+//! enough to see them.
+
+use crate::hooks::HookStage;
+use crate::plist_ext::PlistFormat;
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How an event is presented by a frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventLevel {
+    /// Regular progress (`[*]` in the CLI)
+    Info,
+    /// Something the user may want to know (`[?]`)
+    Notice,
+    /// Something likely wrong that didn't stop the run (`[!]`)
+    Warning,
+}
+
+/// A change made to an Info.plist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum PlistChange {
+    Name(String),
+    LocalizedNames(usize),
+    /// Locales added to `CFBundleLocalizations`
+    Localizations(Vec<String>),
+    Version(String),
+    BundleId(String),
+    OtherBundleIds(usize),
+    MinimumVersion(String),
+    RemovedSupportedDevices,
+    EnabledDocuments,
+    /// Number of keys merged from another plist
+    Merged(usize),
+    /// Rewritten in another encoding
+    Format(PlistFormat),
+    /// Keys (or key paths) deleted
+    RemovedKeys(Vec<String>),
+    /// Schemes added to `CFBundleURLTypes`
+    UrlSchemesAdded(Vec<String>),
+    /// Schemes removed from `CFBundleURLTypes`
+    UrlSchemesRemoved(Vec<String>),
+}
+
+/// A long-running step [`Event::Progress`] reports on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressStage {
+    /// Unpacking the .ipa, counted in bytes
+    Extract,
+    /// Fakesigning, counted in binaries
+    Sign,
+    /// Thinning, counted in binaries
+    Thin,
+    /// Writing the .ipa, counted in bytes
+    Pack,
+}
+
+/// Where a .cyan option ended up coming from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CyanOrigin {
+    /// Set by this .cyan
+    Set,
+    /// Replaced a value from an earlier .cyan (`None` means the caller's own value)
+    Overrode(Option<String>),
+    /// Dropped because an earlier .cyan already set it
+    Ignored(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    Extracting,
+    Extracted,
+    /// A .deb was unpacked into its payload
+    DebExtracted(String),
+    CyanLoaded(String),
+    CyanOption {
+        key: String,
+        source: String,
+        origin: CyanOrigin,
+    },
+    EncryptedIgnored,
+    Injected(String),
+    /// A bundled framework or library a tweak depends on was added
+    AutoInjected(String),
+    Replacing(String),
+    DependencyFixed {
+        binary: String,
+        from: String,
+        to: String,
+        common: bool,
+    },
+    InstallNameFixed {
+        binary: String,
+        name: String,
+    },
+    PlistChanged(PlistChange),
+    IconChanged,
+    EntitlementsEdited(String),
+    EntitlementsMerged,
+    EntitlementsRestored,
+    Removed(String),
+    /// A framework deleted with `--remove-framework`, its size, and how many
+    /// binaries linked it (now weakly)
+    FrameworkRemoved {
+        name: String,
+        bytes: u64,
+        weakened: usize,
+    },
+    PluginsPatched(usize),
+    /// How far a long step has got, emitted a few times a second while it runs.
+    /// `eta` is extrapolated from the rate so far, once there's enough to go on.
+    Progress {
+        stage: ProgressStage,
+        done: u64,
+        total: u64,
+        eta: Option<Duration>,
+    },
+    /// Number of binaries fakesigned
+    Signed(usize),
+    /// The app was signed with a certificate, by its name
+    SignedWith(String),
+    Thinned(usize),
+    Downloading(String),
+    HookRan(HookStage),
+    Packing(PathBuf),
+    Done(PathBuf),
+    /// The working directory was left behind for inspection
+    TempKept(PathBuf),
+    Notice(String),
+    Warning(String),
+}
+
+impl EventLevel {
+    /// `log` level events of this kind are recorded at
+    pub fn log_level(self) -> log::Level {
+        match self {
+            EventLevel::Info => log::Level::Info,
+            EventLevel::Notice => log::Level::Warn,
+            EventLevel::Warning => log::Level::Error,
+        }
+    }
+}
+
+impl Event {
+    pub fn level(&self) -> EventLevel {
+        match self {
+            Event::EncryptedIgnored
+            | Event::Replacing(_)
+            | Event::TempKept(_)
+            | Event::Notice(_) => EventLevel::Notice,
+            Event::CyanOption { origin, .. } if *origin != CyanOrigin::Set => EventLevel::Notice,
+            Event::Warning(_) => EventLevel::Warning,
+            _ => EventLevel::Info,
+        }
+    }
+}
+
+const COUNT: &str = "\x1b[96m";
+const RESET: &str = "\x1b[0m";
+
+/// Drop the terminal colour codes events use for counts
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+pub(crate) fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c == 'm' {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+impl fmt::Display for PlistChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlistChange::Name(name) => write!(f, "changed name to \"{}\"", name),
+            PlistChange::LocalizedNames(n) => {
+                write!(f, "changed {}{}{} localized names", COUNT, n, RESET)
+            }
+            PlistChange::Localizations(locales) => {
+                write!(f, "added localizations {}", locales.join(", "))
+            }
+            PlistChange::Version(version) => write!(f, "changed version to \"{}\"", version),
+            PlistChange::BundleId(id) => write!(f, "changed bundle id to \"{}\"", id),
+            PlistChange::OtherBundleIds(n) => {
+                write!(f, "changed {}{}{} other bundle ids", COUNT, n, RESET)
+            }
+            PlistChange::MinimumVersion(v) => write!(f, "changed minimum version to \"{}\"", v),
+            PlistChange::RemovedSupportedDevices => f.write_str("removed UISupportedDevices"),
+            PlistChange::EnabledDocuments => f.write_str("enabled documents support"),
+            PlistChange::Merged(n) => write!(f, "merged plist ({} keys)", n),
+            PlistChange::Format(format) => write!(f, "rewrote Info.plist as {}", format),
+            PlistChange::RemovedKeys(keys) => write!(f, "removed {}", keys.join(", ")),
+            PlistChange::UrlSchemesAdded(schemes) => {
+                write!(f, "added URL schemes {}", schemes.join(", "))
+            }
+            PlistChange::UrlSchemesRemoved(schemes) => {
+                write!(f, "removed URL schemes {}", schemes.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Extracting => f.write_str("extracting..."),
+            Event::Extracted => f.write_str("extracted"),
+            Event::DebExtracted(name) => write!(f, "extracted {}", name),
+            Event::CyanLoaded(name) => write!(f, "loading {}", name),
+            Event::CyanOption {
+                key,
+                source,
+                origin,
+            } => match origin {
+                CyanOrigin::Set => write!(f, "{} set by {}", key, source),
+                CyanOrigin::Overrode(Some(prev)) => {
+                    write!(f, "{} from {} overridden by {}", key, prev, source)
+                }
+                CyanOrigin::Overrode(None) => {
+                    write!(f, "{} from command line overridden by {}", key, source)
+                }
+                CyanOrigin::Ignored(prev) => {
+                    write!(f, "{} from {} ignored, already set by {}", key, source, prev)
+                }
+            },
+            Event::EncryptedIgnored => f.write_str("main binary is encrypted, ignoring"),
+            Event::Injected(name) => write!(f, "injected {}", name),
+            Event::AutoInjected(name) => write!(f, "auto-injected {}", name),
+            Event::Replacing(name) => write!(f, "{} already existed, replacing", name),
+            Event::DependencyFixed {
+                binary,
+                from,
+                to,
+                common,
+            } => {
+                let kind = if *common { "common dependency" } else { "dependency" };
+                write!(f, "fixed {} in {}: {} -> {}", kind, binary, from, to)
+            }
+            Event::InstallNameFixed { binary, name } => {
+                write!(f, "fixed install name for {}: -> {}", binary, name)
+            }
+            Event::PlistChanged(change) => change.fmt(f),
+            Event::IconChanged => f.write_str("updated app icon"),
+            Event::EntitlementsEdited(binary) => write!(f, "edited entitlements of {}", binary),
+            Event::EntitlementsMerged => f.write_str("merged new entitlements"),
+            Event::EntitlementsRestored => f.write_str("restored entitlements"),
+            Event::Removed(what) => write!(f, "removed {}", what),
+            Event::FrameworkRemoved {
+                name,
+                bytes,
+                weakened,
+            } => write!(
+                f,
+                "removed {}.framework, saving {}{:.1} MB{}; weak-linked from {} binaries",
+                name,
+                COUNT,
+                *bytes as f64 / (1024.0 * 1024.0),
+                RESET,
+                weakened
+            ),
+            Event::PluginsPatched(n) => {
+                write!(f, "patched {}{}{} executable(s) for plugin support", COUNT, n, RESET)
+            }
+            Event::Progress {
+                stage,
+                done,
+                total,
+                eta,
+            } => {
+                let percent = (done * 100).checked_div(*total).unwrap_or(100).min(100);
+                let mb = |n: u64| n as f64 / (1024.0 * 1024.0);
+                match stage {
+                    ProgressStage::Extract | ProgressStage::Pack => write!(
+                        f,
+                        "{} {:.1}/{:.1} MB ({}%)",
+                        if *stage == ProgressStage::Extract { "extracting" } else { "packing" },
+                        mb(*done),
+                        mb(*total),
+                        percent
+                    )?,
+                    ProgressStage::Sign | ProgressStage::Thin => write!(
+                        f,
+                        "{} {}/{} binaries ({}%)",
+                        if *stage == ProgressStage::Sign { "fakesigning" } else { "thinning" },
+                        done,
+                        total,
+                        percent
+                    )?,
+                }
+                match eta {
+                    Some(eta) => {
+                        let secs = eta.as_secs();
+                        write!(f, ", {}:{:02} left", secs / 60, secs % 60)
+                    }
+                    None => Ok(()),
+                }
+            }
+            Event::Signed(n) => write!(f, "fakesigned {}{}{} item(s)", COUNT, n, RESET),
+            Event::SignedWith(name) => write!(f, "signed as {}", name),
+            Event::Thinned(n) => write!(f, "thinned {}{}{} item(s)", COUNT, n, RESET),
+            Event::Downloading(url) => write!(f, "downloading {}", url),
+            Event::HookRan(stage) => write!(f, "ran {} hook", stage),
+            Event::Packing(_) => f.write_str("generating..."),
+            Event::Done(output) => write!(f, "done: {}", output.display()),
+            Event::TempKept(path) => write!(f, "working directory kept at {}", path.display()),
+            Event::Notice(msg) | Event::Warning(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// Receives pipeline events; implemented for any `Fn(&Event)`
+pub trait EventHandler: Send + Sync {
+    fn handle(&self, event: &Event);
+}
+
+impl<F: Fn(&Event) + Send + Sync> EventHandler for F {
+    fn handle(&self, event: &Event) {
+        self(event)
+    }
+}
+
+/// Cheaply cloneable handle passed down the pipeline
+#[derive(Clone, Default)]
+pub struct Events {
+    handler: Option<Arc<dyn EventHandler>>,
+    recorded: Option<Arc<Mutex<Vec<Event>>>>,
+}
+
+impl Events {
+    pub fn new<H: EventHandler + 'static>(handler: H) -> Self {
+        Self {
+            handler: Some(Arc::new(handler)),
+            recorded: None,
+        }
+    }
+
+    /// A handle that drops every event
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Also keep every event, for [`Events::recorded`] and [`Events::warnings`]
+    pub fn recording(mut self) -> Self {
+        self.recorded = Some(Arc::default());
+        self
+    }
+
+    /// Events emitted so far through this handle or its clones
+    pub fn recorded(&self) -> Vec<Event> {
+        self.recorded
+            .as_ref()
+            .map(|r| r.lock().map(|r| r.clone()).unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    /// Text of the warnings emitted so far
+    pub fn warnings(&self) -> Vec<String> {
+        self.recorded()
+            .iter()
+            .filter(|e| e.level() == EventLevel::Warning)
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    pub fn emit(&self, event: Event) {
+        if let Event::Progress { .. } = event {
+            // Its own target, so a frontend can draw it over one line; not
+            // recorded, as nothing reports on it afterwards
+            log::log!(target: "ruzule::progress", event.level().log_level(), "{}", event);
+        } else {
+            log::log!(target: "ruzule", event.level().log_level(), "{}", event);
+            if let Some(Ok(mut recorded)) = self.recorded.as_ref().map(|r| r.lock()) {
+                recorded.push(event.clone());
+            }
+        }
+        if let Some(ref handler) = self.handler {
+            handler.handle(&event);
+        }
+    }
+
+    /// Emit a [`Event::Warning`] for something that failed without stopping the run
+    pub fn warn<S: Into<String>>(&self, message: S) {
+        self.emit(Event::Warning(message.into()));
+    }
+}
+
+/// Counts the work done on one [`ProgressStage`], which may be spread over
+/// threads, and emits [`Event::Progress`] no more than every 250 ms and once
+/// when it's complete
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+pub(crate) struct Progress {
+    events: Events,
+    stage: ProgressStage,
+    total: u64,
+    done: AtomicU64,
+    start: Instant,
+    /// Milliseconds after `start` of the last event
+    last: AtomicU64,
+}
+
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+impl Progress {
+    const INTERVAL_MS: u64 = 250;
+
+    pub(crate) fn new(events: &Events, stage: ProgressStage, total: u64) -> Self {
+        Self {
+            events: events.clone(),
+            stage,
+            total,
+            done: AtomicU64::new(0),
+            start: Instant::now(),
+            last: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn add(&self, n: u64) {
+        let done = self.done.fetch_add(n, Ordering::Relaxed) + n;
+        let completed = done >= self.total && done - n < self.total;
+        let elapsed = self.start.elapsed();
+        let ms = elapsed.as_millis() as u64;
+        let last = self.last.load(Ordering::Relaxed);
+        let due = ms >= last + Self::INTERVAL_MS
+            && self.last.compare_exchange(last, ms, Ordering::Relaxed, Ordering::Relaxed).is_ok();
+        if !completed && !due {
+            return;
+        }
+
+        let eta = (!completed && done > 0 && ms >= 1000)
+            .then(|| elapsed.mul_f64(self.total.saturating_sub(done) as f64 / done as f64));
+        self.events.emit(Event::Progress {
+            stage: self.stage,
+            done: done.min(self.total),
+            total: self.total,
+            eta,
+        });
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.handler.is_some() { "Events(handler)" } else { "Events(none)" })
+    }
+}
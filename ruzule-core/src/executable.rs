@@ -0,0 +1,369 @@
+pub use crate::buffer::strip_jailbreak_root;
+use crate::buffer::Arch;
+use crate::entitlements::EntitlementOps;
+use crate::error::{Result, RuzuleError};
+use crate::events::{Event, Events};
+use crate::frameworks::SubstrateBackend;
+use crate::macho;
+use crate::sign;
+use crate::temp::TempFile;
+use crate::version::Version;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone)]
+pub struct CommonDep {
+    pub name: &'static str,
+    pub path: &'static str,
+}
+
+pub static COMMON_DEPS: LazyLock<HashMap<&'static str, CommonDep>> = LazyLock::new(|| {
+    let mut m = HashMap::new();
+    m.insert("substrate.", CommonDep {
+        name: "CydiaSubstrate.framework",
+        path: "@rpath/CydiaSubstrate.framework/CydiaSubstrate",
+    });
+    m.insert("orion.", CommonDep {
+        name: "Orion.framework",
+        path: "@rpath/Orion.framework/Orion",
+    });
+    m.insert("cephei.", CommonDep {
+        name: "Cephei.framework",
+        path: "@rpath/Cephei.framework/Cephei",
+    });
+    m.insert("cepheiui.", CommonDep {
+        name: "CepheiUI.framework",
+        path: "@rpath/CepheiUI.framework/CepheiUI",
+    });
+    m.insert("cepheiprefs.", CommonDep {
+        name: "CepheiPrefs.framework",
+        path: "@rpath/CepheiPrefs.framework/CepheiPrefs",
+    });
+    m
+});
+
+/// Whether `dep` is CydiaSubstrate, as the framework or `libsubstrate.dylib`, wherever it's
+/// installed. These are pointed at the [`SubstrateBackend`] rather than through [`COMMON_DEPS`].
+pub fn is_substrate_dep(dep: &str) -> bool {
+    let name = dep.rsplit('/').next().unwrap_or(dep).to_lowercase();
+    name == "cydiasubstrate" || name == "libsubstrate.dylib"
+}
+
+pub struct Executable {
+    pub path: PathBuf,
+    pub name: String,
+    events: Events,
+}
+
+impl Executable {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if !path.exists() {
+            return Err(RuzuleError::FileNotFound(path));
+        }
+
+        let name = path
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Ok(Self {
+            path,
+            name,
+            events: Events::none(),
+        })
+    }
+
+    /// Report changes to `events`
+    pub fn with_events(mut self, events: Events) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn is_encrypted(&self) -> Result<bool> {
+        macho::is_encrypted(&self.path)
+    }
+
+    pub fn remove_signature(&self) -> Result<()> {
+        sign::remove_signature(&self.path)
+    }
+
+    pub fn fakesign(&self) -> Result<bool> {
+        sign::fakesign(&self.path)
+    }
+
+    pub fn thin(&self) -> Result<bool> {
+        macho::thin_to_arm64(&self.path)
+    }
+
+    /// Keep only the `arch` slice; true if there were others
+    pub fn thin_to(&self, arch: Arch) -> Result<bool> {
+        macho::thin_to_arch(&self.path, arch)
+    }
+
+    /// Weakly link `dylib_path` (e.g. `@rpath/Tweak.dylib`) from this binary
+    pub fn inject_dylib(&self, dylib_path: &str) -> Result<()> {
+        let dropped = macho::add_weak_dylib(&self.path, dylib_path)?;
+        self.warn_dropped(dylib_path, &dropped);
+        Ok(())
+    }
+
+    pub fn add_rpath(&self, rpath: &str) -> Result<()> {
+        let dropped = macho::add_rpath(&self.path, rpath)?;
+        self.warn_dropped(rpath, &dropped);
+        Ok(())
+    }
+
+    /// Warn about load commands dropped to make room for `added`
+    fn warn_dropped(&self, added: &str, dropped: &[&str]) {
+        if !dropped.is_empty() {
+            self.events.warn(format!(
+                "no room for {} in {}'s header padding, dropped {} (only tools read them)",
+                added,
+                self.name,
+                dropped.join(", ")
+            ));
+        }
+    }
+
+    pub fn get_dependencies(&self) -> Result<Vec<String>> {
+        macho::get_dependencies(&self.path)
+    }
+
+    /// Unlink `dylib_path`; true if the binary loaded it
+    pub fn remove_dylib(&self, dylib_path: &str) -> Result<bool> {
+        macho::remove_dylib(&self.path, dylib_path)
+    }
+
+    pub fn rpaths(&self) -> Result<Vec<String>> {
+        macho::rpaths(&self.path)
+    }
+
+    /// Delete every copy of `rpath`; true if the binary had it
+    pub fn remove_rpath(&self, rpath: &str) -> Result<bool> {
+        macho::remove_rpath(&self.path, rpath)
+    }
+
+    pub fn change_dependency(&self, old: &str, new: &str) -> Result<()> {
+        macho::replace_dylib(&self.path, old, new)
+    }
+
+    pub fn change_install_name(&self, new_name: &str) -> Result<()> {
+        macho::change_install_name(&self.path, new_name)
+    }
+
+    /// Weak-link each of `frameworks`; true if any was linked
+    pub fn weaken_frameworks(&self, frameworks: &[String]) -> Result<bool> {
+        let mut weakened = false;
+        for framework in frameworks {
+            weakened |= macho::weaken_framework(&self.path, framework)?;
+        }
+        Ok(weakened)
+    }
+
+    /// Re-sign with the binary's current entitlements edited by `ops`
+    pub fn edit_entitlements(&self, ops: &EntitlementOps) -> Result<()> {
+        let data = sign::extract_entitlements(&self.path)?;
+        let mut entitlements: plist::Dictionary = if data.is_empty() {
+            plist::Dictionary::new()
+        } else {
+            plist::from_bytes(&data)?
+        };
+
+        ops.apply(&mut entitlements);
+
+        let ent_file = TempFile::new_in(&std::env::temp_dir())?;
+        plist::to_file_xml(ent_file.path(), &entitlements)?;
+        sign::sign_with_entitlements(&self.path, ent_file.path())?;
+
+        self.events.emit(Event::EntitlementsEdited(self.name.clone()));
+        Ok(())
+    }
+
+    pub fn fix_common_dependencies(
+        &self,
+        needed: &mut HashSet<String>,
+        backend: SubstrateBackend,
+    ) -> Result<()> {
+        self.remove_signature()?;
+
+        let deps = self.get_dependencies()?;
+        for dep in deps {
+            let dep_lower = strip_jailbreak_root(&dep).to_lowercase();
+            if dep_lower.ends_with("/libroothide.dylib") {
+                self.events.emit(Event::Notice(format!(
+                    "{} links {}, which only exists on roothide jailbreaks",
+                    self.name, dep
+                )));
+            }
+
+            if is_substrate_dep(&dep) {
+                needed.insert("substrate.".to_string());
+                let target = backend.install_path();
+                if dep != target {
+                    self.change_dependency(&dep, target)?;
+                    self.events.emit(Event::DependencyFixed {
+                        binary: self.name.clone(),
+                        from: dep.clone(),
+                        to: target.to_string(),
+                        common: true,
+                    });
+                }
+                continue;
+            }
+
+            for (key, info) in COMMON_DEPS.iter() {
+                if dep_lower.contains(key) {
+                    needed.insert(key.to_string());
+
+                    let target = info.path;
+                    if dep != target {
+                        self.change_dependency(&dep, target)?;
+                        self.events.emit(Event::DependencyFixed {
+                            binary: self.name.clone(),
+                            from: dep.clone(),
+                            to: target.to_string(),
+                            common: true,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Swift runtime libraries this binary expects to find through @rpath
+    pub fn swift_dependencies(&self) -> Result<Vec<String>> {
+        Ok(crate::swift::rpath_swift_libs(&self.get_dependencies()?))
+    }
+
+    pub fn fix_dependencies(&self, tweaks: &HashMap<String, PathBuf>) -> Result<()> {
+        let deps = self.get_dependencies()?;
+
+        for dep in deps {
+            for cname in tweaks.keys() {
+                if dep.contains(cname) {
+                    let npath = if cname.ends_with(".framework") {
+                        let framework_name = cname.strip_suffix(".framework").unwrap();
+                        format!("@rpath/{}/{}", cname, framework_name)
+                    } else {
+                        format!("@rpath/{}", cname)
+                    };
+
+                    if dep != npath {
+                        self.change_dependency(&dep, &npath)?;
+                        self.events.emit(Event::DependencyFixed {
+                            binary: self.name.clone(),
+                            from: dep.clone(),
+                            to: npath,
+                            common: false,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn fix_install_name(&self, tweaks: &HashMap<String, PathBuf>) -> Result<()> {
+        // Fix install name (LC_ID_DYLIB) for dylibs
+        for cname in tweaks.keys() {
+            if self.name == *cname {
+                let npath = if cname.ends_with(".framework") {
+                    let framework_name = cname.strip_suffix(".framework").unwrap();
+                    format!("@rpath/{}/{}", cname, framework_name)
+                } else {
+                    format!("@rpath/{}", cname)
+                };
+                self.change_install_name(&npath)?;
+                self.events.emit(Event::InstallNameFixed {
+                    binary: self.name.clone(),
+                    name: npath,
+                });
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct MainExecutable {
+    pub inner: Executable,
+    pub bundle_path: PathBuf,
+}
+
+impl MainExecutable {
+    pub fn new<P: AsRef<Path>>(path: P, bundle_path: P) -> Result<Self> {
+        Self::with_events(path, bundle_path, Events::none())
+    }
+
+    pub fn with_events<P: AsRef<Path>>(path: P, bundle_path: P, events: Events) -> Result<Self> {
+        let inner = Executable::new(path)?.with_events(events);
+        Ok(Self {
+            inner,
+            bundle_path: bundle_path.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn is_encrypted(&self) -> Result<bool> {
+        self.inner.is_encrypted()
+    }
+
+    pub fn fakesign(&self) -> Result<bool> {
+        self.inner.fakesign()
+    }
+
+    pub fn thin(&self) -> Result<bool> {
+        self.inner.thin()
+    }
+
+    pub fn thin_to(&self, arch: Arch) -> Result<bool> {
+        self.inner.thin_to(arch)
+    }
+
+    pub fn add_rpath(&self, rpath: &str) -> Result<()> {
+        self.inner.add_rpath(rpath)
+    }
+
+    pub fn inject_dylib(&self, dylib_path: &str) -> Result<()> {
+        self.inner.inject_dylib(dylib_path)
+    }
+
+    pub fn write_entitlements<P: AsRef<Path>>(&self, output: P) -> Result<bool> {
+        let ent_data = sign::extract_entitlements(&self.inner.path)?;
+        if ent_data.is_empty() {
+            return Ok(false);
+        }
+        std::fs::write(output, ent_data)?;
+        Ok(true)
+    }
+
+    /// Set the minimum OS in the binary's load commands, re-signing with its entitlements
+    pub fn set_min_os_version(&self, version: Version) -> Result<()> {
+        let ent_file = TempFile::new_in(&std::env::temp_dir())?;
+        let has_entitlements = self.write_entitlements(ent_file.path())?;
+        macho::set_min_os_version(&self.inner.path, version)?;
+        if has_entitlements {
+            self.sign_with_entitlements(ent_file.path())?;
+        }
+        Ok(())
+    }
+
+    pub fn sign_with_entitlements<P: AsRef<Path>>(&self, entitlements: P) -> Result<bool> {
+        sign::sign_with_entitlements(&self.inner.path, entitlements)
+    }
+
+    pub fn merge_entitlements<P: AsRef<Path>>(&self, entitlements: P) -> Result<()> {
+        if self.sign_with_entitlements(entitlements)? {
+            self.inner.events.emit(Event::EntitlementsMerged);
+        } else {
+            self.inner.events.emit(Event::Warning(
+                "failed to merge new entitlements, are they valid?".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+//! Packaging the output for on-device signers.
+//!
+//! [`ExportFormat::Esign`] puts the .ipa in a directory next to where it would
+//! have been written, with the files ESign and Feather pick up when it's
+//! imported as a local source:
+//!
+//! ```text
+//! Patched-esign/
+//!     Patched.ipa
+//!     icon.png                  the app's largest icon, if it has one
+//!     source.json               name, bundle id, version, size, file names
+//!     certificate.p12           with --export-cert
+//!     profile.mobileprovision   with --export-profile
+//! ```
+
+use crate::app_bundle::AppBundle;
+use crate::error::{Result, RuzuleError};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How the output is packaged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Just the .ipa (or .app)
+    #[default]
+    Ipa,
+    /// The .ipa with the metadata on-device signers import, see the [module docs](self)
+    Esign,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ipa" => Ok(ExportFormat::Ipa),
+            "esign" | "feather" => Ok(ExportFormat::Esign),
+            _ => Err(format!("unknown export format \"{}\" (expected ipa or esign)", s)),
+        }
+    }
+}
+
+const ICON: &str = "icon.png";
+const CERTIFICATE: &str = "certificate.p12";
+const PROFILE: &str = "profile.mobileprovision";
+
+/// The directory an ESign export of `output` is written to
+pub fn esign_dir(output: &Path) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{}-esign", stem))
+}
+
+/// `source.json`, in the AltStore source layout both apps read
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Source {
+    name: String,
+    identifier: String,
+    apps: Vec<SourceApp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    certificate: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provisioning_profile: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SourceApp {
+    name: String,
+    bundle_identifier: String,
+    version: String,
+    version_date: String,
+    size: u64,
+    #[serde(rename = "downloadURL")]
+    download_url: String,
+    #[serde(rename = "iconURL", skip_serializing_if = "Option::is_none")]
+    icon_url: Option<&'static str>,
+    localized_description: String,
+}
+
+/// Move the packed `ipa` into [`esign_dir`] with its metadata and the optional
+/// certificate and provisioning profile, replacing an earlier export. Returns
+/// the directory.
+pub fn esign(
+    app: &AppBundle,
+    ipa: &Path,
+    certificate: Option<&Path>,
+    profile: Option<&Path>,
+) -> Result<PathBuf> {
+    let dir = esign_dir(ipa);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+
+    let file_name = ipa
+        .file_name()
+        .ok_or_else(|| RuzuleError::InvalidInput(format!("bad output {}", ipa.display())))?;
+    let packed = dir.join(file_name);
+    fs::rename(ipa, &packed)?;
+
+    let icon = match app_icon(app) {
+        Some(path) => {
+            fs::copy(path, dir.join(ICON))?;
+            Some(ICON)
+        }
+        None => None,
+    };
+    if let Some(path) = certificate {
+        fs::copy(path, dir.join(CERTIFICATE))?;
+    }
+    if let Some(path) = profile {
+        fs::copy(path, dir.join(PROFILE))?;
+    }
+
+    let plist = |key: &str| app.plist.get_string(key).unwrap_or_default().to_string();
+    let name = Some(plist("CFBundleDisplayName"))
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| plist("CFBundleName"));
+    let bundle_id = plist("CFBundleIdentifier");
+    let source = Source {
+        name: name.clone(),
+        identifier: format!("{}.ruzule", bundle_id),
+        apps: vec![SourceApp {
+            localized_description: format!("{} patched with ruzule", name),
+            name,
+            bundle_identifier: bundle_id,
+            version: plist("CFBundleShortVersionString"),
+            version_date: today(),
+            size: fs::metadata(&packed)?.len(),
+            download_url: file_name.to_string_lossy().to_string(),
+            icon_url: icon,
+        }],
+        certificate: certificate.map(|_| CERTIFICATE),
+        provisioning_profile: profile.map(|_| PROFILE),
+    };
+    fs::write(dir.join("source.json"), serde_json::to_vec_pretty(&source)?)?;
+
+    Ok(dir)
+}
+
+/// The largest image named by the primary icon's `CFBundleIconFiles`
+fn app_icon(app: &AppBundle) -> Option<PathBuf> {
+    let names: Vec<String> = ["CFBundleIcons", "CFBundleIcons~ipad"]
+        .iter()
+        .filter_map(|key| app.plist.get(key)?.as_dictionary())
+        .filter_map(|icons| icons.get("CFBundlePrimaryIcon")?.as_dictionary())
+        .filter_map(|primary| primary.get("CFBundleIconFiles")?.as_array())
+        .flatten()
+        .filter_map(|name| name.as_string().map(str::to_string))
+        .collect();
+
+    // `AppIcon60x60` is stored as `AppIcon60x60@2x.png`, `AppIcon60x60@3x.png`...
+    fs::read_dir(&app.path)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            let file = entry.file_name().to_string_lossy().to_string();
+            file.ends_with(".png") && names.iter().any(|name| file.starts_with(name.as_str()))
+        })
+        .filter_map(|entry| Some((entry.metadata().ok()?.len(), entry.path())))
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// `YYYY-MM-DD` in UTC
+fn today() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    // Days to a civil date, from Howard Hinnant's `civil_from_days`
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,300 @@
+//! C ABI for non-Rust frontends (`ffi` feature). See `include/ruzule.h`.
+//!
+//! Build a shared or static library with
+//! `cargo rustc -p ruzule-core --profile ffi --lib --features ffi --crate-type cdylib`
+//! (or `staticlib`). The `ffi` profile unwinds, so `catch_unwind` in
+//! [`ruzule_inject`] can return [`RUZULE_ERR_PANIC`]; under `panic = "abort"`
+//! a panic takes the host process down.
+
+use crate::cancel::CancellationToken;
+use crate::error::RuzuleError;
+use crate::events::{strip_ansi, Event, EventLevel};
+use crate::frameworks::SubstrateBackend;
+use crate::injector::{InjectOptions, Injector};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const RUZULE_OK: i32 = 0;
+/// A required pointer was null or a string wasn't valid UTF-8
+pub const RUZULE_ERR_ARGUMENT: i32 = -1;
+/// The pipeline failed; see `ruzule_last_error`
+pub const RUZULE_ERR_FAILED: i32 = -2;
+pub const RUZULE_ERR_CANCELLED: i32 = -3;
+/// A bug in ruzule; only returned when built with unwinding (the `ffi` profile)
+pub const RUZULE_ERR_PANIC: i32 = -4;
+
+/// Called for each progress event with its level (0 info, 1 notice, 2 warning)
+/// and a NUL-terminated message valid only for the duration of the call. Runs
+/// on worker threads too, one call at a time.
+pub type RuzuleProgressCallback =
+    Option<extern "C" fn(level: i32, message: *const c_char, user_data: *mut c_void)>;
+
+#[repr(C)]
+pub struct RuzuleOptions {
+    pub input: *const c_char,
+    pub output: *const c_char,
+    pub files: *const *const c_char,
+    pub files_len: usize,
+    pub cyans: *const *const c_char,
+    pub cyans_len: usize,
+    pub name: *const c_char,
+    pub version: *const c_char,
+    pub bundle_id: *const c_char,
+    pub minimum: *const c_char,
+    pub icon: *const c_char,
+    pub plist: *const c_char,
+    pub entitlements: *const c_char,
+    pub remove_supported_devices: bool,
+    pub no_watch: bool,
+    pub enable_documents: bool,
+    pub fakesign: bool,
+    pub thin: bool,
+    pub remove_extensions: bool,
+    pub remove_encrypted: bool,
+    pub compress: u32,
+    pub ignore_encrypted: bool,
+    pub use_frameworks_dir: bool,
+    pub patch_plugins: bool,
+    /// "substrate", "ellekit" or "libhooker"; null for the default
+    pub substrate_backend: *const c_char,
+    pub progress: RuzuleProgressCallback,
+    pub user_data: *mut c_void,
+    /// From `ruzule_cancel_new`; may be null
+    pub cancel: *const RuzuleCancel,
+}
+
+/// Opaque cancellation handle
+pub struct RuzuleCancel(CancellationToken);
+
+/// Code and message of the last error on this thread
+struct LastError {
+    code: CString,
+    message: CString,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(code: &str, message: String) {
+    let error = LastError {
+        code: CString::new(code).unwrap_or_default(),
+        message: CString::new(message.replace('\0', "")).unwrap_or_default(),
+    };
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(error));
+}
+
+/// Progress callback plus the caller's pointer, which the caller vouches for.
+/// Events come from worker threads too; `lock` keeps calls from overlapping,
+/// so callers don't have to make the callback thread-safe.
+struct Progress {
+    callback: extern "C" fn(i32, *const c_char, *mut c_void),
+    user_data: *mut c_void,
+    lock: Mutex<()>,
+}
+
+unsafe impl Send for Progress {}
+unsafe impl Sync for Progress {}
+
+impl Progress {
+    fn report(&self, event: &Event) {
+        let level = match event.level() {
+            EventLevel::Info => 0,
+            EventLevel::Notice => 1,
+            EventLevel::Warning => 2,
+        };
+        if let Ok(message) = CString::new(strip_ansi(&event.to_string())) {
+            let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+            (self.callback)(level, message.as_ptr(), self.user_data);
+        }
+    }
+}
+
+unsafe fn opt_str(ptr: *const c_char) -> Result<Option<String>, String> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(|s| Some(s.to_string()))
+        .map_err(|_| "string argument is not valid UTF-8".to_string())
+}
+
+unsafe fn str_list(ptr: *const *const c_char, len: usize) -> Result<Vec<PathBuf>, String> {
+    if ptr.is_null() || len == 0 {
+        return Ok(Vec::new());
+    }
+    std::slice::from_raw_parts(ptr, len)
+        .iter()
+        .map(|&p| opt_str(p)?.map(PathBuf::from).ok_or("null entry in list".to_string()))
+        .collect()
+}
+
+unsafe fn build_injector(opts: &RuzuleOptions) -> Result<Injector, String> {
+    let input = opt_str(opts.input)?.ok_or("input is required")?;
+    let substrate_backend = match opt_str(opts.substrate_backend)? {
+        Some(backend) => backend.parse::<SubstrateBackend>()?,
+        None => Default::default(),
+    };
+
+    let options = InjectOptions {
+        output: opt_str(opts.output)?.map(PathBuf::from),
+        cyans: str_list(opts.cyans, opts.cyans_len)?,
+        files: str_list(opts.files, opts.files_len)?,
+        name: opt_str(opts.name)?,
+        version: opt_str(opts.version)?,
+        bundle_id: opt_str(opts.bundle_id)?,
+        minimum: opt_str(opts.minimum)?,
+        icon: opt_str(opts.icon)?.map(PathBuf::from),
+        plist: opt_str(opts.plist)?.map(PathBuf::from),
+        entitlements: opt_str(opts.entitlements)?.map(PathBuf::from),
+        remove_supported_devices: opts.remove_supported_devices,
+        no_watch: opts.no_watch,
+        enable_documents: opts.enable_documents,
+        fakesign: opts.fakesign,
+        thin: opts.thin,
+        remove_extensions: opts.remove_extensions,
+        remove_encrypted: opts.remove_encrypted,
+        compress: opts.compress.min(9),
+        ignore_encrypted: opts.ignore_encrypted,
+        use_frameworks_dir: opts.use_frameworks_dir,
+        patch_plugins: opts.patch_plugins,
+        substrate_backend,
+        ..Default::default()
+    };
+
+    let mut injector = Injector::with_options(input, options);
+    if let Some(callback) = opts.progress {
+        let progress = Progress {
+            callback,
+            user_data: opts.user_data,
+            lock: Mutex::new(()),
+        };
+        injector = injector.on_event(move |event: &Event| progress.report(event));
+    }
+    if let Some(cancel) = opts.cancel.as_ref() {
+        injector = injector.cancel_token(cancel.0.clone());
+    }
+    Ok(injector)
+}
+
+/// Fill `options` with the defaults (everything off, compression level 6)
+///
+/// # Safety
+/// `options` must point to writable memory for a `RuzuleOptions`.
+#[no_mangle]
+pub unsafe extern "C" fn ruzule_options_init(options: *mut RuzuleOptions) {
+    if options.is_null() {
+        return;
+    }
+    options.write(RuzuleOptions {
+        input: std::ptr::null(),
+        output: std::ptr::null(),
+        files: std::ptr::null(),
+        files_len: 0,
+        cyans: std::ptr::null(),
+        cyans_len: 0,
+        name: std::ptr::null(),
+        version: std::ptr::null(),
+        bundle_id: std::ptr::null(),
+        minimum: std::ptr::null(),
+        icon: std::ptr::null(),
+        plist: std::ptr::null(),
+        entitlements: std::ptr::null(),
+        remove_supported_devices: false,
+        no_watch: false,
+        enable_documents: false,
+        fakesign: false,
+        thin: false,
+        remove_extensions: false,
+        remove_encrypted: false,
+        compress: 6,
+        ignore_encrypted: false,
+        use_frameworks_dir: false,
+        patch_plugins: false,
+        substrate_backend: std::ptr::null(),
+        progress: None,
+        user_data: std::ptr::null_mut(),
+        cancel: std::ptr::null(),
+    });
+}
+
+/// Run the inject pipeline. Returns `RUZULE_OK` or a negative error code.
+///
+/// # Safety
+/// `options` must point to a valid `RuzuleOptions` whose strings are
+/// NUL-terminated and whose lists hold `*_len` valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn ruzule_inject(options: *const RuzuleOptions) -> i32 {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+    let Some(opts) = options.as_ref() else {
+        set_last_error("E_BAD_INPUT", "options is null".to_string());
+        return RUZULE_ERR_ARGUMENT;
+    };
+
+    let injector = match build_injector(opts) {
+        Ok(injector) => injector,
+        Err(e) => {
+            set_last_error("E_BAD_INPUT", e);
+            return RUZULE_ERR_ARGUMENT;
+        }
+    };
+
+    match std::panic::catch_unwind(AssertUnwindSafe(move || injector.run())) {
+        Ok(Ok(_)) => RUZULE_OK,
+        Ok(Err(e)) => {
+            set_last_error(e.code(), e.to_string());
+            match e {
+                RuzuleError::Cancelled => RUZULE_ERR_CANCELLED,
+                _ => RUZULE_ERR_FAILED,
+            }
+        }
+        Err(_) => {
+            set_last_error("E_PANIC", "internal panic".to_string());
+            RUZULE_ERR_PANIC
+        }
+    }
+}
+
+/// Message of the last error on this thread, or null if the last
+/// [`ruzule_inject`] on it succeeded. Valid until the next call.
+#[no_mangle]
+pub extern "C" fn ruzule_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |e| e.message.as_ptr()))
+}
+
+/// Stable code (e.g. `E_ENCRYPTED`) of the last error on this thread, or null.
+/// Valid until the next call.
+#[no_mangle]
+pub extern "C" fn ruzule_last_error_code() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |e| e.code.as_ptr()))
+}
+
+#[no_mangle]
+pub extern "C" fn ruzule_cancel_new() -> *mut RuzuleCancel {
+    Box::into_raw(Box::new(RuzuleCancel(CancellationToken::new())))
+}
+
+/// Request cancellation of every run using `cancel`; safe to call from any thread
+///
+/// # Safety
+/// `cancel` must come from `ruzule_cancel_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ruzule_cancel(cancel: *const RuzuleCancel) {
+    if let Some(cancel) = cancel.as_ref() {
+        cancel.0.cancel();
+    }
+}
+
+/// # Safety
+/// `cancel` must come from `ruzule_cancel_new`, be freed once, and not be
+/// used by a running `ruzule_inject` call.
+#[no_mangle]
+pub unsafe extern "C" fn ruzule_cancel_free(cancel: *mut RuzuleCancel) {
+    if !cancel.is_null() {
+        drop(Box::from_raw(cancel));
+    }
+}
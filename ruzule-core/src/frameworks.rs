@@ -0,0 +1,607 @@
+use crate::error::{Result, RuzuleError};
+#[cfg(feature = "download-frameworks")]
+use crate::events::Event;
+use crate::events::Events;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "download-frameworks")]
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Clone, Copy)]
+#[cfg_attr(
+    not(any(
+        feature = "bundled-substrate",
+        feature = "bundled-orion",
+        feature = "bundled-cephei"
+    )),
+    allow(dead_code)
+)]
+struct Payload {
+    binary: &'static [u8],
+    plist: &'static [u8],
+}
+
+/// The embedded payload for `$name` if `$feature` is enabled, otherwise `None`
+macro_rules! embedded {
+    ($feature:tt, $name:literal) => {{
+        #[cfg(feature = $feature)]
+        let payload = Some(Payload {
+            binary: include_bytes!(concat!("../frameworks/", $name)),
+            plist: include_bytes!(concat!("../frameworks/", $name, ".plist")),
+        });
+        #[cfg(not(feature = $feature))]
+        let payload = None;
+        payload
+    }};
+}
+
+/// A framework's binary and Info.plist, as loaded by [`BundledFramework::load`]
+pub struct FrameworkPayload {
+    pub binary: Cow<'static, [u8]>,
+    pub plist: Cow<'static, [u8]>,
+}
+
+pub struct BundledFramework {
+    pub name: &'static str,
+    /// CFBundleShortVersionString of the pinned build
+    pub version: &'static str,
+    /// Pinned SHA-256 of the framework binary
+    pub binary_sha256: &'static str,
+    /// Pinned SHA-256 of the framework's Info.plist
+    pub plist_sha256: &'static str,
+    /// Cargo feature that embeds this framework
+    pub feature: &'static str,
+    embedded: Option<Payload>,
+}
+
+pub static CYDIA_SUBSTRATE: BundledFramework = BundledFramework {
+    name: "CydiaSubstrate",
+    version: "1.1.3",
+    binary_sha256: "bca859c9413cb85ca0d3ecfca494c61d67e4acd5846660e618526a152963965b",
+    plist_sha256: "0ebfa285140432c6a5a713a186c3049dd87ce6fc97f46be7b6872332773e408d",
+    feature: "bundled-substrate",
+    embedded: embedded!("bundled-substrate", "CydiaSubstrate"),
+};
+
+pub static ORION: BundledFramework = BundledFramework {
+    name: "Orion",
+    version: "1.0.2",
+    binary_sha256: "290889165a4d156a41ed29c88015083e40b5101153e52fa462f2a25d4942a4e8",
+    plist_sha256: "b3b9ce166f994293d94ed46a81476df2c78db7c25cf076d29571721aaceeb375",
+    feature: "bundled-orion",
+    embedded: embedded!("bundled-orion", "Orion"),
+};
+
+pub static CEPHEI: BundledFramework = BundledFramework {
+    name: "Cephei",
+    version: "1.0.0",
+    binary_sha256: "7b3b0056c992fc271e5de582998376e1c449af98df83a611106eca3589f8fed9",
+    plist_sha256: "4ac7a20d3e702317f305531851911f40ed1fef00cf9fd6eece8c41221356f376",
+    feature: "bundled-cephei",
+    embedded: embedded!("bundled-cephei", "Cephei"),
+};
+
+pub static CEPHEI_UI: BundledFramework = BundledFramework {
+    name: "CepheiUI",
+    version: "1.0.0",
+    binary_sha256: "e03360befca500620670c33073b5f11f176356564a947ba1226c1ed8012b04ab",
+    plist_sha256: "39e4fba59463a49dd0d254a2bae972c947a0401eb8e2775d0b0136f071992f17",
+    feature: "bundled-cephei",
+    embedded: embedded!("bundled-cephei", "CepheiUI"),
+};
+
+pub static CEPHEI_PREFS: BundledFramework = BundledFramework {
+    name: "CepheiPrefs",
+    version: "1.0.0",
+    binary_sha256: "f6375b84c82dec2fb3a9ddd6777d49ca7d8a8c386a1e1d8082d38059548a2160",
+    plist_sha256: "13dd720871ae30e8937374890040e4decdfa4fcaf80edcb65e85951c74d4a024",
+    feature: "bundled-cephei",
+    embedded: embedded!("bundled-cephei", "CepheiPrefs"),
+};
+
+pub static ZX_PLUGINS_INJECT: &[u8] = include_bytes!("../frameworks/zxPluginsInject.dylib");
+pub const ZX_PLUGINS_INJECT_SHA256: &str =
+    "cd903ea15657cbd356398adcb60c8872c41c29b69acc1a5dfb78a49d6e75dea5";
+
+/// The embedded zxPluginsInject.dylib, after checking it against its pinned hash
+pub fn zx_plugins_inject() -> Result<&'static [u8]> {
+    verify_sha256("zxPluginsInject.dylib", ZX_PLUGINS_INJECT, ZX_PLUGINS_INJECT_SHA256)?;
+    Ok(ZX_PLUGINS_INJECT)
+}
+
+pub static BUNDLED_FRAMEWORKS: &[&BundledFramework] = &[
+    &CYDIA_SUBSTRATE,
+    &ORION,
+    &CEPHEI,
+    &CEPHEI_UI,
+    &CEPHEI_PREFS,
+];
+
+/// Version, size and hash of a bundled payload
+pub struct PayloadInfo {
+    pub name: String,
+    pub version: Option<String>,
+    /// `None` for a pinned payload that isn't embedded and hasn't been downloaded yet
+    pub size: Option<usize>,
+    pub sha256: String,
+}
+
+impl PayloadInfo {
+    fn new(name: String, binary: &[u8], plist: Option<&[u8]>) -> Self {
+        let version = plist
+            .and_then(|p| plist::from_bytes::<plist::Dictionary>(p).ok())
+            .and_then(|d| {
+                d.get("CFBundleShortVersionString")
+                    .and_then(|v| v.as_string())
+                    .map(|v| v.to_string())
+            });
+
+        Self {
+            name,
+            version,
+            size: Some(binary.len()),
+            sha256: sha256_hex(binary),
+        }
+    }
+}
+
+/// Describe every bundled framework plus the plugin-patch dylib, without downloading anything
+pub fn payload_infos() -> Vec<PayloadInfo> {
+    let mut infos: Vec<PayloadInfo> = BUNDLED_FRAMEWORKS.iter().map(|fw| fw.info()).collect();
+    infos.push(PayloadInfo::new(
+        "zxPluginsInject.dylib".to_string(),
+        ZX_PLUGINS_INJECT,
+        None,
+    ));
+    infos
+}
+
+impl BundledFramework {
+    pub fn framework_name(&self) -> String {
+        format!("{}.framework", self.name)
+    }
+
+    /// Binary and Info.plist contents.
+    ///
+    /// With `download-frameworks`, the pinned copy is taken from the cache,
+    /// downloading it on first use from the mirror in `RUZULE_FRAMEWORKS_URL`
+    /// if one is set; the embedded copy is the fallback. A build installed by
+    /// [`update_frameworks`] takes precedence over both.
+    #[cfg_attr(not(feature = "download-frameworks"), allow(unused_variables))]
+    pub fn load(&self, events: &Events) -> Result<FrameworkPayload> {
+        #[cfg(feature = "download-frameworks")]
+        {
+            if let Some((binary, plist)) = self.updated() {
+                return Ok(FrameworkPayload {
+                    binary: Cow::Owned(binary),
+                    plist: Cow::Owned(plist),
+                });
+            }
+            match self.fetch(events) {
+                Ok(Some((binary, plist))) => {
+                    return Ok(FrameworkPayload {
+                        binary: Cow::Owned(binary),
+                        plist: Cow::Owned(plist),
+                    })
+                }
+                Ok(None) => {}
+                Err(e) if self.embedded.is_some() => {
+                    events.emit(Event::Notice(format!(
+                        "couldn't fetch {}, using embedded copy: {}",
+                        self.name, e
+                    )));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        match self.embedded {
+            Some(payload) => {
+                verify_sha256(self.name, payload.binary, self.binary_sha256)?;
+                verify_sha256(
+                    &format!("{}/Info.plist", self.framework_name()),
+                    payload.plist,
+                    self.plist_sha256,
+                )?;
+                Ok(FrameworkPayload {
+                    binary: Cow::Borrowed(payload.binary),
+                    plist: Cow::Borrowed(payload.plist),
+                })
+            }
+            None => Err(RuzuleError::InvalidInput(format!(
+                "{} is needed but not included in this build of ruzule \
+                 (rebuild with the {} feature, or with download-frameworks \
+                 and RUZULE_FRAMEWORKS_URL set to a mirror)",
+                self.framework_name(),
+                self.feature
+            ))),
+        }
+    }
+
+    /// The pinned copy from the cache or the mirror; `None` if it isn't
+    /// cached and no mirror is set
+    #[cfg(feature = "download-frameworks")]
+    fn fetch(&self, events: &Events) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let dir = self
+            .pinned_dir()
+            .ok_or_else(|| RuzuleError::Download("no cache directory available".to_string()))?;
+        let mirror = std::env::var("RUZULE_FRAMEWORKS_URL").ok();
+        let mirror = mirror.as_deref();
+
+        let Some(binary) = fetch_pinned(&dir, self.name, self.binary_sha256, mirror, events)? else {
+            return Ok(None);
+        };
+        let plist_file = format!("{}.plist", self.name);
+        let Some(plist) = fetch_pinned(&dir, &plist_file, self.plist_sha256, mirror, events)? else {
+            return Ok(None);
+        };
+        Ok(Some((binary, plist)))
+    }
+
+    /// Where the pinned copy is cached
+    #[cfg(feature = "download-frameworks")]
+    fn pinned_dir(&self) -> Option<PathBuf> {
+        Some(crate::cache::cache_dir()?.join("frameworks").join(self.name))
+    }
+
+    /// The pinned copy, if it's already in the cache and intact
+    #[cfg(feature = "download-frameworks")]
+    fn cached(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        let dir = self.pinned_dir()?;
+        let binary = fs::read(dir.join(self.name)).ok()?;
+        let plist = fs::read(dir.join(format!("{}.plist", self.name))).ok()?;
+        let intact =
+            sha256_hex(&binary) == self.binary_sha256 && sha256_hex(&plist) == self.plist_sha256;
+        intact.then_some((binary, plist))
+    }
+
+    /// The build installed by [`update_frameworks`], if there is one and it's intact
+    #[cfg(feature = "download-frameworks")]
+    fn updated(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        let dir = updates_dir()?;
+        let release = installed_updates(&dir).into_iter().find(|r| r.name == self.name)?;
+        let binary = fs::read(dir.join(self.name)).ok()?;
+        let plist = fs::read(dir.join(format!("{}.plist", self.name))).ok()?;
+        let intact = sha256_hex(&binary) == release.binary_sha256
+            && sha256_hex(&plist) == release.plist_sha256;
+        intact.then_some((binary, plist))
+    }
+
+    /// Describe the copy [`load`](Self::load) would use, without downloading it.
+    /// A pinned copy that's neither embedded nor cached is described by its
+    /// pinned version and hash.
+    pub fn info(&self) -> PayloadInfo {
+        #[cfg(feature = "download-frameworks")]
+        if let Some((binary, plist)) = self.updated().or_else(|| self.cached()) {
+            return PayloadInfo::new(self.framework_name(), &binary, Some(&plist));
+        }
+
+        match self.embedded {
+            Some(payload) => {
+                PayloadInfo::new(self.framework_name(), payload.binary, Some(payload.plist))
+            }
+            None => PayloadInfo {
+                name: self.framework_name(),
+                version: Some(self.version.to_string()),
+                size: None,
+                sha256: self.binary_sha256.to_string(),
+            },
+        }
+    }
+
+    pub fn extract_to<P: AsRef<Path>>(&self, dest: P, events: &Events) -> Result<()> {
+        self.extract_as(dest, self.name, events)
+    }
+
+    /// Extract as `<name>.framework`, renaming the executable and install name if needed
+    pub fn extract_as<P: AsRef<Path>>(&self, dest: P, name: &str, events: &Events) -> Result<()> {
+        let dest = dest.as_ref();
+        let framework_dir = dest.join(format!("{}.framework", name));
+        let binary_path = framework_dir.join(name);
+        let payload = self.load(events)?;
+
+        fs::create_dir_all(&framework_dir)?;
+        fs::write(&binary_path, payload.binary)?;
+
+        if name == self.name {
+            fs::write(framework_dir.join("Info.plist"), payload.plist)?;
+        } else {
+            let mut info: plist::Dictionary = plist::from_bytes(&payload.plist)?;
+            info.insert(
+                "CFBundleExecutable".to_string(),
+                plist::Value::String(name.to_string()),
+            );
+            plist::to_file_xml(framework_dir.join("Info.plist"), &info)?;
+            crate::macho::change_install_name(
+                &binary_path,
+                &format!("@rpath/{0}.framework/{0}", name),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Hooking library that substrate-style dependencies are pointed at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubstrateBackend {
+    /// Bundled ElleKit, installed as CydiaSubstrate.framework
+    #[default]
+    Substrate,
+    /// Bundled ElleKit, installed under its own name
+    Ellekit,
+    /// libhooker's substrate compatibility layer, supplied by the user
+    Libhooker,
+}
+
+impl FromStr for SubstrateBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "substrate" => Ok(SubstrateBackend::Substrate),
+            "ellekit" => Ok(SubstrateBackend::Ellekit),
+            "libhooker" => Ok(SubstrateBackend::Libhooker),
+            _ => Err(format!(
+                "unknown substrate backend \"{}\" (expected ellekit, substrate or libhooker)",
+                s
+            )),
+        }
+    }
+}
+
+impl SubstrateBackend {
+    /// Load path that substrate dependencies are rewritten to
+    pub fn install_path(&self) -> &'static str {
+        match self {
+            SubstrateBackend::Substrate => "@rpath/CydiaSubstrate.framework/CydiaSubstrate",
+            SubstrateBackend::Ellekit => "@rpath/ElleKit.framework/ElleKit",
+            SubstrateBackend::Libhooker => "@rpath/libsubstrate.dylib",
+        }
+    }
+
+    /// Name to bundle CYDIA_SUBSTRATE under, or `None` when the user supplies the backend
+    pub fn framework_name(&self) -> Option<&'static str> {
+        match self {
+            SubstrateBackend::Substrate => Some("CydiaSubstrate"),
+            SubstrateBackend::Ellekit => Some("ElleKit"),
+            SubstrateBackend::Libhooker => None,
+        }
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn verify_sha256(name: &str, data: &[u8], expected: &str) -> Result<()> {
+    let actual = sha256_hex(data);
+    if actual != expected {
+        return Err(RuzuleError::Integrity(format!(
+            "{} has SHA-256 {}, expected {}",
+            name, actual, expected
+        )));
+    }
+    Ok(())
+}
+
+/// Return `file` from the cache dir, downloading it from `mirror` if missing
+/// or stale; `None` if it has to be downloaded and there's no mirror
+#[cfg(feature = "download-frameworks")]
+fn fetch_pinned(
+    dir: &Path,
+    file: &str,
+    sha256: &str,
+    mirror: Option<&str>,
+    events: &Events,
+) -> Result<Option<Vec<u8>>> {
+    let cached = dir.join(file);
+    if let Ok(data) = fs::read(&cached) {
+        if sha256_hex(&data) == sha256 {
+            return Ok(Some(data));
+        }
+    }
+
+    let Some(mirror) = mirror else {
+        return Ok(None);
+    };
+    let url = format!("{}/{}", mirror.trim_end_matches('/'), file);
+    let data = download(&url, Some(sha256), events)?;
+    write_atomic(dir, file, &data)?;
+    Ok(Some(data))
+}
+
+/// GET `url`, checking the body against `sha256` if given
+#[cfg(feature = "download-frameworks")]
+fn download(url: &str, sha256: Option<&str>, events: &Events) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    events.emit(Event::Downloading(url.to_string()));
+    let response = ureq::get(url)
+        .call()
+        .map_err(|e| RuzuleError::Download(format!("{}: {}", url, e)))?;
+    let mut data = Vec::new();
+    response.into_reader().read_to_end(&mut data)?;
+
+    if let Some(sha256) = sha256 {
+        verify_sha256(url, &data, sha256)?;
+    }
+    Ok(data)
+}
+
+/// Replace `dir/file` in one step; runs in other threads or processes may be
+/// writing the same file
+#[cfg(feature = "download-frameworks")]
+fn write_atomic(dir: &Path, file: &str, data: &[u8]) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let temp = crate::temp::TempFile::new_in(dir)?;
+    fs::write(temp.path(), data)?;
+    temp.persist(&dir.join(file))?;
+    Ok(())
+}
+
+/// A framework build listed in the update manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameworkRelease {
+    /// One of the bundled frameworks, e.g. `Cephei`
+    pub name: String,
+    pub version: String,
+    /// Directory holding `<name>` and `<name>.plist`
+    pub url: String,
+    pub binary_sha256: String,
+    pub plist_sha256: String,
+}
+
+/// A framework with a newer build than the one in use
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameworkUpdate {
+    pub name: String,
+    /// Version in use before, if known
+    pub from: Option<String>,
+    pub to: String,
+    /// Whether it was downloaded (false when only checking)
+    pub installed: bool,
+}
+
+/// Where updated frameworks and `installed.json`, the releases they came from, are kept
+#[cfg(feature = "download-frameworks")]
+fn updates_dir() -> Option<PathBuf> {
+    Some(crate::cache::cache_dir()?.join("frameworks").join("updates"))
+}
+
+#[cfg(feature = "download-frameworks")]
+fn installed_updates(dir: &Path) -> Vec<FrameworkRelease> {
+    fs::read(dir.join("installed.json"))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Whether `candidate` is newer than `current`; versions that don't parse
+/// only compare as different
+#[cfg(feature = "download-frameworks")]
+fn is_newer(candidate: &str, current: Option<&str>) -> bool {
+    use crate::version::Version;
+
+    let Some(current) = current else {
+        return true;
+    };
+    match (candidate.parse::<Version>(), current.parse::<Version>()) {
+        (Ok(candidate), Ok(current)) => candidate > current,
+        _ => candidate != current,
+    }
+}
+
+/// The update manifest at `RUZULE_FRAMEWORKS_MANIFEST` and its URL, once
+/// `<url>.sig` checks out as its ed25519 signature by `RUZULE_FRAMEWORKS_KEY`.
+/// The hashes and download locations in it are only as good as that key.
+#[cfg(feature = "download-frameworks")]
+fn signed_manifest(events: &Events) -> Result<(String, Vec<FrameworkRelease>)> {
+    use ring::signature::{UnparsedPublicKey, ED25519};
+
+    let (Ok(url), Ok(key)) = (
+        std::env::var("RUZULE_FRAMEWORKS_MANIFEST"),
+        std::env::var("RUZULE_FRAMEWORKS_KEY"),
+    ) else {
+        return Err(RuzuleError::InvalidInput(
+            "no update manifest configured (set RUZULE_FRAMEWORKS_MANIFEST and the \
+             RUZULE_FRAMEWORKS_KEY it's signed with)"
+                .to_string(),
+        ));
+    };
+    let key = hex::decode(key.trim()).map_err(|_| {
+        RuzuleError::InvalidInput(
+            "RUZULE_FRAMEWORKS_KEY is not a hex-encoded ed25519 public key".to_string(),
+        )
+    })?;
+
+    let manifest = download(&url, None, events)?;
+    let signature = download(&format!("{}.sig", url), None, events)?;
+    let signature = hex::decode(String::from_utf8_lossy(&signature).trim())
+        .map_err(|_| RuzuleError::Integrity(format!("{}.sig is not a hex signature", url)))?;
+    UnparsedPublicKey::new(&ED25519, &key)
+        .verify(&manifest, &signature)
+        .map_err(|_| {
+            RuzuleError::Integrity(format!("{} is not signed by RUZULE_FRAMEWORKS_KEY", url))
+        })?;
+    Ok((url, serde_json::from_slice(&manifest)?))
+}
+
+/// Check the signed update manifest (see [`signed_manifest`]) for builds of the
+/// bundled frameworks newer than the ones in use and, unless `check_only`,
+/// download them (hash-checked) into the cache, where later runs pick them up
+/// instead of the pinned copies.
+pub fn update_frameworks(check_only: bool, events: &Events) -> Result<Vec<FrameworkUpdate>> {
+    #[cfg(feature = "download-frameworks")]
+    {
+        let (url, manifest) = signed_manifest(events)?;
+        let dir = updates_dir()
+            .ok_or_else(|| RuzuleError::Download("no cache directory available".to_string()))?;
+        let mut installed = installed_updates(&dir);
+
+        let mut updates = Vec::new();
+        for release in manifest {
+            let Some(framework) = BUNDLED_FRAMEWORKS.iter().find(|fw| fw.name == release.name)
+            else {
+                events.warn(format!("skipping unknown framework {} in {}", release.name, url));
+                continue;
+            };
+            let current = framework.info().version;
+            if !is_newer(&release.version, current.as_deref()) {
+                continue;
+            }
+
+            if !check_only {
+                let base = release.url.trim_end_matches('/');
+                let plist_file = format!("{}.plist", release.name);
+                let binary = download(
+                    &format!("{}/{}", base, release.name),
+                    Some(&release.binary_sha256),
+                    events,
+                )?;
+                let plist = download(
+                    &format!("{}/{}", base, plist_file),
+                    Some(&release.plist_sha256),
+                    events,
+                )?;
+                write_atomic(&dir, &release.name, &binary)?;
+                write_atomic(&dir, &plist_file, &plist)?;
+
+                installed.retain(|r| r.name != release.name);
+                installed.push(release.clone());
+                write_atomic(&dir, "installed.json", &serde_json::to_vec_pretty(&installed)?)?;
+            }
+            updates.push(FrameworkUpdate {
+                name: release.name,
+                from: current,
+                to: release.version,
+                installed: !check_only,
+            });
+        }
+        Ok(updates)
+    }
+
+    #[cfg(not(feature = "download-frameworks"))]
+    {
+        let _ = (check_only, events);
+        Err(RuzuleError::InvalidInput(
+            "updating frameworks needs a build with the download-frameworks feature".to_string(),
+        ))
+    }
+}
+
+pub fn get_framework_for_dep(dep_key: &str) -> Option<&'static BundledFramework> {
+    match dep_key {
+        "substrate." => Some(&CYDIA_SUBSTRATE),
+        "orion." => Some(&ORION),
+        "cephei." => Some(&CEPHEI),
+        "cepheiui." => Some(&CEPHEI_UI),
+        "cepheiprefs." => Some(&CEPHEI_PREFS),
+        _ => None,
+    }
+}
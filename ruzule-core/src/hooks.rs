@@ -0,0 +1,106 @@
+//! User steps run at fixed points while a [`Plan`](crate::Plan) is applied.
+//!
+//! A hook gets the path of the unpacked .app and may change anything in it;
+//! the bundle is re-read afterwards. Hooks are library closures only; the CLI
+//! turns `--hook stage=command` into one that runs the command.
+
+use crate::error::{Result, RuzuleError};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Where in the pipeline a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HookStage {
+    /// After unpacking, before anything is changed
+    PostExtract,
+    /// After tweaks are injected, before plist, icon and entitlement changes
+    PostInject,
+    /// After every modification, before fakesigning and thinning
+    PreSign,
+    /// Right before the output is written, after signing
+    PrePack,
+}
+
+impl HookStage {
+    pub const ALL: [HookStage; 4] = [
+        HookStage::PostExtract,
+        HookStage::PostInject,
+        HookStage::PreSign,
+        HookStage::PrePack,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HookStage::PostExtract => "post-extract",
+            HookStage::PostInject => "post-inject",
+            HookStage::PreSign => "pre-sign",
+            HookStage::PrePack => "pre-pack",
+        }
+    }
+}
+
+impl fmt::Display for HookStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HookStage {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        HookStage::ALL
+            .into_iter()
+            .find(|stage| stage.as_str() == s.to_lowercase())
+            .ok_or_else(|| {
+                let stages: Vec<&str> = HookStage::ALL.iter().map(|s| s.as_str()).collect();
+                format!("unknown hook stage \"{}\" (expected {})", s, stages.join(", "))
+            })
+    }
+}
+
+/// A custom step; implemented for any `Fn(&Path) -> Result<()>`, which gets the .app path
+pub trait Hook: Send + Sync {
+    fn run(&self, app: &Path) -> Result<()>;
+}
+
+impl<F: Fn(&Path) -> Result<()> + Send + Sync> Hook for F {
+    fn run(&self, app: &Path) -> Result<()> {
+        self(app)
+    }
+}
+
+/// Registered hooks, run in the order they were added
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+#[derive(Clone, Default)]
+pub(crate) struct Hooks(Vec<(HookStage, Arc<dyn Hook>)>);
+
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+impl Hooks {
+    pub(crate) fn add(&mut self, stage: HookStage, hook: Arc<dyn Hook>) {
+        self.0.push((stage, hook));
+    }
+
+    /// Number of hooks registered for `stage`
+    pub(crate) fn count(&self, stage: HookStage) -> usize {
+        self.0.iter().filter(|(s, _)| *s == stage).count()
+    }
+
+    pub(crate) fn run(&self, stage: HookStage, app: &Path) -> Result<()> {
+        for (_, hook) in self.0.iter().filter(|(s, _)| *s == stage) {
+            hook.run(app).map_err(|e| RuzuleError::Hook {
+                stage,
+                message: e.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.iter().map(|(stage, _)| stage)).finish()
+    }
+}
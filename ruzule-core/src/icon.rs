@@ -0,0 +1,127 @@
+//! Loading icon sources for [`AppBundle::change_icon`](crate::AppBundle::change_icon).
+//!
+//! PNG, JPEG and ICO are decoded directly. `.icns` files are read for their
+//! largest PNG entry. SVG and HEIC go through an external tool: `rsvg-convert`
+//! for SVG, rendered at each size instead of scaled, and `sips` on macOS or
+//! `heif-convert` (libheif) elsewhere for HEIC. Sources that aren't square are
+//! centered on a transparent square rather than stretched.
+
+use crate::error::{Result, RuzuleError};
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// `path` as square images of each of `sizes`, working in `tmpdir`
+pub(crate) fn render(path: &Path, sizes: &[u32], tmpdir: &Path) -> Result<Vec<DynamicImage>> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let source = match ext.as_str() {
+        "svg" => {
+            return sizes
+                .iter()
+                .map(|&size| {
+                    let png = tmpdir.join(format!("icon-{}.png", size));
+                    run_tool(
+                        Command::new("rsvg-convert")
+                            .arg("--keep-aspect-ratio")
+                            .args(["-w", &size.to_string(), "-h", &size.to_string(), "-o"])
+                            .arg(&png)
+                            .arg(path),
+                    )?;
+                    Ok(fit(square(image::open(&png)?), size))
+                })
+                .collect();
+        }
+        "icns" => decode_icns(&fs::read(path)?)?,
+        "heic" | "heif" => {
+            let png = tmpdir.join("icon.png");
+            if cfg!(target_os = "macos") {
+                run_tool(
+                    Command::new("sips")
+                        .args(["-s", "format", "png"])
+                        .arg(path)
+                        .arg("--out")
+                        .arg(&png),
+                )?;
+            } else {
+                run_tool(Command::new("heif-convert").arg(path).arg(&png))?;
+            }
+            image::open(&png)?
+        }
+        _ => image::open(path)?,
+    };
+
+    let source = square(source);
+    Ok(sizes
+        .iter()
+        .map(|&size| fit(source.clone(), size))
+        .collect())
+}
+
+/// The largest PNG in an .icns. Its older bitmap entries (`is32`, `il32`...)
+/// top out at 128px, too small for an app icon, and aren't read.
+fn decode_icns(data: &[u8]) -> Result<DynamicImage> {
+    let invalid = |why: &str| RuzuleError::InvalidInput(format!("bad .icns: {}", why));
+    if data.len() < 8 || &data[..4] != b"icns" {
+        return Err(invalid("no icns header"));
+    }
+
+    let mut best: Option<DynamicImage> = None;
+    let mut offset = 8;
+    while offset + 8 <= data.len() {
+        let len = u32::from_be_bytes(data[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        if len < 8 || offset + len > data.len() {
+            return Err(invalid("truncated entry"));
+        }
+        let payload = &data[offset + 8..offset + len];
+        if payload.starts_with(PNG_MAGIC) {
+            let image = image::load_from_memory_with_format(payload, image::ImageFormat::Png)?;
+            if best.as_ref().is_none_or(|b| image.width() > b.width()) {
+                best = Some(image);
+            }
+        }
+        offset += len;
+    }
+    best.ok_or_else(|| invalid("no PNG entry (only legacy bitmaps or JPEG 2000)"))
+}
+
+/// Center a non-square image on a transparent square
+fn square(image: DynamicImage) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    if width == height {
+        return image;
+    }
+    let side = width.max(height);
+    let mut canvas = RgbaImage::new(side, side);
+    let x = i64::from((side - width) / 2);
+    let y = i64::from((side - height) / 2);
+    image::imageops::overlay(&mut canvas, &image.to_rgba8(), x, y);
+    DynamicImage::ImageRgba8(canvas)
+}
+
+fn fit(image: DynamicImage, size: u32) -> DynamicImage {
+    if image.width() == size {
+        return image;
+    }
+    image.resize_exact(size, size, image::imageops::FilterType::Lanczos3)
+}
+
+fn run_tool(command: &mut Command) -> Result<()> {
+    let tool = command.get_program().to_string_lossy().to_string();
+    let output = command.output().map_err(|e| {
+        RuzuleError::ToolFailed(format!("{} is needed for this icon format: {}", tool, e))
+    })?;
+    if !output.status.success() {
+        return Err(RuzuleError::ToolFailed(format!(
+            "{}: {}",
+            tool,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+//! Code signing identities: certificates with their private key, for signing
+//! as a team instead of ad-hoc.
+//!
+//! On macOS they're read from the keychain through `security`. Everywhere,
+//! .p12 files in [`identities_dir`] are read too, with the password from
+//! `$RUZULE_P12_PASSWORD` (empty if unset).
+
+use crate::error::{Result, RuzuleError};
+use apple_codesign::AppleCertificate;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use x509_certificate::CapturedX509Certificate;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SigningIdentity {
+    /// The certificate's common name, e.g. `Apple Development: Jane Doe (AB12CD34EF)`
+    pub name: String,
+    /// SHA-1 of the certificate in upper-case hex, as `security find-identity` shows it
+    pub fingerprint: String,
+    pub team_id: Option<String>,
+    /// Last day the certificate is valid, `YYYY-MM-DD`
+    pub expires: String,
+    pub expired: bool,
+    /// `keychain`, or the .p12 it was read from
+    pub source: String,
+}
+
+/// Where .p12 identities are looked up: `$RUZULE_IDENTITIES_DIR` if set,
+/// otherwise `identities/` next to the [presets](crate::preset::presets_dir)
+pub fn identities_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("RUZULE_IDENTITIES_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    crate::preset::config_dir().map(|dir| dir.join("identities"))
+}
+
+/// Every usable identity, by name: the keychain's on macOS, then the .p12 files
+/// in `dir` (default [`identities_dir`]). Files that can't be read are skipped
+/// with a log warning.
+pub fn list(dir: Option<&Path>) -> Result<Vec<SigningIdentity>> {
+    let mut identities = keychain()?;
+
+    let dir = dir.map(Path::to_path_buf).or_else(identities_dir);
+    if let Some(dir) = dir.filter(|d| d.is_dir()) {
+        let password = std::env::var("RUZULE_P12_PASSWORD").unwrap_or_default();
+        let mut files: Vec<PathBuf> = fs::read_dir(&dir)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("p12"))
+            })
+            .collect();
+        files.sort();
+        for path in files {
+            match from_p12(&path, &password) {
+                Ok(identity) => identities.push(identity),
+                Err(e) => log::warn!("couldn't read {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    identities.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(identities)
+}
+
+/// The identity `query` names: its exact name, its fingerprint, or a part of
+/// its name that matches no other
+pub fn find(query: &str, dir: Option<&Path>) -> Result<SigningIdentity> {
+    let identities = list(dir)?;
+    let exact = identities
+        .iter()
+        .find(|i| i.name == query || i.fingerprint.eq_ignore_ascii_case(query));
+    if let Some(identity) = exact {
+        return Ok(identity.clone());
+    }
+
+    let matches: Vec<&SigningIdentity> = identities
+        .iter()
+        .filter(|i| i.name.contains(query))
+        .collect();
+    match matches[..] {
+        [identity] => Ok(identity.clone()),
+        [] => Err(RuzuleError::InvalidInput(format!(
+            "no signing identity matches \"{}\"",
+            query
+        ))),
+        _ => {
+            let names: Vec<&str> = matches.iter().map(|i| i.name.as_str()).collect();
+            Err(RuzuleError::InvalidInput(format!(
+                "\"{}\" matches several signing identities: {}",
+                query,
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+fn from_certificate(cert: &CapturedX509Certificate, source: String) -> Result<SigningIdentity> {
+    let invalid = |e: &dyn std::fmt::Display| RuzuleError::Sign(format!("bad certificate: {}", e));
+    let fingerprint = cert.sha1_fingerprint().map_err(|e| invalid(&e))?;
+    let not_after = cert.validity_not_after();
+    Ok(SigningIdentity {
+        name: cert.subject_common_name().unwrap_or_default(),
+        fingerprint: hex::encode_upper(fingerprint.as_ref()),
+        team_id: cert.apple_team_id(),
+        expires: not_after.format("%Y-%m-%d").to_string(),
+        expired: SystemTime::from(not_after) < SystemTime::now(),
+        source,
+    })
+}
+
+fn from_p12(path: &Path, password: &str) -> Result<SigningIdentity> {
+    let data = fs::read(path)?;
+    let (cert, _key) = apple_codesign::cryptography::parse_pfx_data(&data, password)
+        .map_err(|e| RuzuleError::Sign(e.to_string()))?;
+    from_certificate(&cert, path.display().to_string())
+}
+
+/// Valid codesigning identities in the default keychain search list
+#[cfg(target_os = "macos")]
+fn keychain() -> Result<Vec<SigningIdentity>> {
+    use std::process::Command;
+
+    let security = |args: &[&str]| -> Result<Vec<u8>> {
+        let output = Command::new("security").args(args).output()?;
+        if !output.status.success() {
+            return Err(RuzuleError::ToolFailed(format!(
+                "security {}: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    };
+
+    let mut identities = Vec::new();
+    let listing = security(&["find-identity", "-v", "-p", "codesigning"])?;
+    for line in String::from_utf8_lossy(&listing).lines() {
+        // `  1) 0123ABCD... "Apple Development: Jane Doe (AB12CD34EF)"`
+        let Some((_, rest)) = line.trim().split_once(") ") else {
+            continue;
+        };
+        let Some((hash, name)) = rest.split_once(' ') else {
+            continue;
+        };
+        let name = name.trim().trim_matches('"');
+
+        // -c matches by substring, so pick the certificate with this hash
+        let pem = security(&["find-certificate", "-a", "-p", "-c", name])?;
+        let certs = CapturedX509Certificate::from_pem_multiple(&pem)
+            .map_err(|e| RuzuleError::Sign(format!("bad certificate for {}: {}", name, e)))?;
+        for cert in &certs {
+            let identity = from_certificate(cert, "keychain".to_string())?;
+            if identity.fingerprint == hash {
+                identities.push(identity);
+                break;
+            }
+        }
+    }
+    Ok(identities)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn keychain() -> Result<Vec<SigningIdentity>> {
+    Ok(Vec::new())
+}
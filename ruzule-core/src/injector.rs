@@ -0,0 +1,1245 @@
+//! High-level injection pipeline.
+//!
+//! ```no_run
+//! use ruzule_core::Injector;
+//!
+//! let report = Injector::new("App.ipa")
+//!     .output("Patched.ipa")
+//!     .tweak("Tweak.dylib")
+//!     .name("Patched")
+//!     .fakesign(true)
+//!     .run()?;
+//! println!("{}", report.output.display());
+//! # Ok::<(), ruzule_core::RuzuleError>(())
+//! ```
+
+use crate::app_bundle::AppBundle;
+use crate::buffer::Arch;
+use crate::cancel::CancellationToken;
+use crate::cyan_config::parse_cyan;
+use crate::entitlements::{self, EntitlementOps};
+use crate::error::{Result, RuzuleError};
+use crate::events::{CyanOrigin, Event, EventHandler, Events, PlistChange};
+use crate::export::ExportFormat;
+use crate::frameworks::SubstrateBackend;
+use crate::hooks::{Hook, HookStage, Hooks};
+use crate::ipa::{copy_app, extract_ipa, find_watch_app};
+use crate::journal::{Journal, Phase};
+use crate::lock::{lock_cache, lock_output};
+use crate::macho;
+use crate::plan::{disk_size, Plan, Stopwatch};
+use crate::plist_ext::{is_valid_url_scheme, PlistFormat, PlistMerge};
+use crate::preflight;
+use crate::prompt::Prompter;
+use crate::sign;
+use crate::temp::TempDir;
+use crate::version::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Precedence when several .cyan files set the same option.
+///
+/// Values from a .cyan always override the caller's; between .cyan files
+/// the policy below applies, in the order they were given.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CyanConflict {
+    /// The last .cyan wins
+    #[default]
+    Last,
+    /// The first .cyan wins
+    First,
+    /// Refuse to continue
+    Error,
+}
+
+impl FromStr for CyanConflict {
+    type Err = RuzuleError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "last" => Ok(Self::Last),
+            "first" => Ok(Self::First),
+            "error" => Ok(Self::Error),
+            _ => Err(RuzuleError::InvalidInput(format!(
+                "Unknown cyan conflict policy: {} (expected last, first or error)",
+                s
+            ))),
+        }
+    }
+}
+
+/// What to do when an injected binary requires a newer OS than the app's minimum
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MinimumCheck {
+    /// Warn and continue
+    #[default]
+    Warn,
+    /// Raise the app's minimum to match, unless it's set explicitly
+    Bump,
+    /// Refuse with [`RuzuleError::NewerOsRequired`]
+    Strict,
+}
+
+impl fmt::Display for CyanConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Last => "last",
+            Self::First => "first",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// Everything an injection run can change. `.cyan` files given in `cyans`
+/// are merged on top of these when the run starts.
+///
+/// Deserializes from JSON with every field optional.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InjectOptions {
+    /// Output path (if unset, overwrites the input)
+    pub output: Option<PathBuf>,
+    pub cyans: Vec<PathBuf>,
+    pub cyan_conflict: CyanConflict,
+    /// Values for `${VAR}` placeholders in .cyan files
+    pub defines: HashMap<String, String>,
+    /// Tweaks/files to inject
+    pub files: Vec<PathBuf>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub bundle_id: Option<String>,
+    pub minimum: Option<String>,
+    pub minimum_check: MinimumCheck,
+    /// Raise the minimum OS (plist and main binary) to what injected binaries require
+    pub auto_minimum: bool,
+    pub icon: Option<PathBuf>,
+    /// Alternate icons to add, by the name the app switches to them with
+    pub alternate_icons: BTreeMap<String, PathBuf>,
+    pub plist: Option<PathBuf>,
+    /// How `plist` is merged (if unset, top-level keys are replaced)
+    pub plist_merge: Option<PlistMerge>,
+    /// Encoding to write the app's Info.plist in (if unset, the one it had)
+    pub plist_format: Option<PlistFormat>,
+    /// Info.plist keys to delete, after `plist` is merged; dotted paths reach
+    /// into dictionaries, see [`PlistFile::remove_keys`](crate::PlistFile::remove_keys)
+    pub remove_plist_keys: Vec<String>,
+    pub entitlements: Option<PathBuf>,
+    /// Entitlement keys to remove from the main binary, after `entitlements` is merged
+    pub remove_entitlements: Vec<String>,
+    pub remove_supported_devices: bool,
+    /// URL schemes to register in `CFBundleURLTypes`
+    pub add_url_schemes: Vec<String>,
+    /// URL schemes to unregister, before `add_url_schemes` are added
+    pub remove_url_schemes: Vec<String>,
+    pub no_watch: bool,
+    /// Modify the watch app in `Watch/` instead of the app itself
+    pub watch_app: bool,
+    /// Reduce watch apps to placeholders instead of removing them
+    pub stub_watch: bool,
+    /// Frameworks to delete from `Frameworks/` and weak-link, by name without `.framework`
+    pub remove_frameworks: Vec<String>,
+    /// Tweaks injected earlier to unlink and delete before injecting, by file name
+    /// (`Tweak.dylib`, `Tweak.framework`) or load path, see [`AppBundle::uninject`]
+    pub uninject: Vec<String>,
+    /// `LC_RPATH`s to delete from the main binary before anything is injected
+    pub remove_rpaths: Vec<String>,
+    pub enable_documents: bool,
+    pub fakesign: bool,
+    /// A .p12 to sign the app and everything in it with, after fakesigning and thinning
+    pub certificate: Option<PathBuf>,
+    /// The .p12's password (default: `$RUZULE_P12_PASSWORD`, or empty). Never serialized.
+    #[serde(skip_serializing)]
+    pub certificate_password: Option<String>,
+    /// A .mobileprovision to embed when signing with `certificate`
+    pub profile: Option<PathBuf>,
+    /// Remove entitlements ad-hoc signing can't honor (push, associated domains, iCloud)
+    pub strip_risky_entitlements: bool,
+    pub thin: bool,
+    /// What `thin` keeps (default: arm64)
+    pub arch: Option<Arch>,
+    /// Thin injected dylibs and frameworks to the app's architectures as they're
+    /// injected (default: when `thin` is on)
+    pub thin_tweaks: Option<bool>,
+    pub remove_extensions: bool,
+    pub remove_encrypted: bool,
+    /// Compression level of the output ipa (0-9)
+    pub compress: u32,
+    /// Threads for preparing tweaks, fakesigning, thinning and packing (default: one per core)
+    pub jobs: Option<usize>,
+    pub ignore_encrypted: bool,
+    pub use_frameworks_dir: bool,
+    /// Link injected dylibs and frameworks from this binary instead of the main
+    /// one, relative to the app (e.g. `Frameworks/SDK.framework/SDK`)
+    pub inject_into: Option<String>,
+    pub patch_plugins: bool,
+    pub patch_plugins_dylib: Option<PathBuf>,
+    pub substrate_backend: SubstrateBackend,
+    pub swift_libs: Option<PathBuf>,
+    /// Keep the working directory (the unpacked app) after the run instead of
+    /// deleting it. It's always kept when applying fails.
+    pub keep_temp: bool,
+    /// Work in a directory that outlives the run and pick up after the last
+    /// finished phase when the same run is repeated, see [`crate::journal`]
+    pub resume: bool,
+    /// How the output is packaged, see [`crate::export`]
+    pub export: ExportFormat,
+    /// A .p12 to put next to an [`ExportFormat::Esign`] export
+    pub export_certificate: Option<PathBuf>,
+    /// A .mobileprovision to put next to an [`ExportFormat::Esign`] export
+    pub export_profile: Option<PathBuf>,
+}
+
+impl Default for InjectOptions {
+    fn default() -> Self {
+        Self {
+            output: None,
+            cyans: Vec::new(),
+            cyan_conflict: CyanConflict::default(),
+            defines: HashMap::new(),
+            files: Vec::new(),
+            name: None,
+            version: None,
+            bundle_id: None,
+            minimum: None,
+            minimum_check: MinimumCheck::default(),
+            auto_minimum: false,
+            icon: None,
+            alternate_icons: BTreeMap::new(),
+            plist: None,
+            plist_merge: None,
+            plist_format: None,
+            remove_plist_keys: Vec::new(),
+            entitlements: None,
+            remove_entitlements: Vec::new(),
+            remove_supported_devices: false,
+            add_url_schemes: Vec::new(),
+            remove_url_schemes: Vec::new(),
+            no_watch: false,
+            watch_app: false,
+            stub_watch: false,
+            remove_frameworks: Vec::new(),
+            uninject: Vec::new(),
+            remove_rpaths: Vec::new(),
+            enable_documents: false,
+            fakesign: false,
+            certificate: None,
+            certificate_password: None,
+            profile: None,
+            strip_risky_entitlements: false,
+            thin: false,
+            arch: None,
+            thin_tweaks: None,
+            remove_extensions: false,
+            remove_encrypted: false,
+            compress: 6,
+            jobs: None,
+            ignore_encrypted: false,
+            use_frameworks_dir: false,
+            inject_into: None,
+            patch_plugins: false,
+            patch_plugins_dylib: None,
+            substrate_backend: SubstrateBackend::default(),
+            swift_libs: None,
+            keep_temp: false,
+            resume: false,
+            export: ExportFormat::default(),
+            export_certificate: None,
+            export_profile: None,
+        }
+    }
+}
+
+impl InjectOptions {
+    /// Open [`certificate`](Self::certificate), if set, with its password
+    pub(crate) fn open_certificate(&self) -> Result<Option<sign::Certificate>> {
+        let Some(ref p12) = self.certificate else {
+            return Ok(None);
+        };
+        let password = self
+            .certificate_password
+            .clone()
+            .or_else(|| std::env::var("RUZULE_P12_PASSWORD").ok())
+            .unwrap_or_default();
+        sign::Certificate::from_p12(p12, &password).map(Some)
+    }
+}
+
+/// How long one stage of a run took
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTiming {
+    /// `extract`, `cyan`, `inject`, `modify`, `sign`, `thin` or `pack`
+    pub name: &'static str,
+    #[serde(rename = "ms", serialize_with = "as_millis")]
+    pub elapsed: Duration,
+}
+
+fn as_millis<S: serde::Serializer>(d: &Duration, s: S) -> std::result::Result<S::Ok, S::Error> {
+    s.serialize_u128(d.as_millis())
+}
+
+/// What a finished run produced
+#[derive(Debug, Clone, Serialize)]
+pub struct InjectReport {
+    pub output: PathBuf,
+    pub name: Option<String>,
+    pub bundle_id: Option<String>,
+    pub version: Option<String>,
+    /// File names of the injected tweaks, including those from .cyan files
+    pub injected: Vec<String>,
+    /// Which .cyan set each option
+    pub cyan_sources: BTreeMap<String, String>,
+    /// The options the run used, with .cyan files merged in
+    pub options: InjectOptions,
+    /// Binaries fakesigned
+    pub signed: usize,
+    pub thinned: usize,
+    pub plist_changes: Vec<PlistChange>,
+    /// Bytes on disk, summed over every file for an .app
+    pub input_size: u64,
+    pub output_size: u64,
+    pub phases: Vec<PhaseTiming>,
+    /// Things that failed without stopping the run, in the order they happened
+    pub warnings: Vec<String>,
+}
+
+/// Builder for an injection run
+#[derive(Debug, Clone)]
+pub struct Injector {
+    input: PathBuf,
+    options: InjectOptions,
+    events: Events,
+    cancel: CancellationToken,
+    hooks: Hooks,
+    prompter: Option<Arc<dyn Prompter>>,
+}
+
+impl Injector {
+    /// Start a run on an .ipa, .tipa or .app
+    pub fn new<P: Into<PathBuf>>(input: P) -> Self {
+        Self::with_options(input, InjectOptions::default())
+    }
+
+    pub fn with_options<P: Into<PathBuf>>(input: P, options: InjectOptions) -> Self {
+        Self {
+            input: input.into(),
+            options,
+            events: Events::none(),
+            cancel: CancellationToken::new(),
+            hooks: Hooks::default(),
+            prompter: None,
+        }
+    }
+
+    /// Receive progress events from the run
+    pub fn on_event<H: EventHandler + 'static>(mut self, handler: H) -> Self {
+        self.events = Events::new(handler);
+        self
+    }
+
+    pub fn events(mut self, events: Events) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Abort the run (with [`RuzuleError::Cancelled`]) once `token` is cancelled
+    pub fn cancel_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Run `hook` on the unpacked app at `stage`; an error from it stops the run
+    pub fn hook<H: Hook + 'static>(mut self, stage: HookStage, hook: H) -> Self {
+        self.hooks.add(stage, Arc::new(hook));
+        self
+    }
+
+    /// Ask `prompter` before replacing an existing output; a no stops the run
+    /// with [`RuzuleError::Cancelled`]. Without one, the output is replaced.
+    pub fn prompter<P: Prompter + 'static>(mut self, prompter: P) -> Self {
+        self.prompter = Some(Arc::new(prompter));
+        self
+    }
+
+    pub fn input(&self) -> &Path {
+        &self.input
+    }
+
+    pub fn options(&self) -> &InjectOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut InjectOptions {
+        &mut self.options
+    }
+
+    pub fn output<P: Into<PathBuf>>(mut self, output: P) -> Self {
+        self.options.output = Some(output.into());
+        self
+    }
+
+    pub fn cyan<P: Into<PathBuf>>(mut self, cyan: P) -> Self {
+        self.options.cyans.push(cyan.into());
+        self
+    }
+
+    pub fn cyan_conflict(mut self, policy: CyanConflict) -> Self {
+        self.options.cyan_conflict = policy;
+        self
+    }
+
+    pub fn define<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.options.defines.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn tweak<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.options.files.push(path.into());
+        self
+    }
+
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.options.name = Some(name.into());
+        self
+    }
+
+    pub fn version<S: Into<String>>(mut self, version: S) -> Self {
+        self.options.version = Some(version.into());
+        self
+    }
+
+    pub fn bundle_id<S: Into<String>>(mut self, bundle_id: S) -> Self {
+        self.options.bundle_id = Some(bundle_id.into());
+        self
+    }
+
+    pub fn minimum<S: Into<String>>(mut self, minimum: S) -> Self {
+        self.options.minimum = Some(minimum.into());
+        self
+    }
+
+    pub fn minimum_check(mut self, check: MinimumCheck) -> Self {
+        self.options.minimum_check = check;
+        self
+    }
+
+    pub fn auto_minimum(mut self, enabled: bool) -> Self {
+        self.options.auto_minimum = enabled;
+        self
+    }
+
+    pub fn icon<P: Into<PathBuf>>(mut self, icon: P) -> Self {
+        self.options.icon = Some(icon.into());
+        self
+    }
+
+    pub fn alternate_icon<S: Into<String>, P: Into<PathBuf>>(mut self, name: S, icon: P) -> Self {
+        self.options.alternate_icons.insert(name.into(), icon.into());
+        self
+    }
+
+    pub fn plist<P: Into<PathBuf>>(mut self, plist: P) -> Self {
+        self.options.plist = Some(plist.into());
+        self
+    }
+
+    pub fn entitlements<P: Into<PathBuf>>(mut self, entitlements: P) -> Self {
+        self.options.entitlements = Some(entitlements.into());
+        self
+    }
+
+    pub fn remove_supported_devices(mut self, enabled: bool) -> Self {
+        self.options.remove_supported_devices = enabled;
+        self
+    }
+
+    pub fn no_watch(mut self, enabled: bool) -> Self {
+        self.options.no_watch = enabled;
+        self
+    }
+
+    pub fn stub_watch(mut self, enabled: bool) -> Self {
+        self.options.stub_watch = enabled;
+        self
+    }
+
+    /// Delete `Frameworks/<name>.framework` and weak-link it, see
+    /// [`AppBundle::remove_frameworks`]
+    pub fn remove_framework<S: Into<String>>(mut self, name: S) -> Self {
+        self.options.remove_frameworks.push(name.into());
+        self
+    }
+
+    /// Remove a previously injected tweak, see [`AppBundle::uninject`]
+    pub fn uninject<S: Into<String>>(mut self, name: S) -> Self {
+        self.options.uninject.push(name.into());
+        self
+    }
+
+    /// Delete an rpath (every copy of it) from the main binary
+    pub fn remove_rpath<S: Into<String>>(mut self, rpath: S) -> Self {
+        self.options.remove_rpaths.push(rpath.into());
+        self
+    }
+
+    pub fn watch_app(mut self, enabled: bool) -> Self {
+        self.options.watch_app = enabled;
+        self
+    }
+
+    pub fn enable_documents(mut self, enabled: bool) -> Self {
+        self.options.enable_documents = enabled;
+        self
+    }
+
+    pub fn fakesign(mut self, enabled: bool) -> Self {
+        self.options.fakesign = enabled;
+        self
+    }
+
+    /// Sign with a developer certificate instead of leaving the app ad-hoc signed
+    pub fn certificate<P: Into<PathBuf>>(mut self, p12: P, password: impl Into<String>) -> Self {
+        self.options.certificate = Some(p12.into());
+        self.options.certificate_password = Some(password.into());
+        self
+    }
+
+    /// Embed a provisioning profile when signing with a [`certificate`](Self::certificate)
+    pub fn profile<P: Into<PathBuf>>(mut self, mobileprovision: P) -> Self {
+        self.options.profile = Some(mobileprovision.into());
+        self
+    }
+
+    /// Remove `key` from the main binary's entitlements (repeatable)
+    pub fn remove_entitlement<S: Into<String>>(mut self, key: S) -> Self {
+        self.options.remove_entitlements.push(key.into());
+        self
+    }
+
+    pub fn plist_merge(mut self, mode: PlistMerge) -> Self {
+        self.options.plist_merge = Some(mode);
+        self
+    }
+
+    pub fn plist_format(mut self, format: PlistFormat) -> Self {
+        self.options.plist_format = Some(format);
+        self
+    }
+
+    pub fn remove_plist_key<S: Into<String>>(mut self, key: S) -> Self {
+        self.options.remove_plist_keys.push(key.into());
+        self
+    }
+
+    pub fn add_url_scheme<S: Into<String>>(mut self, scheme: S) -> Self {
+        self.options.add_url_schemes.push(scheme.into());
+        self
+    }
+
+    pub fn remove_url_scheme<S: Into<String>>(mut self, scheme: S) -> Self {
+        self.options.remove_url_schemes.push(scheme.into());
+        self
+    }
+
+    pub fn strip_risky_entitlements(mut self, enabled: bool) -> Self {
+        self.options.strip_risky_entitlements = enabled;
+        self
+    }
+
+    pub fn thin(mut self, enabled: bool) -> Self {
+        self.options.thin = enabled;
+        self
+    }
+
+    /// Thin to `arch` instead of arm64
+    pub fn arch(mut self, arch: Arch) -> Self {
+        self.options.arch = Some(arch);
+        self
+    }
+
+    pub fn thin_tweaks(mut self, enabled: bool) -> Self {
+        self.options.thin_tweaks = Some(enabled);
+        self
+    }
+
+    pub fn remove_extensions(mut self, enabled: bool) -> Self {
+        self.options.remove_extensions = enabled;
+        self
+    }
+
+    pub fn remove_encrypted(mut self, enabled: bool) -> Self {
+        self.options.remove_encrypted = enabled;
+        self
+    }
+
+    pub fn compress(mut self, level: u32) -> Self {
+        self.options.compress = level;
+        self
+    }
+
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.options.jobs = Some(jobs);
+        self
+    }
+
+    pub fn keep_temp(mut self, enabled: bool) -> Self {
+        self.options.keep_temp = enabled;
+        self
+    }
+
+    pub fn resume(mut self, enabled: bool) -> Self {
+        self.options.resume = enabled;
+        self
+    }
+
+    pub fn export(mut self, format: ExportFormat) -> Self {
+        self.options.export = format;
+        self
+    }
+
+    pub fn export_certificate<P: Into<PathBuf>>(mut self, p12: P) -> Self {
+        self.options.export_certificate = Some(p12.into());
+        self
+    }
+
+    pub fn export_profile<P: Into<PathBuf>>(mut self, profile: P) -> Self {
+        self.options.export_profile = Some(profile.into());
+        self
+    }
+
+    pub fn ignore_encrypted(mut self, enabled: bool) -> Self {
+        self.options.ignore_encrypted = enabled;
+        self
+    }
+
+    pub fn use_frameworks_dir(mut self, enabled: bool) -> Self {
+        self.options.use_frameworks_dir = enabled;
+        self
+    }
+
+    pub fn inject_into(mut self, binary: impl Into<String>) -> Self {
+        self.options.inject_into = Some(binary.into());
+        self
+    }
+
+    pub fn patch_plugins(mut self, enabled: bool) -> Self {
+        self.options.patch_plugins = enabled;
+        self
+    }
+
+    pub fn patch_plugins_dylib<P: Into<PathBuf>>(mut self, dylib: P) -> Self {
+        self.options.patch_plugins_dylib = Some(dylib.into());
+        self
+    }
+
+    pub fn substrate_backend(mut self, backend: SubstrateBackend) -> Self {
+        self.options.substrate_backend = backend;
+        self
+    }
+
+    pub fn swift_libs<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.options.swift_libs = Some(dir.into());
+        self
+    }
+
+    /// Where the result will be written: the output (or the input when unset),
+    /// with `.ipa` appended if it has no app extension. `-` streams the .ipa to stdout.
+    pub fn output_path(&self) -> PathBuf {
+        let output = self.options.output.clone().unwrap_or_else(|| self.input.clone());
+        if is_stdout(&output) || has_extension(&output, &["app", "ipa", "tipa"]) {
+            output
+        } else {
+            output.with_extension("ipa")
+        }
+    }
+
+    /// Check that the input and every referenced file exist
+    pub fn validate(&self) -> Result<()> {
+        let opts = &self.options;
+
+        if !has_extension(&self.input, &["app", "ipa", "tipa"]) {
+            return Err(RuzuleError::InvalidInput(
+                "Input must be an .ipa, .tipa, or .app".to_string(),
+            ));
+        }
+
+        if !self.input.exists() {
+            return Err(RuzuleError::FileNotFound(self.input.clone()));
+        }
+
+        for f in &opts.files {
+            if !f.exists() {
+                return Err(RuzuleError::FileNotFound(f.clone()));
+            }
+        }
+
+        if opts.jobs == Some(0) {
+            return Err(RuzuleError::InvalidInput("jobs must be at least 1".to_string()));
+        }
+
+        if opts.watch_app && (opts.no_watch || opts.stub_watch) {
+            return Err(RuzuleError::InvalidInput(
+                "Can't modify the watch app while removing it".to_string(),
+            ));
+        }
+        if opts.no_watch && opts.stub_watch {
+            return Err(RuzuleError::InvalidInput(
+                "Can't both remove the watch app and keep a placeholder of it".to_string(),
+            ));
+        }
+
+        if let Some(ref m) = opts.minimum {
+            m.parse::<Version>()?;
+            if opts.auto_minimum {
+                return Err(RuzuleError::InvalidInput(
+                    "Can't set the minimum OS version and derive it at once".to_string(),
+                ));
+            }
+        }
+
+        let exporting = opts.export_certificate.is_some() || opts.export_profile.is_some();
+        if exporting && opts.export != ExportFormat::Esign {
+            return Err(RuzuleError::InvalidInput(
+                "A certificate or profile can only be exported with --export esign".to_string(),
+            ));
+        }
+        if let Some(scheme) = opts.add_url_schemes.iter().find(|s| !is_valid_url_scheme(s)) {
+            return Err(RuzuleError::InvalidInput(format!(
+                "Invalid URL scheme: {} (a letter, then letters, digits, '+', '-' and '.')",
+                scheme
+            )));
+        }
+        if opts.arch.is_some() && !opts.thin {
+            return Err(RuzuleError::InvalidInput(
+                "An architecture is only used when thinning".to_string(),
+            ));
+        }
+        if opts.profile.is_some() && opts.certificate.is_none() {
+            return Err(RuzuleError::InvalidInput(
+                "A provisioning profile is only embedded when signing with a certificate"
+                    .to_string(),
+            ));
+        }
+        if opts.export == ExportFormat::Esign {
+            let output = self.output_path();
+            if is_stdout(&output) || !has_extension(&output, &["ipa", "tipa"]) {
+                return Err(RuzuleError::InvalidInput(
+                    "An esign export needs an .ipa or .tipa output".to_string(),
+                ));
+            }
+        }
+
+        let files = opts
+            .cyans
+            .iter()
+            .chain(&opts.icon)
+            .chain(opts.alternate_icons.values())
+            .chain(&opts.plist)
+            .chain(&opts.entitlements)
+            .chain(&opts.patch_plugins_dylib)
+            .chain(&opts.certificate)
+            .chain(&opts.profile)
+            .chain(&opts.export_certificate)
+            .chain(&opts.export_profile);
+        for f in files {
+            if !f.is_file() {
+                return Err(RuzuleError::FileNotFound(f.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// [`validate`](Self::validate), then open every input the way the run will
+    /// (.ipa, .cyan, tweaks, icon, plist and entitlements) and check the output
+    /// is writable and there's room for the work directory and the output.
+    /// [`plan`](Self::plan) does this before unpacking anything.
+    pub fn preflight(&self) -> Result<()> {
+        self.validate()?;
+        preflight::check(&self.input, &self.output_path(), &self.options)
+    }
+
+    /// Run the pipeline, replacing anything already at [`Injector::output_path`]
+    /// (after asking, if a [`prompter`](Self::prompter) is set)
+    pub fn run(self) -> Result<InjectReport> {
+        if let Some(ref prompter) = self.prompter {
+            self.validate()?;
+            let output = self.output_path();
+            if !is_stdout(&output) && output.exists() {
+                let question = if output != self.input {
+                    format!("{} already exists, overwrite it?", output.display())
+                } else {
+                    "no output was specified. overwrite the input?".to_string()
+                };
+                if !prompter.confirm(&question)? {
+                    return Err(RuzuleError::Cancelled);
+                }
+            }
+        }
+        self.plan()?.apply()
+    }
+
+    /// Unpack the input and resolve .cyan files, returning what
+    /// [`Plan::apply`] would change. Nothing outside a temporary directory is
+    /// written until the plan is applied.
+    pub fn plan(self) -> Result<Plan> {
+        self.preflight()?;
+
+        let output = self.output_path();
+        let Injector {
+            input,
+            options,
+            events,
+            cancel,
+            hooks,
+            prompter: _,
+        } = self;
+        let events = events.recording();
+
+        if options.output.as_ref().is_some_and(|o| *o != output) {
+            events.emit(Event::Notice(
+                "valid file extension not found; will create ipa".to_string(),
+            ));
+        }
+
+        // Before unpacking, so a clash fails fast
+        let mut locks = vec![lock_cache(false)?];
+        if !is_stdout(&output) {
+            locks.push(lock_output(&output)?);
+        }
+
+        // Before the options are taken apart, as they name the work directory
+        let (mut tmpdir, mut journal) = if options.resume {
+            let (tmpdir, journal) = Journal::open(&input, &options)?;
+            (tmpdir, Some(journal))
+        } else {
+            (TempDir::new()?, None)
+        };
+
+        let InjectOptions {
+            cyans,
+            cyan_conflict,
+            defines,
+            mut files,
+            mut name,
+            mut version,
+            mut bundle_id,
+            mut minimum,
+            minimum_check,
+            auto_minimum,
+            mut icon,
+            mut alternate_icons,
+            mut plist,
+            mut plist_merge,
+            plist_format,
+            remove_plist_keys,
+            mut entitlements,
+            remove_entitlements,
+            mut remove_supported_devices,
+            add_url_schemes,
+            remove_url_schemes,
+            mut no_watch,
+            watch_app,
+            stub_watch,
+            remove_frameworks,
+            uninject,
+            remove_rpaths,
+            mut enable_documents,
+            mut fakesign,
+            certificate,
+            certificate_password,
+            profile,
+            strip_risky_entitlements,
+            mut thin,
+            arch,
+            thin_tweaks,
+            mut remove_extensions,
+            mut remove_encrypted,
+            compress,
+            jobs,
+            ignore_encrypted,
+            use_frameworks_dir,
+            inject_into,
+            mut patch_plugins,
+            patch_plugins_dylib,
+            substrate_backend,
+            swift_libs,
+            keep_temp,
+            resume,
+            export,
+            export_certificate,
+            export_profile,
+            ..
+        } = options;
+
+        let input_is_ipa = has_extension(&input, &["ipa", "tipa"]);
+
+        if keep_temp {
+            tmpdir.keep(true);
+            events.emit(Event::TempKept(tmpdir.path().to_path_buf()));
+        }
+        let tmpdir_path = tmpdir.path();
+        let mut stopwatch = Stopwatch::start();
+        // Output may replace input, so measure it now
+        let input_size = disk_size(&input);
+
+        // Extract or copy app, unless an earlier run already did
+        let app_path = match journal.as_ref().and_then(|j| Some((j.phase()?, j.app()?))) {
+            Some((phase, app_path)) => {
+                events.emit(Event::Notice(format!("resuming, the app is already {}", phase)));
+                app_path
+            }
+            None => {
+                events.emit(Event::Extracting);
+                let app_path = if input_is_ipa {
+                    extract_ipa(&input, tmpdir_path, &cancel, &events)?
+                } else {
+                    copy_app(&input, tmpdir_path)?
+                };
+                events.emit(Event::Extracted);
+                stopwatch.lap("extract");
+                if let Some(ref mut journal) = journal {
+                    journal.record(Phase::Extracted, &app_path)?;
+                }
+                app_path
+            }
+        };
+        cancel.check()?;
+
+        // Load app bundle
+        let root = app_path.clone();
+        let app_path = if watch_app { find_watch_app(&app_path)? } else { app_path };
+        let app = AppBundle::with_events(&app_path, events.clone())?.with_jobs(jobs);
+        if !app.platform.is_ios() {
+            events.emit(Event::Notice(format!("{} app", app.platform)));
+        }
+
+        // Check encryption
+        if app.executable.is_encrypted()? {
+            if ignore_encrypted {
+                events.emit(Event::EncryptedIgnored);
+            } else {
+                return Err(RuzuleError::EncryptedBinary(app.executable.inner.path.clone()));
+            }
+        }
+
+        // Parse .cyan files
+        let mut merge = CyanMerge::new(cyan_conflict, events.clone());
+        let mut binary_entitlements: BTreeMap<String, EntitlementOps> = BTreeMap::new();
+        let mut required = EntitlementOps::default();
+        for (index, cyan_path) in cyans.iter().enumerate() {
+            cancel.check()?;
+            // As given, so two .cyans with the same file name stay apart
+            let source = cyan_path.display().to_string();
+
+            events.emit(Event::CyanLoaded(source.clone()));
+            let parsed = parse_cyan(cyan_path, tmpdir_path, index, &defines)?;
+
+            // Merge config into options
+            merge.option("name", &mut name, parsed.config.n, &source)?;
+            merge.option("version", &mut version, parsed.config.v, &source)?;
+            merge.option("bundle id", &mut bundle_id, parsed.config.b, &source)?;
+            merge.option("minimum version", &mut minimum, parsed.config.m, &source)?;
+            if let Some(ref m) = minimum {
+                m.parse::<Version>()?;
+            }
+            merge.flag(
+                "remove supported devices",
+                &mut remove_supported_devices,
+                parsed.config.remove_supported_devices,
+                &source,
+            );
+            merge.flag("no watch", &mut no_watch, parsed.config.no_watch, &source);
+            merge.flag(
+                "enable documents",
+                &mut enable_documents,
+                parsed.config.enable_documents,
+                &source,
+            );
+            merge.flag("fakesign", &mut fakesign, parsed.config.fakesign, &source);
+            merge.flag("thin", &mut thin, parsed.config.thin, &source);
+            merge.flag(
+                "remove extensions",
+                &mut remove_extensions,
+                parsed.config.remove_extensions,
+                &source,
+            );
+            merge.flag(
+                "remove encrypted",
+                &mut remove_encrypted,
+                parsed.config.remove_encrypted,
+                &source,
+            );
+            merge.flag("patch plugins", &mut patch_plugins, parsed.config.patch_plugins, &source);
+
+            // Merge files
+            files.extend(parsed.files.into_values());
+
+            merge.option("icon", &mut icon, parsed.icon, &source)?;
+            alternate_icons.extend(parsed.alternate_icons);
+            merge.option("merge plist", &mut plist, parsed.plist, &source)?;
+            merge.option("plist merge", &mut plist_merge, parsed.config.plist_merge, &source)?;
+            merge.option("entitlements", &mut entitlements, parsed.entitlements, &source)?;
+
+            for (binary, ops) in parsed.config.binary_entitlements {
+                binary_entitlements.entry(binary).or_default().extend(ops);
+            }
+            required.extend(EntitlementOps {
+                require: parsed.config.requires_entitlements,
+                ..Default::default()
+            });
+        }
+
+        // Tweak.dylib.entitlements next to Tweak.dylib is read, not injected
+        let sidecars: Vec<PathBuf> =
+            files.iter().map(|f| entitlements::sidecar_path(f)).collect();
+        files.retain(|f| !sidecars.contains(f));
+        for file in &files {
+            if let Some(require) = entitlements::read_sidecar(file)? {
+                let name = file.file_name().unwrap_or_default().to_string_lossy();
+                let keys: Vec<&str> = require.keys().map(String::as_str).collect();
+                events.emit(Event::Notice(format!(
+                    "{} needs entitlements {}",
+                    name,
+                    keys.join(", ")
+                )));
+                required.extend(EntitlementOps {
+                    require,
+                    ..Default::default()
+                });
+            }
+        }
+        if let Some(arch) = arch.filter(|_| thin) {
+            let main = &app.executable.inner.path;
+            if !macho::has_arch(main, arch)? {
+                let found = macho::architectures(main)?;
+                return Err(RuzuleError::ArchMismatch {
+                    name: app.executable.inner.name.clone(),
+                    found: found.into_iter().map(|(_, name)| name).collect(),
+                    required: vec![arch.to_string()],
+                });
+            }
+        }
+        if let Some(ref rel) = inject_into {
+            app.resolve_executable(rel).map_err(|e| {
+                RuzuleError::InvalidInput(format!("--inject-into {}: {}", rel, e))
+            })?;
+        }
+        if !required.is_empty() {
+            let main = &app.executable.inner.path;
+            let main = main.strip_prefix(&app.path).unwrap_or(main);
+            let main = main.to_string_lossy().to_string();
+            binary_entitlements.entry(main).or_default().extend(required);
+        }
+
+        let options = InjectOptions {
+            output: Some(output.clone()),
+            cyans,
+            cyan_conflict,
+            defines,
+            files,
+            name,
+            version,
+            bundle_id,
+            minimum,
+            minimum_check,
+            auto_minimum,
+            icon,
+            alternate_icons,
+            plist,
+            plist_merge,
+            plist_format,
+            remove_plist_keys,
+            entitlements,
+            remove_entitlements,
+            remove_supported_devices,
+            add_url_schemes,
+            remove_url_schemes,
+            no_watch,
+            watch_app,
+            stub_watch,
+            remove_frameworks,
+            uninject,
+            remove_rpaths,
+            enable_documents,
+            fakesign,
+            certificate,
+            certificate_password,
+            profile,
+            strip_risky_entitlements,
+            thin,
+            arch,
+            thin_tweaks,
+            remove_extensions,
+            remove_encrypted,
+            compress,
+            jobs,
+            ignore_encrypted,
+            use_frameworks_dir,
+            inject_into,
+            patch_plugins,
+            patch_plugins_dylib,
+            substrate_backend,
+            swift_libs,
+            keep_temp,
+            resume,
+            export,
+            export_certificate,
+            export_profile,
+        };
+
+        let app = app.with_thin_injected(options.thin_tweaks.unwrap_or(options.thin));
+        let mut plan = Plan {
+            operations: Vec::new(),
+            options,
+            binary_entitlements,
+            cyan_sources: merge.origins.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+            input_size,
+            output,
+            root,
+            app,
+            tmpdir,
+            journal,
+            _locks: locks,
+            events,
+            cancel,
+            hooks,
+            stopwatch,
+        };
+        plan.operations = plan.describe();
+        Ok(plan)
+    }
+}
+
+/// Inject into `input` and write `output` with options built elsewhere (a GUI's
+/// form, a config file). Shorthand for [`Injector::with_options`] and
+/// [`Injector::run`]; use the [`Injector`] to also get events, hooks or cancellation.
+pub fn inject<P: Into<PathBuf>, Q: Into<PathBuf>>(
+    input: P,
+    output: Q,
+    options: InjectOptions,
+) -> Result<InjectReport> {
+    let options = InjectOptions {
+        output: Some(output.into()),
+        ..options
+    };
+    Injector::with_options(input, options).run()
+}
+
+#[cfg(feature = "async")]
+impl Injector {
+    /// [`Injector::run`] on tokio's blocking pool.
+    ///
+    /// Dropping the returned future cancels the run.
+    pub async fn run_async(self) -> Result<InjectReport> {
+        let mut guard = CancelOnDrop(Some(self.cancel.clone()));
+        let result = tokio::task::spawn_blocking(move || self.run()).await;
+        guard.0 = None;
+        result.map_err(|e| RuzuleError::Io(std::io::Error::other(e)))?
+    }
+}
+
+/// Cancels the token unless disarmed, for runs whose future was dropped
+#[cfg(feature = "async")]
+struct CancelOnDrop(Option<CancellationToken>);
+
+#[cfg(feature = "async")]
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(ref token) = self.0 {
+            token.cancel();
+        }
+    }
+}
+
+/// `-`, the output path that means stdout
+pub(crate) fn is_stdout(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+pub(crate) fn has_extension(path: &Path, exts: &[&str]) -> bool {
+    path.extension()
+        .map(|e| exts.contains(&e.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Tracks which .cyan set each option so overrides can be reported
+struct CyanMerge {
+    policy: CyanConflict,
+    origins: HashMap<&'static str, String>,
+    events: Events,
+}
+
+impl CyanMerge {
+    fn new(policy: CyanConflict, events: Events) -> Self {
+        Self {
+            policy,
+            origins: HashMap::new(),
+            events,
+        }
+    }
+
+    fn emit(&self, key: &str, source: &str, origin: CyanOrigin) {
+        self.events.emit(Event::CyanOption {
+            key: key.to_string(),
+            source: source.to_string(),
+            origin,
+        });
+    }
+
+    fn option<T: PartialEq>(
+        &mut self,
+        key: &'static str,
+        slot: &mut Option<T>,
+        value: Option<T>,
+        source: &str,
+    ) -> Result<()> {
+        let Some(value) = value else {
+            return Ok(());
+        };
+
+        // Nothing changes, but a later .cyan setting something else still clashes with this one
+        if slot.as_ref() == Some(&value) {
+            self.origins.entry(key).or_insert_with(|| source.to_string());
+            return Ok(());
+        }
+
+        match self.origins.get(key) {
+            Some(prev) => match self.policy {
+                CyanConflict::Last => {
+                    self.emit(key, source, CyanOrigin::Overrode(Some(prev.clone())));
+                }
+                CyanConflict::First => {
+                    self.emit(key, source, CyanOrigin::Ignored(prev.clone()));
+                    return Ok(());
+                }
+                CyanConflict::Error => {
+                    return Err(RuzuleError::InvalidInput(format!(
+                        "{} is set by both {} and {}",
+                        key, prev, source
+                    )));
+                }
+            },
+            None if slot.is_some() => self.emit(key, source, CyanOrigin::Overrode(None)),
+            None => self.emit(key, source, CyanOrigin::Set),
+        }
+
+        *slot = Some(value);
+        self.origins.insert(key, source.to_string());
+        Ok(())
+    }
+
+    fn flag(&mut self, key: &'static str, slot: &mut bool, value: bool, source: &str) {
+        if value && !*slot {
+            self.emit(key, source, CyanOrigin::Set);
+            *slot = true;
+            self.origins.insert(key, source.to_string());
+        }
+    }
+}
@@ -0,0 +1,808 @@
+use crate::cancel::CancellationToken;
+use crate::cyan_config::{create_symlink, normalize, resolves_inside};
+use crate::error::{Result, RuzuleError};
+use crate::events::{Events, Progress, ProgressStage};
+use crate::macho;
+use crate::plan::disk_size;
+use crate::platform::Layout;
+use crate::temp::TempFile;
+use crate::vfs::{Fs, RealFs};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Unpack `ipa_path` into `dest`, returning the path of the .app. Progress is
+/// reported to `events` in bytes written.
+pub fn extract_ipa<P: AsRef<Path>, Q: AsRef<Path>>(
+    ipa_path: P,
+    dest: Q,
+    cancel: &CancellationToken,
+    events: &Events,
+) -> Result<PathBuf> {
+    let ipa_path = ipa_path.as_ref();
+    let dest = dest.as_ref();
+
+    let file = File::open(ipa_path)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+
+    // Check for valid IPA structure
+    let has_payload = archive
+        .file_names()
+        .any(|name| name.starts_with("Payload/"));
+    if !has_payload {
+        return Err(RuzuleError::InvalidIpa(
+            "No Payload folder found".to_string(),
+        ));
+    }
+
+    let has_info_plist = archive
+        .file_names()
+        .any(|name| {
+            name.ends_with(".app/Info.plist") || name.ends_with(".app/Contents/Info.plist")
+        });
+    if !has_info_plist {
+        return Err(RuzuleError::InvalidIpa(
+            "No Info.plist found, invalid app".to_string(),
+        ));
+    }
+
+    let mut total = 0;
+    for i in 0..archive.len() {
+        total += archive.by_index_raw(i)?.size();
+    }
+    let progress = Progress::new(events, ProgressStage::Extract, total);
+    // Created once every regular file is written, so nothing is written through one
+    let mut symlinks: Vec<(PathBuf, String)> = Vec::new();
+
+    // Extract all files
+    for i in 0..archive.len() {
+        cancel.check()?;
+        let mut file = archive.by_index(i)?;
+        let outpath = dest.join(file.name());
+
+        if file.name().ends_with('/') {
+            fs::create_dir_all(&outpath)?;
+        } else if file.is_symlink() {
+            // Framework links in a Contents/ bundle (Versions/Current, ...)
+            let mut target = String::new();
+            file.read_to_string(&mut target)?;
+            let parent = outpath.parent().unwrap_or(dest);
+            let inside = normalize(&parent.join(&target)).starts_with(normalize(dest));
+            if Path::new(&target).is_absolute() || !inside {
+                return Err(RuzuleError::InvalidIpa(format!(
+                    "Symlink points outside the ipa: {} -> {}",
+                    file.name(),
+                    target
+                )));
+            }
+            progress.add(target.len() as u64);
+            symlinks.push((outpath, target));
+        } else {
+            if let Some(p) = outpath.parent() {
+                if !p.exists() {
+                    fs::create_dir_all(p)?;
+                }
+            }
+            let mut outfile = ProgressWriter {
+                inner: File::create(&outpath)?,
+                progress: &progress,
+            };
+            std::io::copy(&mut file, &mut outfile)?;
+
+            // Preserve Unix permissions
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if let Some(mode) = file.unix_mode() {
+                    fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
+                }
+            }
+        }
+    }
+
+    for (link, target) in &symlinks {
+        if let Some(p) = link.parent() {
+            fs::create_dir_all(p)?;
+        }
+        create_symlink(target, link)?;
+    }
+    // A link can also escape through another one
+    let root = fs::canonicalize(dest)?;
+    for (link, target) in &symlinks {
+        if !resolves_inside(link, &root) {
+            return Err(RuzuleError::InvalidIpa(format!(
+                "Symlink points outside the ipa: {} -> {}",
+                link.strip_prefix(dest).unwrap_or(link).display(),
+                target
+            )));
+        }
+    }
+
+    // Find the .app folder
+    let payload = dest.join("Payload");
+    let app_path = find_app_in_payload(&payload)?;
+
+    Ok(app_path)
+}
+
+fn find_app_in_payload(payload: &Path) -> Result<PathBuf> {
+    for entry in fs::read_dir(payload)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() && path.extension().map(|e| e == "app").unwrap_or(false) {
+            return Ok(path);
+        }
+    }
+    Err(RuzuleError::InvalidIpa("No .app folder found".to_string()))
+}
+
+/// The watch app nested in `app` (`Watch/*.app`)
+pub(crate) fn find_watch_app(app: &Path) -> Result<PathBuf> {
+    let watch = Layout::detect(app).contents_dir(app).join("Watch");
+    if watch.is_dir() {
+        for entry in fs::read_dir(&watch)? {
+            let path = entry?.path();
+            if path.is_dir() && path.extension().is_some_and(|e| e == "app") {
+                return Ok(path);
+            }
+        }
+    }
+    Err(RuzuleError::InvalidAppBundle("No watch app found in Watch/".to_string()))
+}
+
+pub fn copy_app<P: AsRef<Path>, Q: AsRef<Path>>(app_path: P, dest: Q) -> Result<PathBuf> {
+    let app_path = app_path.as_ref();
+    let dest = dest.as_ref();
+
+    // Check for Info.plist
+    if !Layout::detect(app_path).info_plist(app_path).exists() {
+        return Err(RuzuleError::InvalidAppBundle(
+            "No Info.plist found".to_string(),
+        ));
+    }
+
+    let payload = dest.join("Payload");
+    fs::create_dir_all(&payload)?;
+
+    let app_name = app_path
+        .file_name()
+        .ok_or_else(|| RuzuleError::InvalidInput("Invalid app path".to_string()))?;
+    let new_app_path = payload.join(app_name);
+
+    RealFs.copy_dir(app_path, &new_app_path)?;
+
+    Ok(new_app_path)
+}
+
+/// Zip `tmpdir/Payload` into `output`, compressing on `jobs` threads (default: one per core)
+///
+/// The archive is written next to `output` and renamed over it only once it's
+/// complete, so a failed or cancelled run leaves any existing file untouched.
+pub fn create_ipa<P: AsRef<Path>, Q: AsRef<Path>>(
+    tmpdir: P,
+    output: Q,
+    compression_level: u32,
+    jobs: Option<usize>,
+    cancel: &CancellationToken,
+    events: &Events,
+) -> Result<()> {
+    let output = output.as_ref();
+    let partial = sibling_path(output, "partial");
+    let result = write_ipa(tmpdir.as_ref(), &partial, compression_level, jobs, cancel, events)
+        .and_then(|()| fs::rename(&partial, output).map_err(Into::into));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Zip `tmpdir/Payload` into `writer`, which needn't be seekable (e.g. stdout).
+/// Returns the number of bytes written.
+pub fn stream_ipa<P: AsRef<Path>, W: Write>(
+    tmpdir: P,
+    writer: W,
+    compression_level: u32,
+    jobs: Option<usize>,
+    cancel: &CancellationToken,
+    events: &Events,
+) -> Result<u64> {
+    let tmpdir = tmpdir.as_ref();
+    let options = pack_options(compression_level);
+    let payload = tmpdir.join("Payload");
+    let progress = Progress::new(events, ProgressStage::Pack, disk_size(&payload));
+    let entries = payload_entries(tmpdir, cancel)?;
+
+    // Every entry is compressed before it's written, so the local headers
+    // carry their sizes and nothing needs seeking back to
+    let mut zip = StreamZip {
+        out: CountingWriter {
+            inner: writer,
+            count: 0,
+        },
+        central: Vec::new(),
+        entries: 0,
+    };
+    let threads = pack_threads(jobs);
+    write_parallel(&mut zip, &entries, options, tmpdir, threads, cancel, &progress)?;
+    zip.finish()
+}
+
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Adds what's written through it to a [`Progress`]
+struct ProgressWriter<'a, W> {
+    inner: W,
+    progress: &'a Progress,
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.progress.add(n as u64);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Move a finished .app to `output`, replacing what's there only on success
+///
+/// An existing `output` is set aside first and put back if the new bundle
+/// can't be moved into place.
+pub fn install_app<P: AsRef<Path>, Q: AsRef<Path>>(app_path: P, output: Q) -> Result<()> {
+    let app_path = app_path.as_ref();
+    let output = output.as_ref();
+
+    // Stage next to the output so the final step is a rename within one directory
+    let partial = sibling_path(output, "partial");
+    if fs::rename(app_path, &partial).is_err() {
+        // Different filesystem (e.g. a tmpfs temp dir)
+        if let Err(e) = RealFs.copy_dir(app_path, &partial) {
+            let _ = fs::remove_dir_all(&partial);
+            return Err(e.into());
+        }
+    }
+
+    let backup = sibling_path(output, "old");
+    let had_output = output.exists();
+    if had_output {
+        if let Err(e) = fs::rename(output, &backup) {
+            let _ = fs::remove_dir_all(&partial);
+            return Err(e.into());
+        }
+    }
+
+    if let Err(e) = fs::rename(&partial, output) {
+        let _ = fs::remove_dir_all(&partial);
+        if had_output {
+            let _ = fs::rename(&backup, output);
+        }
+        return Err(e.into());
+    }
+
+    if had_output {
+        let _ = fs::remove_dir_all(&backup);
+    }
+    Ok(())
+}
+
+/// The mode stored for a file in the ipa: its own where there is one, with
+/// the executable bits added to Mach-O files, which can't launch without them
+fn file_mode(path: &Path) -> Result<u32> {
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path)?.permissions().mode() & 0o7777
+    };
+    #[cfg(not(unix))]
+    let mode = 0o644;
+
+    if mode & 0o111 != 0o111 && macho::is_macho(path) {
+        Ok(mode | 0o755)
+    } else {
+        Ok(mode)
+    }
+}
+
+/// `options` for the entry of the file at `path`: its mode, and zip64 sizes
+/// if it's too big for plain ones
+fn file_options(options: SimpleFileOptions, path: &Path) -> Result<SimpleFileOptions> {
+    let large = fs::metadata(path)?.len() >= ZIP64_LIMIT;
+    Ok(options.unix_permissions(file_mode(path)?).large_file(large))
+}
+
+/// Hidden path next to `path`, used for staging output
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}-{}", name, suffix, std::process::id()))
+}
+
+fn write_ipa(
+    tmpdir: &Path,
+    output: &Path,
+    compression_level: u32,
+    jobs: Option<usize>,
+    cancel: &CancellationToken,
+    events: &Events,
+) -> Result<()> {
+    let zip = ZipWriter::new(File::create(output)?);
+    write_payload(zip, tmpdir, compression_level, jobs, cancel, events)
+}
+
+fn pack_options(compression_level: u32) -> SimpleFileOptions {
+    // zip refuses a level for stored entries
+    match compression_level {
+        0 => SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+        _ => SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(compression_level as i64)),
+    }
+}
+
+/// `jobs`, or one per core
+fn pack_threads(jobs: Option<usize>) -> usize {
+    jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+        .max(1)
+}
+
+/// An entry of the archive, in the order it's written
+enum PayloadEntry {
+    Dir(String),
+    File { name: String, path: PathBuf },
+    /// Stored as a link, as the framework links of a Contents/ bundle must stay
+    Symlink { name: String, target: String },
+}
+
+/// Entries compressed larger than this are spooled to disk instead of memory
+const SPOOL_IN_MEMORY: u64 = 32 * 1024 * 1024;
+
+/// A single-entry zip a worker compressed one file into
+trait Spool: Read + Write + Seek + Send {}
+impl<T: Read + Write + Seek + Send> Spool for T {}
+
+fn write_payload<W: Write + Seek>(
+    mut zip: ZipWriter<W>,
+    tmpdir: &Path,
+    compression_level: u32,
+    jobs: Option<usize>,
+    cancel: &CancellationToken,
+    events: &Events,
+) -> Result<()> {
+    let options = pack_options(compression_level);
+    let payload = tmpdir.join("Payload");
+    let progress = Progress::new(events, ProgressStage::Pack, disk_size(&payload));
+    let entries = payload_entries(tmpdir, cancel)?;
+
+    let threads = pack_threads(jobs);
+    if compression_level == 0 || threads == 1 {
+        // Nothing to gain from workers, so straight into the archive
+        for entry in &entries {
+            cancel.check()?;
+            match entry {
+                PayloadEntry::Dir(name) => zip.add_directory(name, options)?,
+                PayloadEntry::Symlink { name, target } => zip.add_symlink(name, target, options)?,
+                PayloadEntry::File { name, path } => {
+                    zip.start_file(name, file_options(options, path)?)?;
+                    let mut writer = ProgressWriter {
+                        inner: &mut zip,
+                        progress: &progress,
+                    };
+                    std::io::copy(&mut File::open(path)?, &mut writer)?;
+                }
+            }
+        }
+    } else {
+        write_parallel(&mut zip, &entries, options, tmpdir, threads, cancel, &progress)?;
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+/// Everything under `tmpdir/Payload` but hidden files, in archive order
+fn payload_entries(tmpdir: &Path, cancel: &CancellationToken) -> Result<Vec<PayloadEntry>> {
+    let payload = tmpdir.join("Payload");
+    let mut entries = Vec::new();
+    for entry in WalkDir::new(&payload) {
+        cancel.check()?;
+        let entry = entry?;
+        let path = entry.path();
+        let name = path.strip_prefix(tmpdir).expect("path is within tmpdir");
+
+        // Skip hidden files (fixes installd errors)
+        if name
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+
+        if entry.path_is_symlink() {
+            let name = name.to_string_lossy().replace('\\', "/");
+            let target = fs::read_link(path)?.to_string_lossy().replace('\\', "/");
+            entries.push(PayloadEntry::Symlink { name, target });
+        } else if path.is_file() {
+            let name = name.to_string_lossy().replace('\\', "/");
+            entries.push(PayloadEntry::File {
+                name,
+                path: path.to_path_buf(),
+            });
+        } else if path.is_dir() && path != payload {
+            let name = format!("{}/", name.to_string_lossy().replace('\\', "/"));
+            entries.push(PayloadEntry::Dir(name));
+        }
+    }
+    Ok(entries)
+}
+
+/// Where [`write_parallel`] puts the entries, in order
+trait Archive {
+    fn add_directory(&mut self, name: &str, options: SimpleFileOptions) -> Result<()>;
+    /// Copy in the entry of a single-entry zip made by [`compress_entry`]
+    fn add_compressed(&mut self, spool: Box<dyn Spool>) -> Result<()>;
+
+    fn add_symlink(&mut self, name: &str, target: &str, options: SimpleFileOptions) -> Result<()> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_symlink(name, target, options)?;
+        let mut spool = zip.finish()?;
+        spool.rewind()?;
+        self.add_compressed(Box::new(spool))
+    }
+
+    /// Compress a file of [`ZIP64_LIMIT`] bytes or more on the writing thread
+    fn add_large_file(
+        &mut self,
+        name: &str,
+        path: &Path,
+        options: SimpleFileOptions,
+        tmpdir: &Path,
+        cancel: &CancellationToken,
+        progress: &Progress,
+    ) -> Result<()> {
+        let (spool, _temp) = compress_entry(name, path, options, tmpdir, cancel, progress)?;
+        self.add_compressed(spool)
+    }
+}
+
+impl<W: Write + Seek> Archive for ZipWriter<W> {
+    fn add_directory(&mut self, name: &str, options: SimpleFileOptions) -> Result<()> {
+        Ok(ZipWriter::add_directory(self, name, options)?)
+    }
+
+    fn add_compressed(&mut self, spool: Box<dyn Spool>) -> Result<()> {
+        let mut archive = zip::ZipArchive::new(spool)?;
+        self.raw_copy_file(archive.by_index_raw(0)?)?;
+        Ok(())
+    }
+
+    fn add_symlink(&mut self, name: &str, target: &str, options: SimpleFileOptions) -> Result<()> {
+        Ok(ZipWriter::add_symlink(self, name, target, options)?)
+    }
+
+    // zip's raw copy loses the uncompressed size of zip64 entries, so these
+    // are compressed straight into the archive instead
+    fn add_large_file(
+        &mut self,
+        name: &str,
+        path: &Path,
+        options: SimpleFileOptions,
+        _tmpdir: &Path,
+        cancel: &CancellationToken,
+        progress: &Progress,
+    ) -> Result<()> {
+        cancel.check()?;
+        self.start_file(name, file_options(options, path)?)?;
+        let mut writer = ProgressWriter {
+            inner: self,
+            progress,
+        };
+        std::io::copy(&mut File::open(path)?, &mut writer)?;
+        Ok(())
+    }
+}
+
+/// A zip written front to back, for outputs that can't seek (e.g. stdout).
+/// Only takes entries whose sizes and CRC are already known.
+struct StreamZip<W> {
+    out: CountingWriter<W>,
+    /// Central directory records, written out by [`StreamZip::finish`]
+    central: Vec<u8>,
+    entries: u64,
+}
+
+/// Sizes and offsets from here on are stored in a zip64 extra field
+const ZIP64_LIMIT: u64 = 0xFFFF_FFFF;
+
+impl<W: Write> StreamZip<W> {
+    /// Write the local header of an entry, whose data the caller writes next,
+    /// and queue its central directory record
+    #[allow(clippy::too_many_arguments)]
+    fn add_entry(
+        &mut self,
+        name: &str,
+        method: u16,
+        modified: zip::DateTime,
+        crc: u32,
+        compressed: u64,
+        size: u64,
+        mode: u32,
+    ) -> Result<()> {
+        let offset = self.out.count;
+        let sizes_64 = compressed >= ZIP64_LIMIT || size >= ZIP64_LIMIT;
+        let version: u16 = if sizes_64 || offset >= ZIP64_LIMIT { 45 } else { 20 };
+        let clamp = |n: u64| n.min(ZIP64_LIMIT) as u32;
+
+        let mut local = Vec::with_capacity(30 + name.len() + 20);
+        local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        local.extend_from_slice(&version.to_le_bytes());
+        // Flags: UTF-8 names
+        local.extend_from_slice(&(1u16 << 11).to_le_bytes());
+        local.extend_from_slice(&method.to_le_bytes());
+        local.extend_from_slice(&modified.timepart().to_le_bytes());
+        local.extend_from_slice(&modified.datepart().to_le_bytes());
+        local.extend_from_slice(&crc.to_le_bytes());
+        local.extend_from_slice(&clamp(compressed).to_le_bytes());
+        local.extend_from_slice(&clamp(size).to_le_bytes());
+        local.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        local.extend_from_slice(&(if sizes_64 { 20u16 } else { 0 }).to_le_bytes());
+        local.extend_from_slice(name.as_bytes());
+        if sizes_64 {
+            local.extend_from_slice(&1u16.to_le_bytes());
+            local.extend_from_slice(&16u16.to_le_bytes());
+            local.extend_from_slice(&size.to_le_bytes());
+            local.extend_from_slice(&compressed.to_le_bytes());
+        }
+        self.out.write_all(&local)?;
+
+        // The central record's zip64 field holds only what overflowed, in this order
+        let mut extra = Vec::new();
+        for (value, overflowed) in [
+            (size, size >= ZIP64_LIMIT),
+            (compressed, compressed >= ZIP64_LIMIT),
+            (offset, offset >= ZIP64_LIMIT),
+        ] {
+            if overflowed {
+                extra.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let c = &mut self.central;
+        c.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        // Made by: Unix, so the mode in the external attributes is read
+        c.extend_from_slice(&(0x0300 | version).to_le_bytes());
+        c.extend_from_slice(&version.to_le_bytes());
+        c.extend_from_slice(&(1u16 << 11).to_le_bytes());
+        c.extend_from_slice(&method.to_le_bytes());
+        c.extend_from_slice(&modified.timepart().to_le_bytes());
+        c.extend_from_slice(&modified.datepart().to_le_bytes());
+        c.extend_from_slice(&crc.to_le_bytes());
+        c.extend_from_slice(&clamp(compressed).to_le_bytes());
+        c.extend_from_slice(&clamp(size).to_le_bytes());
+        c.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        let extra_len = if extra.is_empty() { 0 } else { 4 + extra.len() };
+        c.extend_from_slice(&(extra_len as u16).to_le_bytes());
+        // Comment length, disk number, internal attributes
+        c.extend_from_slice(&[0; 6]);
+        let dos_dir = if name.ends_with('/') { 0x10 } else { 0 };
+        c.extend_from_slice(&((mode << 16) | dos_dir).to_le_bytes());
+        c.extend_from_slice(&clamp(offset).to_le_bytes());
+        c.extend_from_slice(name.as_bytes());
+        if !extra.is_empty() {
+            c.extend_from_slice(&1u16.to_le_bytes());
+            c.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+            c.extend_from_slice(&extra);
+        }
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Write the central directory and return the archive's size
+    fn finish(mut self) -> Result<u64> {
+        let start = self.out.count;
+        self.out.write_all(&self.central)?;
+        let size = self.central.len() as u64;
+        let zip64 = self.entries >= 0xFFFF || start >= ZIP64_LIMIT || size >= ZIP64_LIMIT;
+
+        let mut end = Vec::new();
+        if zip64 {
+            let record = self.out.count;
+            end.extend_from_slice(&0x0606_4b50u32.to_le_bytes());
+            end.extend_from_slice(&44u64.to_le_bytes());
+            end.extend_from_slice(&0x032Du16.to_le_bytes());
+            end.extend_from_slice(&45u16.to_le_bytes());
+            end.extend_from_slice(&[0; 8]);
+            end.extend_from_slice(&self.entries.to_le_bytes());
+            end.extend_from_slice(&self.entries.to_le_bytes());
+            end.extend_from_slice(&size.to_le_bytes());
+            end.extend_from_slice(&start.to_le_bytes());
+            // Locator
+            end.extend_from_slice(&0x0706_4b50u32.to_le_bytes());
+            end.extend_from_slice(&0u32.to_le_bytes());
+            end.extend_from_slice(&record.to_le_bytes());
+            end.extend_from_slice(&1u32.to_le_bytes());
+        }
+        let entries = self.entries.min(0xFFFF) as u16;
+        end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]);
+        end.extend_from_slice(&entries.to_le_bytes());
+        end.extend_from_slice(&entries.to_le_bytes());
+        end.extend_from_slice(&(size.min(ZIP64_LIMIT) as u32).to_le_bytes());
+        end.extend_from_slice(&(start.min(ZIP64_LIMIT) as u32).to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.out.write_all(&end)?;
+        self.out.flush()?;
+        Ok(self.out.count)
+    }
+}
+
+impl<W: Write> Archive for StreamZip<W> {
+    fn add_directory(&mut self, name: &str, _options: SimpleFileOptions) -> Result<()> {
+        self.add_entry(name, 0, zip::DateTime::default(), 0, 0, 0, 0o40755)
+    }
+
+    fn add_compressed(&mut self, spool: Box<dyn Spool>) -> Result<()> {
+        let mut archive = zip::ZipArchive::new(spool)?;
+        let mut file = archive.by_index_raw(0)?;
+        let method = match file.compression() {
+            CompressionMethod::Stored => 0,
+            CompressionMethod::Deflated => 8,
+            other => {
+                return Err(RuzuleError::InvalidInput(format!(
+                    "Can't stream {} entries",
+                    other
+                )))
+            }
+        };
+        let name = file.name().to_string();
+        let mode = file.unix_mode().unwrap_or(0o100644);
+        let modified = file.last_modified().unwrap_or_default();
+        let (crc, compressed, size) = (file.crc32(), file.compressed_size(), file.size());
+        self.add_entry(&name, method, modified, crc, compressed, size, mode)?;
+        std::io::copy(&mut file, &mut self.out)?;
+        Ok(())
+    }
+}
+
+/// Compress files on `threads` workers, each into a zip of its own, and copy
+/// the compressed entries into `zip` in their original order
+///
+/// Workers stay at most `2 * threads` entries ahead of the writer, so only that
+/// many compressed entries are ever held at once.
+fn write_parallel<A: Archive>(
+    zip: &mut A,
+    entries: &[PayloadEntry],
+    options: SimpleFileOptions,
+    tmpdir: &Path,
+    threads: usize,
+    cancel: &CancellationToken,
+    progress: &Progress,
+) -> Result<()> {
+    let window = threads * 2;
+    let next = AtomicUsize::new(0);
+    // Index of the next entry to write, or usize::MAX once writing stopped
+    let written = Mutex::new(0usize);
+    let advanced = Condvar::new();
+    let (sender, receiver) = mpsc::sync_channel(threads);
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.min(entries.len()) {
+            let (sender, next, written, advanced) = (sender.clone(), &next, &written, &advanced);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(entry) = entries.get(i) else {
+                    break;
+                };
+                let mut at = written.lock().unwrap_or_else(|e| e.into_inner());
+                while i >= at.saturating_add(window) {
+                    at = advanced.wait(at).unwrap_or_else(|e| e.into_inner());
+                }
+                if *at == usize::MAX {
+                    break;
+                }
+                drop(at);
+                let compressed = match entry {
+                    PayloadEntry::File { name, path } => match fs::metadata(path) {
+                        // Left to the writer, see Archive::add_large_file
+                        Ok(meta) if meta.len() >= ZIP64_LIMIT => Ok(None),
+                        Ok(_) => {
+                            compress_entry(name, path, options, tmpdir, cancel, progress).map(Some)
+                        }
+                        Err(e) => Err(e.into()),
+                    },
+                    PayloadEntry::Dir(_) | PayloadEntry::Symlink { .. } => Ok(None),
+                };
+                // The receiver is gone once writing failed
+                if sender.send((i, compressed)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Workers finish out of order; hold entries back until it's their turn
+        let result = (|| {
+            let mut pending = BTreeMap::new();
+            let mut index = 0;
+            for (i, compressed) in receiver {
+                pending.insert(i, compressed);
+                while let Some(compressed) = pending.remove(&index) {
+                    match (&entries[index], compressed?) {
+                        (PayloadEntry::Dir(name), _) => zip.add_directory(name, options)?,
+                        (PayloadEntry::Symlink { name, target }, _) => {
+                            zip.add_symlink(name, target, options)?
+                        }
+                        (PayloadEntry::File { .. }, Some((spool, _temp))) => {
+                            zip.add_compressed(spool)?
+                        }
+                        (PayloadEntry::File { name, path }, None) => {
+                            zip.add_large_file(name, path, options, tmpdir, cancel, progress)?
+                        }
+                    }
+                    index += 1;
+                    *written.lock().unwrap_or_else(|e| e.into_inner()) = index;
+                    advanced.notify_all();
+                }
+            }
+            Ok(())
+        })();
+
+        // Release workers still waiting for their turn after a failure
+        *written.lock().unwrap_or_else(|e| e.into_inner()) = usize::MAX;
+        advanced.notify_all();
+        result
+    })
+}
+
+/// Compress one file into a single-entry zip, in memory or, if it's large, in
+/// a temp file under `tmpdir` that's removed when the returned [`TempFile`] drops
+fn compress_entry(
+    name: &str,
+    path: &Path,
+    options: SimpleFileOptions,
+    tmpdir: &Path,
+    cancel: &CancellationToken,
+    progress: &Progress,
+) -> Result<(Box<dyn Spool>, Option<TempFile>)> {
+    cancel.check()?;
+    let (spool, temp): (Box<dyn Spool>, _) = if fs::metadata(path)?.len() > SPOOL_IN_MEMORY {
+        let temp = TempFile::new_in(tmpdir)?;
+        let file = fs::OpenOptions::new().read(true).write(true).open(temp.path())?;
+        (Box::new(file), Some(temp))
+    } else {
+        (Box::new(Cursor::new(Vec::new())), None)
+    };
+
+    let mut zip = ZipWriter::new(spool);
+    zip.start_file(name, file_options(options, path)?)?;
+    let mut writer = ProgressWriter {
+        inner: &mut zip,
+        progress,
+    };
+    std::io::copy(&mut File::open(path)?, &mut writer)?;
+    let mut spool = zip.finish()?;
+    spool.rewind()?;
+    Ok((spool, temp))
+}
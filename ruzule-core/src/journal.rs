@@ -0,0 +1,147 @@
+//! Work directories that survive an interrupted run.
+//!
+//! With [`InjectOptions::resume`], a run works in a directory under the cache
+//! named after its input and options instead of a temporary one, and records
+//! each phase it finishes in `journal.json`. Running the same command again
+//! after a crash or Ctrl-C continues after the last finished phase. The
+//! directory is removed once the output is written.
+
+use crate::cache::cache_dir;
+use crate::error::{Result, RuzuleError};
+use crate::export::ExportFormat;
+use crate::injector::InjectOptions;
+use crate::lock::Lock;
+use crate::temp::TempDir;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// How far a resumable run got
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// The input is unpacked
+    Extracted,
+    /// Tweaks and modifications are being applied. The app may be half
+    /// modified, so this isn't resumed from; the run starts over.
+    Injecting,
+    /// Every modification is made, pre-sign hooks included
+    Injected,
+    /// Fakesigned and thinned, pre-pack hooks run; only packing is left
+    Signed,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Extracted => "extracted",
+            Phase::Injecting => "injecting",
+            Phase::Injected => "injected",
+            Phase::Signed => "signed",
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    phase: Phase,
+    /// The unpacked .app, relative to the work directory
+    app: PathBuf,
+}
+
+#[derive(Debug)]
+pub(crate) struct Journal {
+    dir: PathBuf,
+    entry: Option<Entry>,
+    /// Two identical runs with different outputs would share the directory
+    _lock: Lock,
+}
+
+impl Journal {
+    /// The work directory for running `options` on `input`, and its journal.
+    /// A directory left by a run that stopped before finishing extraction, or
+    /// while injecting, is emptied first. The directory is kept when dropped.
+    pub(crate) fn open(input: &Path, options: &InjectOptions) -> Result<(TempDir, Self)> {
+        let root = cache_dir().ok_or_else(|| {
+            RuzuleError::InvalidInput(
+                "no cache directory to resume in (set RUZULE_CACHE_DIR)".to_string(),
+            )
+        })?;
+        let key = key(input, options)?;
+        let lock = Lock::exclusive(&format!("work-{}", key), "the work directory of this run")?;
+        let dir = root.join("work").join(key);
+        let entry = fs::read(dir.join("journal.json"))
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Entry>(&data).ok())
+            .filter(|e| e.phase != Phase::Injecting && dir.join(&e.app).is_dir());
+        if entry.is_none() && dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+
+        let mut tmpdir = TempDir::at(&dir)?;
+        tmpdir.keep(true);
+        Ok((
+            tmpdir,
+            Self {
+                dir,
+                entry,
+                _lock: lock,
+            },
+        ))
+    }
+
+    /// The last phase finished, if any
+    pub(crate) fn phase(&self) -> Option<Phase> {
+        self.entry.as_ref().map(|e| e.phase)
+    }
+
+    /// The unpacked .app of an earlier run
+    pub(crate) fn app(&self) -> Option<PathBuf> {
+        self.entry.as_ref().map(|e| self.dir.join(&e.app))
+    }
+
+    /// Note that `phase` was reached, with `app` the unpacked .app
+    pub(crate) fn record(&mut self, phase: Phase, app: &Path) -> Result<()> {
+        let entry = Entry {
+            phase,
+            app: app.strip_prefix(&self.dir).unwrap_or(app).to_path_buf(),
+        };
+        // Written aside and renamed, so a crash never leaves half a journal
+        let partial = self.dir.join("journal.json.partial");
+        fs::write(&partial, serde_json::to_vec_pretty(&entry)?)?;
+        fs::rename(&partial, self.dir.join("journal.json"))?;
+        self.entry = Some(entry);
+        Ok(())
+    }
+}
+
+/// Names the work directory: the input, the files going in (by size and
+/// modification time) and every option that affects the unpacked app
+fn key(input: &Path, options: &InjectOptions) -> Result<String> {
+    let stamp = |path: &Path| -> (PathBuf, Option<(u64, u64, u32)>) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let meta = fs::metadata(&path).ok().and_then(|m| {
+            let modified = m.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+            Some((m.len(), modified.as_secs(), modified.subsec_nanos()))
+        });
+        (path, meta)
+    };
+
+    let options = InjectOptions {
+        output: None,
+        compress: 0,
+        jobs: None,
+        keep_temp: false,
+        resume: false,
+        export: ExportFormat::Ipa,
+        export_certificate: None,
+        export_profile: None,
+        ..options.clone()
+    };
+    let files: Vec<_> = options.files.iter().chain(&options.cyans).map(|p| stamp(p)).collect();
+    let key = serde_json::to_vec(&(stamp(input), files, options))?;
+    Ok(hex::encode(&Sha256::digest(key)[..16]))
+}
@@ -0,0 +1,99 @@
+#[cfg(feature = "native")]
+pub mod app_bundle;
+pub mod buffer;
+#[cfg(feature = "native")]
+pub mod cache;
+pub mod cancel;
+pub mod cyan_config;
+#[cfg(feature = "native")]
+pub mod daemon;
+#[cfg(feature = "native")]
+pub mod deb;
+#[cfg(feature = "native")]
+pub mod deps;
+pub mod entitlements;
+pub mod error;
+pub mod events;
+#[cfg(feature = "native")]
+pub mod executable;
+#[cfg(feature = "native")]
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "native")]
+pub mod frameworks;
+pub mod hooks;
+#[cfg(feature = "native")]
+mod icon;
+#[cfg(feature = "native")]
+pub mod identity;
+#[cfg(feature = "native")]
+pub mod injector;
+#[cfg(feature = "native")]
+pub mod ipa;
+#[cfg(feature = "native")]
+pub mod journal;
+#[cfg(feature = "native")]
+mod lock;
+#[cfg(feature = "native")]
+pub mod macho;
+#[cfg(feature = "native")]
+pub mod optimize;
+#[cfg(feature = "native")]
+pub mod plan;
+pub mod platform;
+pub mod plist_ext;
+#[cfg(feature = "native")]
+mod preflight;
+#[cfg(feature = "native")]
+pub mod preset;
+pub mod prompt;
+#[cfg(feature = "native")]
+pub mod report;
+#[cfg(feature = "native")]
+pub mod sign;
+#[cfg(feature = "native")]
+pub mod swift;
+#[cfg(feature = "native")]
+mod temp;
+#[cfg(feature = "native")]
+pub mod validate;
+pub mod version;
+pub mod vfs;
+
+#[cfg(feature = "native")]
+pub use app_bundle::AppBundle;
+pub use buffer::Arch;
+pub use cancel::CancellationToken;
+#[cfg(feature = "native")]
+pub use cyan_config::compress_cyan;
+pub use cyan_config::{
+    diff_cyans, parse_cyan, CyanCompression, CyanConfig, CyanDiff, CyanWriter, ParsedCyan,
+};
+pub use entitlements::EntitlementOps;
+pub use error::{Result, RuzuleError};
+pub use events::{Event, EventHandler, EventLevel, Events, PlistChange, ProgressStage};
+#[cfg(feature = "native")]
+pub use executable::{Executable, MainExecutable};
+#[cfg(feature = "native")]
+pub use export::ExportFormat;
+#[cfg(feature = "native")]
+pub use frameworks::{get_framework_for_dep, BundledFramework, SubstrateBackend};
+pub use hooks::{Hook, HookStage};
+#[cfg(feature = "native")]
+pub use injector::{inject, CyanConflict, InjectOptions, InjectReport, Injector, MinimumCheck};
+#[cfg(feature = "native")]
+pub use ipa::{copy_app, create_ipa, extract_ipa, install_app, stream_ipa};
+#[cfg(feature = "native")]
+pub use optimize::{OptimizeOptions, OptimizeReport};
+#[cfg(feature = "native")]
+pub use plan::{Operation, Plan};
+pub use platform::Platform;
+pub use plist_ext::{PlistFile, PlistFormat, PlistMerge};
+#[cfg(feature = "native")]
+pub use preset::Preset;
+pub use prompt::{AssumeYes, NoInput, Prompter};
+#[cfg(feature = "native")]
+pub use report::AppReport;
+pub use version::Version;
+pub use vfs::{Fs, MemFs, RealFs};
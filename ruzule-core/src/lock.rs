@@ -0,0 +1,79 @@
+//! Advisory locks between ruzule processes, so parallel runs (CI matrices,
+//! batch scripts) fail fast instead of writing over each other.
+//!
+//! Lock files live in the cache's `locks/` directory and are never deleted;
+//! the OS releases a lock when its holder exits, however it exits.
+
+use crate::cache::cache_dir;
+use crate::error::{Result, RuzuleError};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+pub(crate) const LOCKS_DIR: &str = "locks";
+
+/// Held until dropped
+#[derive(Debug)]
+pub(crate) struct Lock {
+    _file: Option<File>,
+}
+
+impl Lock {
+    /// Lock `name` for this process alone, or fail with [`RuzuleError::Locked`]
+    /// naming `what` if another process holds it
+    pub(crate) fn exclusive(name: &str, what: &str) -> Result<Self> {
+        Self::acquire(name, what, false)
+    }
+
+    /// Lock `name` alongside other shared holders; fails only while someone
+    /// holds it exclusively
+    pub(crate) fn shared(name: &str, what: &str) -> Result<Self> {
+        Self::acquire(name, what, true)
+    }
+
+    fn acquire(name: &str, what: &str, shared: bool) -> Result<Self> {
+        let dir = locks_dir();
+        fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(format!("{}.lock", name)))?;
+
+        let result = if shared { file.try_lock_shared() } else { file.try_lock() };
+        match result {
+            Ok(()) => Ok(Self { _file: Some(file) }),
+            Err(TryLockError::WouldBlock) => Err(RuzuleError::Locked(what.to_string())),
+            // Some network filesystems can't lock; go ahead unprotected there
+            Err(TryLockError::Error(e)) if e.kind() == ErrorKind::Unsupported => {
+                log::debug!("can't lock {} on this filesystem: {}", what, e);
+                Ok(Self { _file: None })
+            }
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
+
+fn locks_dir() -> PathBuf {
+    cache_dir()
+        .unwrap_or_else(|| std::env::temp_dir().join("ruzule"))
+        .join(LOCKS_DIR)
+}
+
+/// Lock `output` for writing, so no other run writes the same file at the same time
+pub(crate) fn lock_output(output: &Path) -> Result<Lock> {
+    let path = std::path::absolute(output).unwrap_or_else(|_| output.to_path_buf());
+    let name = hex::encode(&Sha256::digest(path.to_string_lossy().as_bytes())[..16]);
+    Lock::exclusive(&format!("output-{}", name), &path.display().to_string())
+}
+
+/// Shared use of the cache, held by every run; [`crate::cache::clean`] needs
+/// it exclusively so it doesn't delete what a run is using
+pub(crate) fn lock_cache(exclusive: bool) -> Result<Lock> {
+    match exclusive {
+        true => Lock::exclusive("cache", "the cache"),
+        false => Lock::shared("cache", "the cache"),
+    }
+}
@@ -0,0 +1,274 @@
+//! Mach-O edits on files.
+//!
+//! Binaries are memory-mapped rather than read into memory, and load command
+//! edits are written in place, so a multi-gigabyte game binary only has the
+//! pages that are actually touched resident. Rewrites that change the size
+//! (thinning, removing the signature) stream into a temp file next to the
+//! binary and rename it over the original.
+
+use crate::buffer::{self, Patch};
+use crate::error::Result;
+use crate::temp::TempFile;
+use crate::version::Version;
+use memmap2::{Mmap, MmapMut};
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+/// Map `path` read-only.
+///
+/// The binaries ruzule works on are copies in its own temporary directory, or
+/// inputs it only reads, so nothing truncates them while they're mapped.
+pub(crate) fn map(path: &Path) -> Result<Mmap> {
+    let file = File::open(path)?;
+    // SAFETY: see above; the map never outlives the calls in this crate that use it
+    Ok(unsafe { Mmap::map(&file)? })
+}
+
+/// Write the patches `plan` works out to `path` in place. Nothing is written
+/// if `plan` fails, e.g. because a load command doesn't fit in one slice.
+fn patch_file<F>(path: &Path, plan: F) -> Result<()>
+where
+    F: FnOnce(&[u8]) -> Result<Vec<Patch>>,
+{
+    let file = fs::OpenOptions::new().read(true).write(true).open(path)?;
+    // SAFETY: as for `map`
+    let mut data = unsafe { MmapMut::map_mut(&file)? };
+    let patches = plan(&data)?;
+    if !patches.is_empty() {
+        buffer::apply_patches(&mut data, &patches);
+        data.flush()?;
+    }
+    Ok(())
+}
+
+/// Replace `path` with what `write` produces from its contents, keeping its
+/// permissions. `write` returns false to leave the file as it is.
+fn rewrite<F>(path: &Path, write: F) -> Result<bool>
+where
+    F: FnOnce(&[u8], &mut dyn Write) -> Result<bool>,
+{
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let temp = TempFile::new_in(dir)?;
+    {
+        let data = map(path)?;
+        let mut out = BufWriter::new(fs::OpenOptions::new().write(true).open(temp.path())?);
+        if !write(&data, &mut out)? {
+            return Ok(false);
+        }
+        out.flush()?;
+    } // Unmapped before the rename, which Windows requires
+
+    fs::set_permissions(temp.path(), fs::metadata(path)?.permissions())?;
+    temp.persist(path)?;
+    Ok(true)
+}
+
+/// Whether the file at `path` is a Mach-O, from its first bytes
+pub fn is_macho<P: AsRef<Path>>(path: P) -> bool {
+    let mut magic = [0; 4];
+    File::open(path.as_ref()).and_then(|mut f| f.read_exact(&mut magic)).is_ok()
+        && buffer::is_macho(&magic)
+}
+
+/// Add the executable bits (`0755`) to every Mach-O at or under `path` that
+/// lacks them, as binaries from zips and .debs sometimes do. Returns how many
+/// were changed.
+#[cfg(unix)]
+pub fn make_executable<P: AsRef<Path>>(path: P) -> Result<usize> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut count = 0;
+    for entry in walkdir::WalkDir::new(path.as_ref()).into_iter().flatten() {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        let mode = meta.permissions().mode();
+        if !meta.is_file() || mode & 0o111 == 0o111 || !is_macho(entry.path()) {
+            continue;
+        }
+        fs::set_permissions(entry.path(), fs::Permissions::from_mode(mode | 0o755))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Files have no executable bit to set here
+#[cfg(not(unix))]
+pub fn make_executable<P: AsRef<Path>>(_path: P) -> Result<usize> {
+    Ok(0)
+}
+
+pub fn is_encrypted<P: AsRef<Path>>(path: P) -> Result<bool> {
+    buffer::is_encrypted(&map(path.as_ref())?)
+}
+
+pub fn get_dependencies<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+    buffer::dependencies(&map(path.as_ref())?)
+}
+
+/// Install name, every linked dylib and rpath, unfiltered (unlike [`get_dependencies`])
+pub fn linkage<P: AsRef<Path>>(path: P) -> Result<buffer::Linkage> {
+    buffer::linkage(&map(path.as_ref())?)
+}
+
+pub fn has_bitcode<P: AsRef<Path>>(path: P) -> Result<bool> {
+    buffer::has_bitcode(&map(path.as_ref())?)
+}
+
+pub fn has_arch<P: AsRef<Path>>(path: P, arch: buffer::Arch) -> Result<bool> {
+    buffer::has_arch(&map(path.as_ref())?, arch)
+}
+
+pub fn architectures<P: AsRef<Path>>(path: P) -> Result<Vec<(u32, String)>> {
+    buffer::architectures(&map(path.as_ref())?)
+}
+
+pub fn build_platforms<P: AsRef<Path>>(path: P) -> Result<Vec<u32>> {
+    buffer::build_platforms(&map(path.as_ref())?)
+}
+
+pub fn min_os_version<P: AsRef<Path>>(path: P) -> Result<Option<Version>> {
+    buffer::min_os_version(&map(path.as_ref())?)
+}
+
+pub fn set_min_os_version<P: AsRef<Path>>(path: P, version: Version) -> Result<()> {
+    let path = path.as_ref();
+    log::debug!("setting minimum OS of {} to {}", path.display(), version);
+    patch_file(path, |data| buffer::min_os_patches(data, version))
+}
+
+pub fn jailbreak_markers<P: AsRef<Path>>(path: P) -> Result<Vec<&'static str>> {
+    Ok(buffer::jailbreak_markers(&map(path.as_ref())?))
+}
+
+/// Weakly link `dylib_path` from `path`. Returns the load commands dropped to
+/// make room, if there wasn't enough.
+pub fn add_weak_dylib<P: AsRef<Path>>(path: P, dylib_path: &str) -> Result<Vec<&'static str>> {
+    let path = path.as_ref();
+    log::debug!("adding weak load command {} to {}", dylib_path, path.display());
+    let mut dropped = Vec::new();
+    patch_file(path, |data| buffer::add_weak_dylib_patches(data, dylib_path, &mut dropped))?;
+    Ok(dropped)
+}
+
+pub fn replace_dylib<P: AsRef<Path>>(path: P, old_path: &str, new_path: &str) -> Result<()> {
+    let path = path.as_ref();
+    log::debug!("replacing {} with {} in {}", old_path, new_path, path.display());
+    patch_file(path, |data| buffer::replace_dylib_patches(data, old_path, new_path))
+}
+
+/// Delete the load commands for `dylib_path` from `path`. Returns whether there were any.
+pub fn remove_dylib<P: AsRef<Path>>(path: P, dylib_path: &str) -> Result<bool> {
+    let path = path.as_ref();
+    let mut removed = false;
+    patch_file(path, |data| {
+        let patches = buffer::remove_dylib_patches(data, dylib_path)?;
+        removed = !patches.is_empty();
+        Ok(patches)
+    })?;
+    if removed {
+        log::debug!("removed load command {} from {}", dylib_path, path.display());
+    }
+    Ok(removed)
+}
+
+/// Weak-link `framework` wherever `path` loads it. Returns whether anything changed.
+pub fn weaken_framework<P: AsRef<Path>>(path: P, framework: &str) -> Result<bool> {
+    let path = path.as_ref();
+    let mut weakened = false;
+    patch_file(path, |data| {
+        let patches = buffer::weaken_framework_patches(data, framework)?;
+        weakened = !patches.is_empty();
+        Ok(patches)
+    })?;
+    if weakened {
+        log::debug!("weak-linked {} in {}", framework, path.display());
+    }
+    Ok(weakened)
+}
+
+pub fn change_install_name<P: AsRef<Path>>(path: P, new_name: &str) -> Result<()> {
+    let path = path.as_ref();
+    log::debug!("setting install name of {} to {}", path.display(), new_name);
+    patch_file(path, |data| buffer::install_name_patches(data, new_name))
+}
+
+/// Add `rpath` to `path`. Returns the load commands dropped to make room, if
+/// there wasn't enough.
+pub fn add_rpath<P: AsRef<Path>>(path: P, rpath: &str) -> Result<Vec<&'static str>> {
+    let path = path.as_ref();
+    log::debug!("adding rpath {} to {}", rpath, path.display());
+    let mut dropped = Vec::new();
+    patch_file(path, |data| buffer::add_rpath_patches(data, rpath, &mut dropped))?;
+    Ok(dropped)
+}
+
+pub fn rpaths<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+    buffer::rpaths(&map(path.as_ref())?)
+}
+
+/// Delete every `LC_RPATH` for `rpath` from `path`. Returns whether there were any.
+pub fn remove_rpath<P: AsRef<Path>>(path: P, rpath: &str) -> Result<bool> {
+    let path = path.as_ref();
+    let mut removed = false;
+    patch_file(path, |data| {
+        let patches = buffer::remove_rpath_patches(data, rpath)?;
+        removed = !patches.is_empty();
+        Ok(patches)
+    })?;
+    if removed {
+        log::debug!("removed rpath {} from {}", rpath, path.display());
+    }
+    Ok(removed)
+}
+
+pub fn thin_to_arm64<P: AsRef<Path>>(path: P) -> Result<bool> {
+    thin_to_arch(path, buffer::Arch::Arm64)
+}
+
+/// Keep only the `arch` slice. Returns false when the binary already is only that.
+pub fn thin_to_arch<P: AsRef<Path>>(path: P, arch: buffer::Arch) -> Result<bool> {
+    rewrite(path.as_ref(), |data, out| match buffer::arch_slice(data, arch)? {
+        Some(range) => {
+            out.write_all(&data[range])?;
+            Ok(true)
+        }
+        None => Ok(false),
+    })
+}
+
+/// Keep only the slices a binary built for `archs` (see [`architectures`]) can load
+pub fn thin_to_architectures<P: AsRef<Path>>(path: P, archs: &[(u32, String)]) -> Result<bool> {
+    rewrite(path.as_ref(), |data, out| buffer::write_thinned(data, archs, out))
+}
+
+/// Merge the slices of `inputs` into a fat binary at `output`, with the first
+/// input's permissions. Returns the architectures it holds.
+pub fn merge_slices<P: AsRef<Path>>(inputs: &[P], output: &Path) -> Result<Vec<String>> {
+    let dir = output.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let temp = TempFile::new_in(dir)?;
+    let archs = {
+        let maps = inputs.iter().map(|p| map(p.as_ref())).collect::<Result<Vec<_>>>()?;
+        let binaries: Vec<&[u8]> = maps.iter().map(|m| &m[..]).collect();
+        let mut out = BufWriter::new(fs::OpenOptions::new().write(true).open(temp.path())?);
+        let archs = buffer::write_universal(&binaries, &mut out)?;
+        out.flush()?;
+        archs
+    }; // Unmapped before the rename, which Windows requires
+
+    if let Some(first) = inputs.first() {
+        fs::set_permissions(temp.path(), fs::metadata(first.as_ref())?.permissions())?;
+    }
+    temp.persist(output)?;
+    log::debug!("merged {} into {}", archs.join(", "), output.display());
+    Ok(archs)
+}
+
+pub fn remove_code_signature<P: AsRef<Path>>(path: P) -> Result<()> {
+    rewrite(path.as_ref(), |data, out| {
+        buffer::write_without_signature(data, out)?;
+        Ok(true)
+    })?;
+    Ok(())
+}
@@ -0,0 +1,342 @@
+//! Size reductions that leave what the app does alone, for `ruzule optimize`.
+//!
+//! Each step can be turned off in [`OptimizeOptions`]:
+//!
+//! - thinning every binary to arm64, which drops simulator and 32-bit slices
+//! - removing embedded bitcode (`bitcode_strip`, or `llvm-bitcode-strip`)
+//! - stripping debug and local symbols (`strip -S -x`, or `llvm-strip`)
+//! - removing the `.lproj` directories of languages not asked to be kept
+//! - recompressing PNGs losslessly, keeping whichever copy is smaller
+//! - removing frameworks an extension bundles that are identical to the app's
+//!
+//! Steps that need a tool are skipped when it isn't on the `PATH`, and the
+//! report says so. Stripping invalidates signatures, so stripped apps are
+//! fakesigned.
+
+use crate::app_bundle::AppBundle;
+use crate::cancel::CancellationToken;
+use crate::error::{Result, RuzuleError};
+use crate::events::{Event, Events};
+use crate::executable::Executable;
+use crate::injector::has_extension;
+use crate::ipa::{copy_app, create_ipa, extract_ipa};
+use crate::macho;
+use crate::plan::disk_size;
+use crate::platform::{self, Layout};
+use crate::temp::TempDir;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::ImageEncoder;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone)]
+pub struct OptimizeOptions {
+    pub thin: bool,
+    pub strip_bitcode: bool,
+    pub strip_symbols: bool,
+    /// Languages to keep (`en` keeps `en.lproj` and `en-GB.lproj`); every
+    /// localization is kept when empty. `Base.lproj` is always kept.
+    pub keep_localizations: Vec<String>,
+    pub recompress_png: bool,
+    pub dedupe_frameworks: bool,
+    /// Compression level of the output ipa (0-9)
+    pub compress: u32,
+    pub jobs: Option<usize>,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            thin: true,
+            strip_bitcode: true,
+            strip_symbols: true,
+            keep_localizations: Vec::new(),
+            recompress_png: true,
+            dedupe_frameworks: true,
+            compress: 9,
+            jobs: None,
+        }
+    }
+}
+
+/// What one step saved, measured on the unpacked app
+#[derive(Debug, Clone, Serialize)]
+pub struct StepSaving {
+    /// `thin`, `bitcode`, `symbols`, `localizations`, `png` or `dedupe`
+    pub name: &'static str,
+    pub saved: u64,
+    /// Why the step didn't run, if it was on but couldn't
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptimizeReport {
+    pub output: PathBuf,
+    /// Bytes on disk, summed over every file for an .app
+    pub input_size: u64,
+    pub output_size: u64,
+    pub steps: Vec<StepSaving>,
+}
+
+/// Optimize the app at `input` (.ipa, .tipa or .app) into an .ipa at `output`
+pub fn optimize(
+    input: &Path,
+    output: &Path,
+    options: &OptimizeOptions,
+    events: &Events,
+    cancel: &CancellationToken,
+) -> Result<OptimizeReport> {
+    if !input.exists() {
+        return Err(RuzuleError::FileNotFound(input.to_path_buf()));
+    }
+    if !has_extension(output, &["ipa", "tipa"]) {
+        return Err(RuzuleError::InvalidInput("The output must be an .ipa or .tipa".to_string()));
+    }
+
+    let tmpdir = TempDir::new()?;
+    let input_size = disk_size(input);
+    events.emit(Event::Extracting);
+    let app_path = if has_extension(input, &["ipa", "tipa"]) {
+        extract_ipa(input, tmpdir.path(), cancel, events)?
+    } else {
+        copy_app(input, tmpdir.path())?
+    };
+    events.emit(Event::Extracted);
+    let app = AppBundle::with_events(&app_path, events.clone())?.with_jobs(options.jobs);
+
+    let mut steps = Vec::new();
+    let mut resign = false;
+    // Runs a step and records what it saved; false if it was skipped
+    let mut step = |name: &'static str, run: &dyn Fn() -> Result<Option<String>>| {
+        cancel.check()?;
+        let before = disk_size(&app_path);
+        let skipped = run()?;
+        let saved = before.saturating_sub(disk_size(&app_path));
+        let ran = skipped.is_none();
+        steps.push(StepSaving { name, saved, skipped });
+        Ok::<bool, RuzuleError>(ran)
+    };
+
+    if options.thin {
+        step("thin", &|| app.thin_all().map(|_| None))?;
+    }
+    if options.strip_bitcode {
+        resign |= step("bitcode", &|| strip_bitcode(&app))?;
+    }
+    if options.strip_symbols {
+        resign |= step("symbols", &|| strip_symbols(&app))?;
+    }
+    if !options.keep_localizations.is_empty() {
+        step("localizations", &|| {
+            remove_localizations(&app, &options.keep_localizations).map(|_| None)
+        })?;
+    }
+    if options.recompress_png {
+        step("png", &|| recompress_pngs(&app_path, cancel).map(|_| None))?;
+    }
+    if options.dedupe_frameworks {
+        step("dedupe", &|| dedupe_frameworks(&app, events).map(|_| None))?;
+    }
+    if resign {
+        cancel.check()?;
+        app.fakesign_all()?;
+    }
+
+    cancel.check()?;
+    events.emit(Event::Packing(output.to_path_buf()));
+    create_ipa(tmpdir.path(), output, options.compress, options.jobs, cancel, events)?;
+    events.emit(Event::Done(output.to_path_buf()));
+
+    Ok(OptimizeReport {
+        output: output.to_path_buf(),
+        input_size,
+        output_size: disk_size(output),
+        steps,
+    })
+}
+
+/// The main binary and every nested one
+fn binaries(app: &AppBundle) -> Result<Vec<PathBuf>> {
+    let nested = app.nested_executables()?.into_iter().map(|e| e.path);
+    Ok(std::iter::once(app.executable.inner.path.clone()).chain(nested).collect())
+}
+
+/// The first of `candidates` (a command and its leading arguments) on the `PATH`
+fn find_tool(candidates: &[&[&'static str]]) -> Option<Vec<&'static str>> {
+    let path = std::env::var_os("PATH")?;
+    let on_path = |name: &str| {
+        std::env::split_paths(&path).any(|dir| {
+            dir.join(name).is_file() || dir.join(format!("{}.exe", name)).is_file()
+        })
+    };
+    candidates.iter().find(|c| on_path(c[0])).map(|c| c.to_vec())
+}
+
+fn run_tool(tool: &[&str], args: &[&std::ffi::OsStr]) -> Result<()> {
+    let output = Command::new(tool[0]).args(&tool[1..]).args(args).output()?;
+    if !output.status.success() {
+        return Err(RuzuleError::ToolFailed(format!(
+            "{}: {}",
+            tool.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn strip_bitcode(app: &AppBundle) -> Result<Option<String>> {
+    let with_bitcode: Vec<PathBuf> = binaries(app)?
+        .into_iter()
+        .filter(|path| macho::has_bitcode(path).unwrap_or(false))
+        .collect();
+    if with_bitcode.is_empty() {
+        return Ok(None);
+    }
+    let Some(tool) = find_tool(&[&["xcrun", "bitcode_strip"], &["llvm-bitcode-strip"]]) else {
+        return Ok(Some("bitcode_strip or llvm-bitcode-strip isn't installed".to_string()));
+    };
+    for path in with_bitcode {
+        let stripped = path.with_extension("stripped");
+        let args = ["-r".as_ref(), path.as_os_str(), "-o".as_ref(), stripped.as_os_str()];
+        run_tool(&tool, &args)?;
+        fs::set_permissions(&stripped, fs::metadata(&path)?.permissions())?;
+        fs::rename(&stripped, &path)?;
+    }
+    Ok(None)
+}
+
+fn strip_symbols(app: &AppBundle) -> Result<Option<String>> {
+    let Some(tool) = find_tool(&[&["xcrun", "strip"], &["llvm-strip"]]) else {
+        return Ok(Some("strip or llvm-strip isn't installed".to_string()));
+    };
+    for path in binaries(app)? {
+        // Debug and local symbols only; exported ones are what other binaries link
+        let args = ["-S".as_ref(), "-x".as_ref(), path.as_os_str()];
+        if let Err(e) = run_tool(&tool, &args) {
+            log::warn!("couldn't strip {}: {}", path.display(), e);
+        }
+    }
+    Ok(None)
+}
+
+/// Remove every `.lproj` for a language outside `keep`, anywhere in the bundle
+fn remove_localizations(app: &AppBundle, keep: &[String]) -> Result<()> {
+    let kept = |language: &str| {
+        let language = language.to_lowercase();
+        language == "base"
+            || keep.iter().map(|k| k.to_lowercase()).any(|k| {
+                language == k
+                    || language.strip_prefix(&k).is_some_and(|region| {
+                        region.starts_with('-') || region.starts_with('_')
+                    })
+            })
+    };
+
+    let mut removed = Vec::new();
+    let mut walker = WalkDir::new(&app.path).into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy();
+        let Some(language) = name.strip_suffix(".lproj") else {
+            continue;
+        };
+        if entry.file_type().is_dir() {
+            walker.skip_current_dir();
+            if !kept(language) {
+                removed.push(entry.path().to_path_buf());
+            }
+        }
+    }
+    for path in &removed {
+        fs::remove_dir_all(path)?;
+    }
+    Ok(())
+}
+
+/// Re-encode PNGs at the highest compression, replacing those that shrink.
+/// Xcode's crushed PNGs (`CgBI`) only decode on Apple platforms and PNGs with
+/// a colour profile would lose it, so both are left alone.
+fn recompress_pngs(app_path: &Path, cancel: &CancellationToken) -> Result<()> {
+    for entry in WalkDir::new(app_path) {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || !has_extension(path, &["png"]) {
+            continue;
+        }
+        cancel.check()?;
+
+        let data = fs::read(path)?;
+        let crushed = data.get(12..16) == Some(b"CgBI");
+        let has_profile = data.windows(4).any(|w| w == b"iCCP");
+        if crushed || has_profile {
+            continue;
+        }
+        let Ok(image) = image::load_from_memory_with_format(&data, image::ImageFormat::Png) else {
+            continue;
+        };
+
+        let mut smaller = Vec::new();
+        let encoder =
+            PngEncoder::new_with_quality(&mut smaller, CompressionType::Best, FilterType::Adaptive);
+        encoder.write_image(image.as_bytes(), image.width(), image.height(), image.color())?;
+        if smaller.len() < data.len() {
+            fs::write(path, smaller)?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove frameworks an extension bundles that are byte-for-byte the app's own,
+/// and point the extension at the app's `Frameworks/` instead
+fn dedupe_frameworks(app: &AppBundle, events: &Events) -> Result<()> {
+    let contents = app.layout.contents_dir(&app.path);
+    let app_frameworks = contents.join("Frameworks");
+    let pattern = format!("{}/*/*.appex", contents.display());
+    let appexes = glob::glob(&pattern).map_err(|e| RuzuleError::InvalidInput(e.to_string()))?;
+
+    for appex in appexes.flatten() {
+        let frameworks = Layout::detect(&appex).contents_dir(&appex).join("Frameworks");
+        let Ok(entries) = fs::read_dir(&frameworks) else {
+            continue;
+        };
+        let mut deduped = false;
+        for entry in entries {
+            let framework = entry?.path();
+            let original = app_frameworks.join(framework.file_name().unwrap_or_default());
+            if original.is_dir() && tree_hash(&framework)? == tree_hash(&original)? {
+                fs::remove_dir_all(&framework)?;
+                deduped = true;
+            }
+        }
+        let Some(executable) = platform::bundle_executable(&appex).filter(|_| deduped) else {
+            continue;
+        };
+        // From PlugIns/X.appex/ (or Contents/PlugIns/X.appex/Contents/MacOS/) to Frameworks/
+        let rpath = match app.layout {
+            Layout::Shallow => "@executable_path/../../Frameworks",
+            Layout::Deep => "@executable_path/../../../../Frameworks",
+        };
+        Executable::new(&executable)?.with_events(events.clone()).add_rpath(rpath)?;
+    }
+    Ok(())
+}
+
+/// Hash of every file under `dir` but its signature, by relative path
+fn tree_hash(dir: &Path) -> Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        if rel.starts_with("_CodeSignature") || !entry.file_type().is_file() {
+            continue;
+        }
+        hasher.update(rel.to_string_lossy().as_bytes());
+        hasher.update(fs::read(entry.path())?);
+    }
+    Ok(hasher.finalize().to_vec())
+}
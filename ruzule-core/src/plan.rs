@@ -0,0 +1,813 @@
+//! Planned changes of an injection run.
+//!
+//! [`Injector::plan`](crate::Injector::plan) unpacks the input and resolves
+//! .cyan files without touching the bundle, returning a [`Plan`] that lists
+//! what [`Plan::apply`] will do.
+
+use crate::app_bundle::AppBundle;
+use crate::buffer::Arch;
+use crate::cancel::CancellationToken;
+use crate::entitlements::EntitlementOps;
+use crate::error::{Result, RuzuleError};
+use crate::events::{Event, Events, PlistChange};
+use crate::export::{self, ExportFormat};
+use crate::hooks::{HookStage, Hooks};
+use crate::injector::{
+    has_extension, is_stdout, InjectOptions, InjectReport, MinimumCheck, PhaseTiming,
+};
+use crate::ipa::{create_ipa, install_app, stream_ipa};
+use crate::journal::{Journal, Phase};
+use crate::lock::Lock;
+use crate::macho;
+use crate::platform::Platform;
+use crate::plist_ext::PlistMerge;
+use crate::temp::TempDir;
+use crate::validate::check_bundles;
+use crate::version::Version;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use walkdir::WalkDir;
+
+/// One change a [`Plan`] makes, listed in the order they're applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Operation {
+    /// Delete every app extension, or only those with an encrypted executable
+    RemoveExtensions { encrypted_only: bool },
+    /// Unlink a previously injected tweak and delete its file
+    Uninject(String),
+    RemoveRpath(String),
+    /// Unpack a .deb and inject what it contains
+    ExtractDeb(String),
+    /// Copy an injected file to `dest`, relative to the app
+    AddFile { name: String, dest: String },
+    AddRpath { binary: String, path: String },
+    /// Weakly link `dylib` from `binary`
+    AddLoadCommand { binary: String, dylib: String },
+    Plist(PlistChange),
+    MergePlist { path: PathBuf, mode: PlistMerge },
+    ChangeIcon(PathBuf),
+    /// Add an alternate icon the app can switch to, by name
+    AddAlternateIcon { name: String, path: PathBuf },
+    MergeEntitlements(PathBuf),
+    /// Apply a .cyan's entitlement edits to a binary, relative to the app
+    EditEntitlements(String),
+    /// Remove the main binary's entitlements that ad-hoc signing can't honor
+    StripEntitlements(Vec<String>),
+    RemoveWatchApps,
+    /// Reduce watch apps to their Info.plist and executable
+    StubWatchApps,
+    /// Delete a framework by name and weak-link it everywhere
+    RemoveFramework(String),
+    /// Link zxPluginsInject (or the given dylib) from every executable
+    PatchPlugins(Option<PathBuf>),
+    /// Binaries to fakesign, relative to the app
+    Fakesign(Vec<String>),
+    /// Binaries to thin (relative to the app) and the architecture they keep
+    Thin { binaries: Vec<String>, arch: Arch },
+    /// Sign everything with a certificate, embedding the provisioning profile if any
+    Sign {
+        certificate: PathBuf,
+        profile: Option<PathBuf>,
+    },
+    /// Run the hooks registered for a stage
+    RunHooks(HookStage),
+    Write(PathBuf),
+    /// Move the written .ipa into this directory with metadata for on-device signers
+    ExportEsign(PathBuf),
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::RemoveExtensions { encrypted_only: false } => {
+                f.write_str("remove app extensions")
+            }
+            Operation::RemoveExtensions { encrypted_only: true } => {
+                f.write_str("remove encrypted app extensions")
+            }
+            Operation::Uninject(name) => write!(f, "remove injected {}", name),
+            Operation::RemoveRpath(rpath) => write!(f, "remove rpath {}", rpath),
+            Operation::ExtractDeb(name) => write!(f, "extract {}", name),
+            Operation::AddFile { name, dest } => write!(f, "copy {} to {}", name, dest),
+            Operation::AddRpath { binary, path } => write!(f, "add rpath {} to {}", path, binary),
+            Operation::AddLoadCommand { binary, dylib } => {
+                write!(f, "link {} from {}", dylib, binary)
+            }
+            Operation::Plist(change) => match change {
+                PlistChange::Name(name) => write!(f, "set name to \"{}\"", name),
+                PlistChange::Version(version) => write!(f, "set version to \"{}\"", version),
+                PlistChange::BundleId(id) => write!(f, "set bundle id to \"{}\"", id),
+                PlistChange::MinimumVersion(v) => write!(f, "set minimum version to \"{}\"", v),
+                PlistChange::RemovedSupportedDevices => f.write_str("remove UISupportedDevices"),
+                PlistChange::EnabledDocuments => f.write_str("enable documents support"),
+                PlistChange::Format(format) => write!(f, "write Info.plist as {}", format),
+                PlistChange::RemovedKeys(keys) => {
+                    write!(f, "remove {} from Info.plist", keys.join(", "))
+                }
+                PlistChange::UrlSchemesAdded(schemes) => {
+                    write!(f, "add URL schemes {}", schemes.join(", "))
+                }
+                PlistChange::UrlSchemesRemoved(schemes) => {
+                    write!(f, "remove URL schemes {}", schemes.join(", "))
+                }
+                PlistChange::Localizations(locales) => {
+                    write!(f, "register localizations {}", locales.join(", "))
+                }
+                other => other.fmt(f),
+            },
+            Operation::MergePlist { path, mode: PlistMerge::Replace } => {
+                write!(f, "merge {} into Info.plist", path.display())
+            }
+            Operation::MergePlist { path, mode } => {
+                write!(f, "merge {} into Info.plist ({})", path.display(), mode)
+            }
+            Operation::ChangeIcon(path) => write!(f, "set app icon from {}", path.display()),
+            Operation::AddAlternateIcon { name, path } => {
+                write!(f, "add alternate icon {} from {}", name, path.display())
+            }
+            Operation::MergeEntitlements(path) => {
+                write!(f, "merge entitlements from {}", path.display())
+            }
+            Operation::EditEntitlements(binary) => write!(f, "edit entitlements of {}", binary),
+            Operation::StripEntitlements(keys) => {
+                write!(f, "remove entitlements {}", keys.join(", "))
+            }
+            Operation::RemoveWatchApps => f.write_str("remove watch app"),
+            Operation::StubWatchApps => f.write_str("reduce watch app to a placeholder"),
+            Operation::RemoveFramework(name) => write!(f, "remove {}.framework", name),
+            Operation::PatchPlugins(None) => f.write_str("patch executables for plugin support"),
+            Operation::PatchPlugins(Some(dylib)) => {
+                write!(f, "link {} from every executable", dylib.display())
+            }
+            Operation::Fakesign(binaries) => write!(f, "fakesign {} binaries", binaries.len()),
+            Operation::Thin { binaries, arch } => {
+                write!(f, "thin {} binaries to {}", binaries.len(), arch)
+            }
+            Operation::Sign { certificate, profile: None } => {
+                write!(f, "sign with {}", certificate.display())
+            }
+            Operation::Sign { certificate, profile: Some(profile) } => write!(
+                f,
+                "sign with {}, embedding {}",
+                certificate.display(),
+                profile.display()
+            ),
+            Operation::RunHooks(stage) => write!(f, "run {} hooks", stage),
+            Operation::Write(output) if is_stdout(output) => f.write_str("write to stdout"),
+            Operation::Write(output) => write!(f, "write {}", output.display()),
+            Operation::ExportEsign(dir) => {
+                write!(f, "export for on-device signers to {}", dir.display())
+            }
+        }
+    }
+}
+
+/// A resolved injection run, ready to [`apply`](Plan::apply).
+///
+/// The input has been unpacked to a temporary directory that's removed when
+/// the plan is dropped; nothing is written elsewhere until it's applied.
+pub struct Plan {
+    pub(crate) operations: Vec<Operation>,
+    /// The caller's options with every .cyan merged in
+    pub(crate) options: InjectOptions,
+    pub(crate) binary_entitlements: BTreeMap<String, EntitlementOps>,
+    pub(crate) cyan_sources: BTreeMap<String, String>,
+    pub(crate) input_size: u64,
+    pub(crate) output: PathBuf,
+    /// The extracted .app that gets written out; contains `app` when modifying a watch app
+    pub(crate) root: PathBuf,
+    pub(crate) app: AppBundle,
+    pub(crate) tmpdir: TempDir,
+    /// Set for resumable runs, see [`InjectOptions::resume`]
+    pub(crate) journal: Option<Journal>,
+    /// On the output and the cache, held until the plan is applied or dropped
+    pub(crate) _locks: Vec<Lock>,
+    pub(crate) events: Events,
+    pub(crate) cancel: CancellationToken,
+    pub(crate) hooks: Hooks,
+    pub(crate) stopwatch: Stopwatch,
+}
+
+impl Plan {
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Options after merging .cyan files
+    pub fn options(&self) -> &InjectOptions {
+        &self.options
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// List what applying will do. Files pulled in while applying (.deb
+    /// contents, bundled dependencies) aren't known yet and aren't listed.
+    pub(crate) fn describe(&self) -> Vec<Operation> {
+        let opts = &self.options;
+        let app = &self.app;
+        let main = app.executable.inner.name.clone();
+        let mut ops = Vec::new();
+        let hooks = |ops: &mut Vec<Operation>, stage| {
+            if self.hooks.count(stage) > 0 {
+                ops.push(Operation::RunHooks(stage));
+            }
+        };
+
+        hooks(&mut ops, HookStage::PostExtract);
+        if opts.remove_extensions {
+            ops.push(Operation::RemoveExtensions { encrypted_only: false });
+        } else if opts.remove_encrypted {
+            ops.push(Operation::RemoveExtensions { encrypted_only: true });
+        }
+        for name in &opts.uninject {
+            ops.push(Operation::Uninject(name.clone()));
+        }
+        for rpath in &opts.remove_rpaths {
+            ops.push(Operation::RemoveRpath(rpath.clone()));
+        }
+
+        // Same layout as AppBundle::inject
+        let tweaks: BTreeMap<String, &PathBuf> = opts
+            .files
+            .iter()
+            .filter(|f| !f.is_symlink())
+            .filter_map(|f| Some((f.file_name()?.to_string_lossy().to_string(), f)))
+            .collect();
+        // Directories relative to the app, with a trailing slash unless they're the app itself
+        let layout = app.layout;
+        let dir = |p: PathBuf| match p.to_string_lossy() {
+            p if p.is_empty() => String::new(),
+            p => format!("{}/", p.replace('\\', "/")),
+        };
+        let plugins = dir(layout.rel("PlugIns"));
+        let extensions = dir(layout.rel("Extensions"));
+        let resources = dir(layout.resources_dir(Path::new("")));
+        let frameworks = if opts.use_frameworks_dir {
+            dir(layout.rel("Frameworks"))
+        } else {
+            dir(layout.executable_dir(Path::new("")))
+        };
+        let loader = if opts.use_frameworks_dir { "@rpath" } else { "@executable_path" };
+        let mut added = BTreeSet::new();
+
+        let injectable = tweaks.keys().any(|bn| {
+            bn.ends_with(".deb") || bn.ends_with(".dylib") || bn.ends_with(".framework")
+        });
+        if injectable && opts.use_frameworks_dir {
+            ops.push(Operation::AddRpath {
+                binary: main.clone(),
+                path: layout.frameworks_rpath().to_string(),
+            });
+        }
+
+        for bn in tweaks.keys() {
+            let (dest, dylib) = if bn.ends_with(".deb") {
+                ops.push(Operation::ExtractDeb(bn.clone()));
+                continue;
+            } else if bn.ends_with(".appex") {
+                (format!("{}{}", plugins, bn), None)
+            } else if bn.ends_with(".dylib") {
+                (format!("{}{}", frameworks, bn), Some(format!("{}/{}", loader, bn)))
+            } else if let Some(framework_name) = bn.strip_suffix(".framework") {
+                let dylib = format!("{}/{}/{}", loader, bn, framework_name);
+                (format!("{}{}", frameworks, bn), Some(dylib))
+            } else {
+                (format!("{}{}", resources, bn), None)
+            };
+
+            if dylib.is_some() || bn.ends_with(".appex") {
+                added.insert(dest.clone());
+            }
+            ops.push(Operation::AddFile {
+                name: bn.clone(),
+                dest,
+            });
+            if let Some(dylib) = dylib {
+                ops.push(Operation::AddLoadCommand {
+                    binary: opts.inject_into.clone().unwrap_or_else(|| main.clone()),
+                    dylib,
+                });
+            }
+        }
+
+        let locales: Vec<String> = tweaks
+            .keys()
+            .filter_map(|bn| bn.strip_suffix(".lproj"))
+            .filter(|locale| *locale != "Base")
+            .map(str::to_string)
+            .collect();
+        if !locales.is_empty() {
+            ops.push(Operation::Plist(PlistChange::Localizations(locales)));
+        }
+
+        hooks(&mut ops, HookStage::PostInject);
+        if let Some(ref n) = opts.name {
+            ops.push(Operation::Plist(PlistChange::Name(n.clone())));
+        }
+        if let Some(ref v) = opts.version {
+            ops.push(Operation::Plist(PlistChange::Version(v.clone())));
+        }
+        if let Some(ref b) = opts.bundle_id {
+            ops.push(Operation::Plist(PlistChange::BundleId(b.clone())));
+        }
+        if let Some(ref m) = opts.minimum {
+            ops.push(Operation::Plist(PlistChange::MinimumVersion(m.clone())));
+        }
+        if let Some(ref i) = opts.icon {
+            ops.push(Operation::ChangeIcon(i.clone()));
+        }
+        for (name, path) in &opts.alternate_icons {
+            ops.push(Operation::AddAlternateIcon {
+                name: name.clone(),
+                path: path.clone(),
+            });
+        }
+        if let Some(ref p) = opts.plist {
+            ops.push(Operation::MergePlist {
+                path: p.clone(),
+                mode: opts.plist_merge.unwrap_or_default(),
+            });
+        }
+        if !opts.remove_plist_keys.is_empty() {
+            let keys = opts.remove_plist_keys.clone();
+            ops.push(Operation::Plist(PlistChange::RemovedKeys(keys)));
+        }
+        if let Some(ref e) = opts.entitlements {
+            ops.push(Operation::MergeEntitlements(e.clone()));
+        }
+        for binary in self.binary_entitlements.keys() {
+            ops.push(Operation::EditEntitlements(binary.clone()));
+        }
+        if opts.strip_risky_entitlements {
+            // Only what's there now; -x and .cyan edits may add more while applying
+            let keys = app.risky_entitlements().unwrap_or_default();
+            if !keys.is_empty() {
+                ops.push(Operation::StripEntitlements(keys));
+            }
+        }
+        if !opts.remove_entitlements.is_empty() {
+            ops.push(Operation::StripEntitlements(opts.remove_entitlements.clone()));
+        }
+        if opts.remove_supported_devices {
+            ops.push(Operation::Plist(PlistChange::RemovedSupportedDevices));
+        }
+        if !opts.remove_url_schemes.is_empty() {
+            let schemes = opts.remove_url_schemes.clone();
+            ops.push(Operation::Plist(PlistChange::UrlSchemesRemoved(schemes)));
+        }
+        if !opts.add_url_schemes.is_empty() {
+            let schemes = opts.add_url_schemes.clone();
+            ops.push(Operation::Plist(PlistChange::UrlSchemesAdded(schemes)));
+        }
+        if opts.no_watch {
+            ops.push(Operation::RemoveWatchApps);
+        } else if opts.stub_watch {
+            ops.push(Operation::StubWatchApps);
+        }
+        for name in &opts.remove_frameworks {
+            ops.push(Operation::RemoveFramework(name.clone()));
+        }
+        if opts.enable_documents {
+            ops.push(Operation::Plist(PlistChange::EnabledDocuments));
+        }
+        if opts.patch_plugins || opts.patch_plugins_dylib.is_some() {
+            ops.push(Operation::PatchPlugins(opts.patch_plugins_dylib.clone()));
+        }
+        if let Some(format) = opts.plist_format.filter(|&f| f != app.plist.format) {
+            ops.push(Operation::Plist(PlistChange::Format(format)));
+        }
+
+        hooks(&mut ops, HookStage::PreSign);
+        if opts.fakesign || opts.thin {
+            let existing = app
+                .get_executables()
+                .into_iter()
+                .filter_map(|p| Some(p.strip_prefix(&app.path).ok()?.to_string_lossy().to_string()))
+                .filter(|p| {
+                    !opts.remove_extensions
+                        || !(p.starts_with(&plugins) || p.starts_with(&extensions))
+                })
+                .filter(|p| {
+                    let removed = |name: &String| {
+                        let name = name.strip_suffix(".framework").unwrap_or(name);
+                        p.ends_with(&format!("/{}.framework", name))
+                    };
+                    !opts.remove_frameworks.iter().any(removed)
+                });
+            added.extend(existing);
+
+            let binaries: Vec<String> = std::iter::once(main).chain(added).collect();
+            if opts.fakesign {
+                ops.push(Operation::Fakesign(binaries.clone()));
+            }
+            if opts.thin {
+                ops.push(Operation::Thin {
+                    binaries,
+                    arch: opts.arch.unwrap_or_default(),
+                });
+            }
+        }
+        if let Some(ref certificate) = opts.certificate {
+            ops.push(Operation::Sign {
+                certificate: certificate.clone(),
+                profile: opts.profile.clone(),
+            });
+        }
+
+        hooks(&mut ops, HookStage::PrePack);
+        ops.push(Operation::Write(self.output.clone()));
+        if opts.export == ExportFormat::Esign {
+            ops.push(Operation::ExportEsign(export::esign_dir(&self.output)));
+        }
+        ops
+    }
+
+    /// Make the planned changes and write the output. If that fails, the working
+    /// directory is kept (see [`InjectOptions::keep_temp`]). Resumable runs skip
+    /// the phases an earlier run finished.
+    pub fn apply(self) -> Result<InjectReport> {
+        let Plan {
+            options,
+            binary_entitlements,
+            cyan_sources,
+            input_size,
+            output,
+            root,
+            mut app,
+            mut tmpdir,
+            mut journal,
+            events,
+            cancel,
+            hooks,
+            mut stopwatch,
+            ..
+        } = self;
+        let resumed = journal.as_ref().and_then(Journal::phase);
+        // A failed run leaves the partly modified app behind for bug reports
+        let result = (|| -> Result<InjectReport> {
+            let tmpdir_path = tmpdir.path();
+            let mut record = |phase| match journal {
+                Some(ref mut journal) => journal.record(phase, &root),
+                None => Ok(()),
+            };
+            let injected: Vec<String> = options
+                .files
+                .iter()
+                .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            // Don't count time spent between planning and applying
+            stopwatch.restart();
+            let run_hooks = |app: &mut AppBundle, stage| -> Result<()> {
+                if hooks.count(stage) == 0 {
+                    return Ok(());
+                }
+                cancel.check()?;
+                hooks.run(stage, &app.path)?;
+                events.emit(Event::HookRan(stage));
+                app.reload()
+            };
+
+            if resumed.is_none_or(|phase| phase < Phase::Injected) {
+                record(Phase::Injecting)?;
+                run_hooks(&mut app, HookStage::PostExtract)?;
+
+                // Process extensions removal (before injection)
+                if options.remove_extensions {
+                    app.remove_all_extensions();
+                } else if options.remove_encrypted {
+                    app.remove_encrypted_extensions()?;
+                }
+                if !options.uninject.is_empty() {
+                    app.uninject(&options.uninject)?;
+                }
+                if !options.remove_rpaths.is_empty() {
+                    app.remove_rpaths(&options.remove_rpaths)?;
+                }
+
+                // Inject files
+                cancel.check()?;
+                if !options.files.is_empty() {
+                    let mut tweaks: HashMap<String, PathBuf> =
+                        injected.iter().cloned().zip(options.files.iter().cloned()).collect();
+                    let min_os = app.inject(
+                        &mut tweaks,
+                        tmpdir_path,
+                        options.use_frameworks_dir,
+                        options.substrate_backend,
+                        options.swift_libs.as_deref(),
+                        options.inject_into.as_deref(),
+                    )?;
+                    check_minimum_os(&mut app, &min_os, &options, &events)?;
+
+                    // Base.lproj holds storyboards, not a language
+                    let locales: Vec<String> = injected
+                        .iter()
+                        .filter_map(|bn| bn.strip_suffix(".lproj"))
+                        .filter(|locale| *locale != "Base")
+                        .map(str::to_string)
+                        .collect();
+                    if !locales.is_empty() {
+                        app.plist.add_localizations(&locales);
+                    }
+                }
+
+                stopwatch.lap("inject");
+                run_hooks(&mut app, HookStage::PostInject)?;
+
+                // Apply modifications
+                cancel.check()?;
+                if let Some(ref n) = options.name {
+                    app.plist.change_name(n);
+                }
+                if let Some(ref v) = options.version {
+                    app.plist.change_version(v);
+                }
+                if let Some(ref b) = options.bundle_id {
+                    app.plist.change_bundle_id(b);
+                }
+                if let Some(ref m) = options.minimum {
+                    app.plist.change_minimum_version(m.parse()?);
+                }
+                if let Some(ref i) = options.icon {
+                    app.change_icon(i, tmpdir_path)?;
+                }
+                for (name, path) in &options.alternate_icons {
+                    app.add_alternate_icon(name, path, tmpdir_path)?;
+                }
+                if let Some(ref p) = options.plist {
+                    app.plist.merge_plist_with(p, options.plist_merge.unwrap_or_default())?;
+                }
+                if !options.remove_plist_keys.is_empty() {
+                    app.plist.remove_keys(&options.remove_plist_keys);
+                }
+                if let Some(ref e) = options.entitlements {
+                    app.executable.merge_entitlements(e)?;
+                }
+                for (binary, ops) in &binary_entitlements {
+                    app.edit_entitlements(binary, ops)?;
+                }
+                if options.strip_risky_entitlements {
+                    app.strip_risky_entitlements()?;
+                }
+                if !options.remove_entitlements.is_empty() {
+                    app.remove_entitlements(&options.remove_entitlements)?;
+                }
+
+                if options.remove_supported_devices {
+                    app.plist.remove_uisd();
+                }
+                if !options.remove_url_schemes.is_empty() {
+                    app.plist.remove_url_schemes(&options.remove_url_schemes);
+                }
+                if !options.add_url_schemes.is_empty() {
+                    app.plist.add_url_schemes(&options.add_url_schemes);
+                }
+                if options.no_watch {
+                    app.remove_watch_apps();
+                } else if options.stub_watch {
+                    app.stub_watch_apps()?;
+                }
+                if !options.remove_frameworks.is_empty() {
+                    app.remove_frameworks(&options.remove_frameworks)?;
+                }
+                if options.enable_documents {
+                    if app.platform.is_ios() {
+                        app.plist.enable_documents();
+                    } else {
+                        let platform = app.platform;
+                        events.warn(format!(
+                            "documents support is iOS-only, not enabled on {}",
+                            platform
+                        ));
+                    }
+                }
+                if options.patch_plugins || options.patch_plugins_dylib.is_some() {
+                    app.patch_plugins(options.patch_plugins_dylib.as_deref())?;
+                }
+                if let Some(format) = options.plist_format {
+                    app.plist.convert(format)?;
+                }
+                stopwatch.lap("modify");
+                run_hooks(&mut app, HookStage::PreSign)?;
+                record(Phase::Injected)?;
+            }
+
+            if resumed.is_none_or(|phase| phase < Phase::Signed) {
+                cancel.check()?;
+                if options.fakesign {
+                    app.fakesign_all()?;
+                    stopwatch.lap("sign");
+                }
+                if options.thin {
+                    if app.platform == Platform::Watchos {
+                        events.warn("watchOS binaries are arm64_32, not thinned");
+                    } else {
+                        app.thin_all_to(options.arch.unwrap_or_default())?;
+                    }
+                    stopwatch.lap("thin");
+                }
+                if let Some(certificate) = options.open_certificate()? {
+                    app.sign(&certificate, options.profile.as_deref())?;
+                    stopwatch.lap("sign");
+                }
+                run_hooks(&mut app, HookStage::PrePack)?;
+                record(Phase::Signed)?;
+            }
+
+            // Surfaces what installd would only reject with an error number
+            for issue in check_bundles(&app)? {
+                events.warn(issue.to_string());
+            }
+
+            let name = app
+                .plist
+                .get_string("CFBundleDisplayName")
+                .or_else(|| app.plist.get_string("CFBundleName"))
+                .map(str::to_string);
+            let bundle_id = app.plist.get_string("CFBundleIdentifier").map(str::to_string);
+            let version = app.plist.get_string("CFBundleShortVersionString").map(str::to_string);
+
+            cancel.check()?;
+
+            // Create output directories if needed
+            if let Some(parent) = output.parent() {
+                if !parent.as_os_str().is_empty() && !parent.exists() {
+                    fs::create_dir_all(parent)?;
+                }
+            }
+
+            // Generate output
+            events.emit(Event::Packing(output.clone()));
+            let output_size = if is_stdout(&output) {
+                let stdout = std::io::stdout().lock();
+                stream_ipa(tmpdir_path, stdout, options.compress, options.jobs, &cancel, &events)?
+            } else if has_extension(&output, &["ipa", "tipa"]) {
+                create_ipa(tmpdir_path, &output, options.compress, options.jobs, &cancel, &events)?;
+                disk_size(&output)
+            } else {
+                install_app(&root, &output)?;
+                disk_size(&output)
+            };
+            let output = if options.export == ExportFormat::Esign {
+                export::esign(
+                    &app,
+                    &output,
+                    options.export_certificate.as_deref(),
+                    options.export_profile.as_deref(),
+                )?
+            } else {
+                output
+            };
+            events.emit(Event::Done(output.clone()));
+            stopwatch.lap("pack");
+
+            let mut report = InjectReport {
+                name,
+                bundle_id,
+                version,
+                input_size,
+                output_size,
+                output,
+                injected,
+                cyan_sources,
+                options: options.clone(),
+                signed: 0,
+                thinned: 0,
+                plist_changes: Vec::new(),
+                phases: stopwatch.phases,
+                warnings: events.warnings(),
+            };
+            for event in events.recorded() {
+                match event {
+                    Event::Signed(n) => report.signed += n,
+                    Event::Thinned(n) => report.thinned += n,
+                    Event::PlistChanged(change) => report.plist_changes.push(change),
+                    _ => {}
+                }
+            }
+            Ok(report)
+        })();
+
+        match result {
+            Ok(_) if journal.is_some() => tmpdir.keep(options.keep_temp),
+            Err(_) if journal.is_some() => events.emit(Event::Notice(
+                "run the same command again to resume where it stopped".to_string(),
+            )),
+            Err(ref e) if !options.keep_temp && !matches!(e, RuzuleError::Cancelled) => {
+                tmpdir.keep(true);
+                events.emit(Event::TempKept(tmpdir.path().to_path_buf()));
+            }
+            _ => {}
+        }
+        result
+    }
+}
+
+/// Compare the OS versions injected binaries require with the app's minimum
+/// (or the one it's about to get), handled as `options.minimum_check` says,
+/// or raise it to the highest of them with `options.auto_minimum`
+fn check_minimum_os(
+    app: &mut AppBundle,
+    required: &[(String, Version)],
+    options: &InjectOptions,
+    events: &Events,
+) -> Result<()> {
+    let explicit = options.minimum.as_deref().map(str::parse::<Version>).transpose()?;
+    let Some(minimum) = explicit.or_else(|| app.plist.minimum_version()) else {
+        return Ok(());
+    };
+    let newer: Vec<&(String, Version)> =
+        required.iter().filter(|(_, version)| *version > minimum).collect();
+    let Some(&highest) = newer.iter().map(|(_, v)| v).max() else {
+        return Ok(());
+    };
+
+    if options.auto_minimum {
+        app.plist.change_minimum_version(highest);
+        // Keep the main binary's own floor in line, the loader and App Store tools read it too
+        let main = &app.executable;
+        if macho::min_os_version(&main.inner.path)? < Some(highest) {
+            main.set_min_os_version(highest)?;
+        }
+        return Ok(());
+    }
+
+    match options.minimum_check {
+        MinimumCheck::Strict => {
+            let (name, required) = newer[0].clone();
+            return Err(RuzuleError::NewerOsRequired {
+                name,
+                required,
+                minimum,
+            });
+        }
+        MinimumCheck::Bump if options.minimum.is_none() => {
+            app.plist.change_minimum_version(highest);
+        }
+        _ => {
+            for (name, version) in newer {
+                events.warn(format!(
+                    "{} requires {} {}, but the app's minimum is {}; it may crash on older devices",
+                    name, app.platform, version, minimum
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Times consecutive stages of a run
+#[derive(Debug)]
+pub(crate) struct Stopwatch {
+    phases: Vec<PhaseTiming>,
+    last: Instant,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self {
+            phases: Vec::new(),
+            last: Instant::now(),
+        }
+    }
+
+    pub(crate) fn restart(&mut self) {
+        self.last = Instant::now();
+    }
+
+    /// Record the time since the previous lap as `name`
+    pub(crate) fn lap(&mut self, name: &'static str) {
+        let now = Instant::now();
+        self.phases.push(PhaseTiming {
+            name,
+            elapsed: now - self.last,
+        });
+        self.last = now;
+    }
+}
+
+/// Size of a file, or of everything in a directory
+pub(crate) fn disk_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+impl fmt::Debug for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Plan")
+            .field("operations", &self.operations)
+            .field("output", &self.output)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,214 @@
+//! Which Apple platform a bundle was built for, and how its files are laid out.
+//!
+//! Most of the pipeline assumes iOS; other platforms skip or refuse the steps
+//! that don't apply to them instead of producing a bundle that won't run.
+
+use crate::plist_ext::PlistFile;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    #[default]
+    Ios,
+    Tvos,
+    Macos,
+    /// An iPad app built for macOS
+    MacCatalyst,
+    Visionos,
+    Watchos,
+}
+
+impl Platform {
+    /// From `CFBundleSupportedPlatforms`, then `DTPlatformName`, then `UIDeviceFamily`.
+    /// Bundles that say nothing are taken to be iOS.
+    pub fn detect(plist: &PlistFile) -> Self {
+        let supported = plist
+            .get("CFBundleSupportedPlatforms")
+            .and_then(|v| v.as_array())
+            .and_then(|a| a.first())
+            .and_then(|v| v.as_string());
+        let families = plist.get("UIDeviceFamily").and_then(|v| v.as_array());
+        let family = |n| {
+            families
+                .into_iter()
+                .flatten()
+                .any(|v| v.as_unsigned_integer() == Some(n))
+        };
+
+        if let Some(platform) = supported.or(plist.get_string("DTPlatformName")) {
+            return match Self::from_platform_name(platform) {
+                // Catalyst apps keep UIKit's device families (with 6 for Mac)
+                Platform::Macos if families.is_some() => Platform::MacCatalyst,
+                platform => platform,
+            };
+        }
+
+        if family(3) {
+            Platform::Tvos
+        } else if family(4) {
+            Platform::Watchos
+        } else if family(7) {
+            Platform::Visionos
+        } else {
+            Platform::Ios
+        }
+    }
+
+    /// `AppleTVOS`, `appletvsimulator`, `MacOSX`, `XROS`, `WatchOS`, `iPhoneOS`, ...
+    fn from_platform_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        if name.starts_with("appletv") {
+            Platform::Tvos
+        } else if name.starts_with("macos") {
+            Platform::Macos
+        } else if name.starts_with("xr") {
+            Platform::Visionos
+        } else if name.starts_with("watch") {
+            Platform::Watchos
+        } else {
+            Platform::Ios
+        }
+    }
+
+    /// From the `platform` of a Mach-O `LC_BUILD_VERSION`; simulators count as their device.
+    /// `None` for platforms ruzule doesn't handle (bridgeOS, DriverKit, ...).
+    pub fn from_build_platform(platform: u32) -> Option<Self> {
+        match platform {
+            1 => Some(Platform::Macos),
+            2 | 7 => Some(Platform::Ios),
+            3 | 8 => Some(Platform::Tvos),
+            4 | 9 => Some(Platform::Watchos),
+            6 => Some(Platform::MacCatalyst),
+            11 | 12 => Some(Platform::Visionos),
+            _ => None,
+        }
+    }
+
+    /// The Info.plist key holding the lowest OS version the app runs on
+    pub fn minimum_version_key(self) -> &'static str {
+        match self {
+            Platform::Macos | Platform::MacCatalyst => "LSMinimumSystemVersion",
+            _ => "MinimumOSVersion",
+        }
+    }
+
+    pub fn is_ios(self) -> bool {
+        self == Platform::Ios
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Platform::Ios => "iOS",
+            Platform::Tvos => "tvOS",
+            Platform::Macos => "macOS",
+            Platform::MacCatalyst => "Mac Catalyst",
+            Platform::Visionos => "visionOS",
+            Platform::Watchos => "watchOS",
+        })
+    }
+}
+
+/// Where a bundle keeps its files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// Everything at the top level, as on iOS
+    #[default]
+    Shallow,
+    /// `Contents/Info.plist`, `Contents/MacOS/`, `Contents/Frameworks/`, ... as on macOS
+    Deep,
+}
+
+impl Layout {
+    pub fn detect(bundle: &Path) -> Self {
+        if bundle.join("Contents/Info.plist").is_file() {
+            Layout::Deep
+        } else {
+            Layout::Shallow
+        }
+    }
+
+    /// The directory `Info.plist`, `Frameworks/` and `PlugIns/` are in
+    pub fn contents_dir(self, bundle: &Path) -> PathBuf {
+        match self {
+            Layout::Shallow => bundle.to_path_buf(),
+            Layout::Deep => bundle.join("Contents"),
+        }
+    }
+
+    /// `name` (e.g. `Frameworks`, `PlugIns`) relative to the bundle
+    pub fn rel(self, name: &str) -> PathBuf {
+        match self {
+            Layout::Shallow => PathBuf::from(name),
+            Layout::Deep => Path::new("Contents").join(name),
+        }
+    }
+
+    pub fn info_plist(self, bundle: &Path) -> PathBuf {
+        bundle.join(self.rel("Info.plist"))
+    }
+
+    /// Where the main executable lives, which `@executable_path` refers to
+    pub fn executable_dir(self, bundle: &Path) -> PathBuf {
+        match self {
+            Layout::Shallow => bundle.to_path_buf(),
+            Layout::Deep => bundle.join("Contents/MacOS"),
+        }
+    }
+
+    /// Where loose files and resource bundles go
+    pub fn resources_dir(self, bundle: &Path) -> PathBuf {
+        match self {
+            Layout::Shallow => bundle.to_path_buf(),
+            Layout::Deep => bundle.join("Contents/Resources"),
+        }
+    }
+
+    /// The rpath that finds `Frameworks/` from the main executable
+    pub fn frameworks_rpath(self) -> &'static str {
+        match self {
+            Layout::Shallow => "@executable_path/Frameworks",
+            Layout::Deep => "@executable_path/../Frameworks",
+        }
+    }
+}
+
+/// The Info.plist of a nested .app, .appex or .framework and the directory its
+/// executable is in
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+pub(crate) fn bundle_paths(bundle: &Path) -> (PathBuf, PathBuf) {
+    let layout = Layout::detect(bundle);
+    // Versioned macOS frameworks keep Info.plist in Resources/ and link the binary at the top
+    if bundle.join("Resources/Info.plist").is_file() {
+        (bundle.join("Resources/Info.plist"), bundle.to_path_buf())
+    } else {
+        (layout.info_plist(bundle), layout.executable_dir(bundle))
+    }
+}
+
+/// The executable of a nested .app, .appex or .framework, if it has one
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+pub(crate) fn bundle_executable(bundle: &Path) -> Option<PathBuf> {
+    let (plist, exec_dir) = bundle_paths(bundle);
+    let name = PlistFile::open(plist).ok()?.get_string("CFBundleExecutable")?.to_string();
+    Some(exec_dir.join(name))
+}
+
+/// The CFBundleIdentifier of the bundle `executable` is the main binary of,
+/// if it's one's
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
+pub(crate) fn bundle_identifier(executable: &Path) -> Option<String> {
+    let is_bundle = |dir: &Path| {
+        dir.extension().is_some_and(|e| e == "app" || e == "appex" || e == "framework")
+    };
+    // At most Contents/MacOS/ between a bundle and its executable
+    let bundle = executable.ancestors().skip(1).take(3).find(|dir| is_bundle(dir))?;
+    let (plist, exec_dir) = bundle_paths(bundle);
+    let plist = PlistFile::open(plist).ok()?;
+    if exec_dir.join(plist.get_string("CFBundleExecutable")?) != executable {
+        return None;
+    }
+    plist.get_string("CFBundleIdentifier").map(str::to_string)
+}
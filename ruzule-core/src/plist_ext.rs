@@ -0,0 +1,573 @@
+use crate::buffer;
+use crate::error::Result;
+use crate::events::{Event, Events, PlistChange};
+use crate::platform::{Layout, Platform};
+use crate::version::Version;
+use plist::Value;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How [`PlistFile::merge_plist_with`] combines another plist with this one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlistMerge {
+    /// Top-level keys replace the app's whole value
+    #[default]
+    Replace,
+    /// Dictionaries are merged key by key at every level, anything else replaced
+    Deep,
+    /// Like `Deep`, and arrays get the items they don't have yet appended
+    Append,
+}
+
+impl PlistMerge {
+    pub const ALL: [PlistMerge; 3] = [PlistMerge::Replace, PlistMerge::Deep, PlistMerge::Append];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlistMerge::Replace => "replace",
+            PlistMerge::Deep => "deep",
+            PlistMerge::Append => "append",
+        }
+    }
+}
+
+impl fmt::Display for PlistMerge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PlistMerge {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!("unknown plist merge mode \"{}\" (expected replace, deep or append)", s)
+            })
+    }
+}
+
+/// Whether `scheme` can be a URL scheme: a letter, then letters, digits, '+', '-' or '.'
+pub fn is_valid_url_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// Remove `path` from `dict`, trying the rest of the path as one key at each
+/// level before descending at the next dot
+fn remove_key_path(dict: &mut plist::Dictionary, path: &str) -> bool {
+    if dict.remove(path).is_some() {
+        return true;
+    }
+    let mut dots = path.match_indices('.').map(|(i, _)| i);
+    dots.any(|i| {
+        let (key, rest) = (&path[..i], &path[i + 1..]);
+        match dict.get_mut(key).and_then(Value::as_dictionary_mut) {
+            Some(nested) => remove_key_path(nested, rest),
+            None => false,
+        }
+    })
+}
+
+fn merge_dictionary(base: &mut plist::Dictionary, other: plist::Dictionary, mode: PlistMerge) {
+    for (key, value) in other {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Dictionary(ours)), Value::Dictionary(theirs))
+                if mode != PlistMerge::Replace =>
+            {
+                merge_dictionary(ours, theirs, mode);
+            }
+            (Some(Value::Array(ours)), Value::Array(theirs)) if mode == PlistMerge::Append => {
+                for item in theirs {
+                    if !ours.contains(&item) {
+                        ours.push(item);
+                    }
+                }
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Encoding a plist is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlistFormat {
+    #[default]
+    Xml,
+    /// `bplist00`, what Xcode compiles Info.plist to
+    Binary,
+}
+
+impl PlistFormat {
+    pub const ALL: [PlistFormat; 2] = [PlistFormat::Xml, PlistFormat::Binary];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlistFormat::Xml => "xml",
+            PlistFormat::Binary => "binary",
+        }
+    }
+
+    /// The format of an encoded plist; anything that isn't binary is written back as XML
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(b"bplist") {
+            PlistFormat::Binary
+        } else {
+            PlistFormat::Xml
+        }
+    }
+}
+
+impl fmt::Display for PlistFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PlistFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown plist format \"{}\" (expected xml or binary)", s))
+    }
+}
+
+pub struct PlistFile {
+    pub path: PathBuf,
+    pub data: plist::Dictionary,
+    /// What [`save`](Self::save) writes, the format the file was read in
+    pub format: PlistFormat,
+    app_path: Option<PathBuf>,
+    events: Events,
+}
+
+impl PlistFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let raw = std::fs::read(&path)?;
+        Ok(Self {
+            path,
+            data: buffer::parse_plist(&raw)?,
+            format: PlistFormat::detect(&raw),
+            app_path: None,
+            events: Events::none(),
+        })
+    }
+
+    pub fn open_with_app_path<P: AsRef<Path>>(path: P, app_path: P) -> Result<Self> {
+        let mut plist = Self::open(path)?;
+        plist.app_path = Some(app_path.as_ref().to_path_buf());
+        Ok(plist)
+    }
+
+    /// Report changes to `events`
+    pub fn with_events(mut self, events: Events) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn try_open<P: AsRef<Path>>(path: P) -> Option<Self> {
+        Self::open(path).ok()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.data.get(key)
+    }
+
+    pub fn get_string(&self, key: &str) -> Option<&str> {
+        self.data.get(key).and_then(|v| v.as_string())
+    }
+
+    pub fn set(&mut self, key: &str, value: Value) {
+        self.data.insert(key.to_string(), value);
+    }
+
+    pub fn set_string(&mut self, key: &str, value: &str) {
+        self.data.insert(key.to_string(), Value::String(value.to_string()));
+    }
+
+    pub fn set_bool(&mut self, key: &str, value: bool) {
+        self.data.insert(key.to_string(), Value::Boolean(value));
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.data.remove(key).is_some()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.data.contains_key(key)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let data = match self.format {
+            PlistFormat::Xml => buffer::plist_to_xml(&self.data)?,
+            PlistFormat::Binary => buffer::plist_to_binary(&self.data)?,
+        };
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+
+    /// Rewrite the file in `format`, if it isn't in it already
+    pub fn convert(&mut self, format: PlistFormat) -> Result<bool> {
+        if self.format == format {
+            return Ok(false);
+        }
+
+        self.format = format;
+        self.save()?;
+        self.events.emit(Event::PlistChanged(PlistChange::Format(format)));
+        Ok(true)
+    }
+
+    /// Save, reporting a failure as a warning instead of an error
+    fn save_or_warn(&self) {
+        if let Err(e) = self.save() {
+            self.events.warn(format!("couldn't save {}: {}", self.path.display(), e));
+        }
+    }
+
+    pub fn remove_uisd(&mut self) -> bool {
+        let removed = self.remove("UISupportedDevices");
+        if removed {
+            self.save_or_warn();
+            self.events.emit(Event::PlistChanged(PlistChange::RemovedSupportedDevices));
+        }
+        removed
+    }
+
+    pub fn enable_documents(&mut self) -> bool {
+        let mut changed = false;
+
+        if self.get_string("UISupportsDocumentBrowser") != Some("true") {
+            self.set_bool("UISupportsDocumentBrowser", true);
+            changed = true;
+        }
+        if self.get_string("UIFileSharingEnabled") != Some("true") {
+            self.set_bool("UIFileSharingEnabled", true);
+            changed = true;
+        }
+
+        if changed {
+            self.save_or_warn();
+            self.events.emit(Event::PlistChanged(PlistChange::EnabledDocuments));
+        }
+        changed
+    }
+
+    pub fn change_name(&mut self, name: &str) -> bool {
+        let current_name = self.get_string("CFBundleName").map(|s| s.to_string());
+        let current_display = self.get_string("CFBundleDisplayName").map(|s| s.to_string());
+
+        if current_name.as_deref() == Some(name) && current_display.as_deref() == Some(name) {
+            return false;
+        }
+
+        self.set_string("CFBundleName", name);
+        self.set_string("CFBundleDisplayName", name);
+        self.save_or_warn();
+        self.events.emit(Event::PlistChanged(PlistChange::Name(name.to_string())));
+
+        // Update localized names
+        if let Some(ref app_path) = self.app_path {
+            let mut changed_count = 0;
+            let resources = Layout::detect(app_path).resources_dir(app_path);
+            if let Ok(entries) = glob::glob(&format!("{}/*.lproj", resources.display())) {
+                for entry in entries.flatten() {
+                    let strings_path = entry.join("InfoPlist.strings");
+                    if let Ok(mut pl) = PlistFile::open(&strings_path) {
+                        pl.set_string("CFBundleName", name);
+                        pl.set_string("CFBundleDisplayName", name);
+                        match pl.save() {
+                            Ok(()) => changed_count += 1,
+                            Err(e) => self.events.warn(format!(
+                                "couldn't save {}: {}",
+                                strings_path.display(),
+                                e
+                            )),
+                        }
+                    }
+                }
+            }
+            if changed_count > 0 {
+                let change = PlistChange::LocalizedNames(changed_count);
+                self.events.emit(Event::PlistChanged(change));
+            }
+        }
+        true
+    }
+
+    /// Add `locales` (e.g. `fr`, `pt-BR`) to `CFBundleLocalizations` unless already listed
+    pub fn add_localizations(&mut self, locales: &[String]) -> bool {
+        let mut list = match self.get("CFBundleLocalizations").and_then(Value::as_array) {
+            Some(list) => list.clone(),
+            None => Vec::new(),
+        };
+        let added: Vec<String> = locales
+            .iter()
+            .filter(|l| !list.iter().any(|v| v.as_string() == Some(l.as_str())))
+            .cloned()
+            .collect();
+        if added.is_empty() {
+            return false;
+        }
+
+        list.extend(added.iter().cloned().map(Value::String));
+        self.set("CFBundleLocalizations", Value::Array(list));
+        self.save_or_warn();
+        self.events.emit(Event::PlistChanged(PlistChange::Localizations(added)));
+        true
+    }
+
+    /// Every scheme in `CFBundleURLTypes`
+    pub fn url_schemes(&self) -> Vec<String> {
+        let url_types = self.get("CFBundleURLTypes").and_then(Value::as_array);
+        url_types
+            .into_iter()
+            .flatten()
+            .filter_map(|t| t.as_dictionary()?.get("CFBundleURLSchemes")?.as_array())
+            .flatten()
+            .filter_map(|s| s.as_string().map(str::to_string))
+            .collect()
+    }
+
+    /// Register `schemes` in `CFBundleURLTypes`, creating it if missing. Schemes
+    /// are case-insensitive; ones the app already handles are skipped.
+    pub fn add_url_schemes(&mut self, schemes: &[String]) -> bool {
+        let mut known = self.url_schemes();
+        let mut added = Vec::new();
+        for scheme in schemes {
+            if !known.iter().any(|s| s.eq_ignore_ascii_case(scheme)) {
+                known.push(scheme.clone());
+                added.push(scheme.clone());
+            }
+        }
+        if added.is_empty() {
+            return false;
+        }
+
+        let mut url_types = match self.get("CFBundleURLTypes").and_then(Value::as_array) {
+            Some(url_types) => url_types.clone(),
+            None => Vec::new(),
+        };
+        for scheme in &added {
+            let mut url_type = plist::Dictionary::new();
+            url_type.insert("CFBundleURLName".to_string(), Value::String(scheme.clone()));
+            url_type.insert(
+                "CFBundleURLSchemes".to_string(),
+                Value::Array(vec![Value::String(scheme.clone())]),
+            );
+            url_types.push(Value::Dictionary(url_type));
+        }
+        self.set("CFBundleURLTypes", Value::Array(url_types));
+        self.save_or_warn();
+        self.events.emit(Event::PlistChanged(PlistChange::UrlSchemesAdded(added)));
+        true
+    }
+
+    /// Unregister `schemes` (case-insensitive), dropping URL types left without
+    /// a scheme and `CFBundleURLTypes` itself once it's empty
+    pub fn remove_url_schemes(&mut self, schemes: &[String]) -> bool {
+        let Some(mut url_types) = self.get("CFBundleURLTypes").and_then(Value::as_array).cloned()
+        else {
+            return false;
+        };
+
+        let mut removed = Vec::new();
+        url_types.retain_mut(|url_type| {
+            let list = url_type
+                .as_dictionary_mut()
+                .and_then(|d| d.get_mut("CFBundleURLSchemes"))
+                .and_then(Value::as_array_mut);
+            let Some(list) = list else {
+                return true;
+            };
+            list.retain(|s| match s.as_string() {
+                Some(s) if schemes.iter().any(|r| r.eq_ignore_ascii_case(s)) => {
+                    removed.push(s.to_string());
+                    false
+                }
+                _ => true,
+            });
+            !list.is_empty()
+        });
+        if removed.is_empty() {
+            return false;
+        }
+
+        if url_types.is_empty() {
+            self.remove("CFBundleURLTypes");
+        } else {
+            self.set("CFBundleURLTypes", Value::Array(url_types));
+        }
+        self.save_or_warn();
+        self.events.emit(Event::PlistChanged(PlistChange::UrlSchemesRemoved(removed)));
+        true
+    }
+
+    pub fn change_version(&mut self, version: &str) -> bool {
+        let current_ver = self.get_string("CFBundleVersion").map(|s| s.to_string());
+        let current_short = self.get_string("CFBundleShortVersionString").map(|s| s.to_string());
+
+        if current_ver.as_deref() == Some(version) && current_short.as_deref() == Some(version) {
+            return false;
+        }
+
+        self.set_string("CFBundleVersion", version);
+        self.set_string("CFBundleShortVersionString", version);
+        self.save_or_warn();
+        self.events.emit(Event::PlistChanged(PlistChange::Version(version.to_string())));
+        true
+    }
+
+    pub fn change_bundle_id(&mut self, bundle_id: &str) -> bool {
+        let orig = match self.get_string("CFBundleIdentifier") {
+            Some(id) => id.to_string(),
+            None => return false,
+        };
+
+        if orig == bundle_id {
+            return false;
+        }
+
+        self.set_string("CFBundleIdentifier", bundle_id);
+        self.save_or_warn();
+        self.events.emit(Event::PlistChanged(PlistChange::BundleId(bundle_id.to_string())));
+
+        // Update extension and watch app bundle IDs
+        if let Some(ref app_path) = self.app_path {
+            let mut changed_count = 0;
+            let contents = Layout::detect(app_path).contents_dir(app_path);
+            let patterns = ["*/*.appex", "Watch/*.app", "Watch/*.app/PlugIns/*.appex"];
+            let entries = patterns
+                .iter()
+                .filter_map(|p| glob::glob(&format!("{}/{}", contents.display(), p)).ok())
+                .flat_map(|entries| entries.flatten());
+            for entry in entries {
+                let plist_path = Layout::detect(&entry).info_plist(&entry);
+                if let Ok(mut pl) = PlistFile::open(&plist_path) {
+                    if pl.replace_bundle_ids(&orig, bundle_id) {
+                        match pl.save() {
+                            Ok(()) => changed_count += 1,
+                            Err(e) => self.events.warn(format!(
+                                "couldn't save {}: {}",
+                                plist_path.display(),
+                                e
+                            )),
+                        }
+                    }
+                }
+            }
+            if changed_count > 0 {
+                let change = PlistChange::OtherBundleIds(changed_count);
+                self.events.emit(Event::PlistChanged(change));
+            }
+        }
+        true
+    }
+
+    /// Replace `orig` in a nested bundle's own id and the ids tying a watch app to its
+    /// companion (`WKCompanionAppBundleIdentifier`) and extension (`WKAppBundleIdentifier`)
+    fn replace_bundle_ids(&mut self, orig: &str, bundle_id: &str) -> bool {
+        let mut changed = false;
+        let mut replace = |value: &mut Value| {
+            if let Value::String(id) = value {
+                if id.contains(orig) {
+                    *id = id.replace(orig, bundle_id);
+                    changed = true;
+                }
+            }
+        };
+
+        for key in ["CFBundleIdentifier", "WKCompanionAppBundleIdentifier"] {
+            if let Some(value) = self.data.get_mut(key) {
+                replace(value);
+            }
+        }
+        let attributes = self
+            .data
+            .get_mut("NSExtension")
+            .and_then(Value::as_dictionary_mut)
+            .and_then(|ext| ext.get_mut("NSExtensionAttributes"))
+            .and_then(Value::as_dictionary_mut);
+        if let Some(value) = attributes.and_then(|attrs| attrs.get_mut("WKAppBundleIdentifier")) {
+            replace(value);
+        }
+        changed
+    }
+
+    pub fn change_minimum_version(&mut self, minimum: Version) -> bool {
+        let key = Platform::detect(self).minimum_version_key();
+        let current = self.minimum_version();
+
+        if current == Some(minimum) {
+            return false;
+        }
+
+        let minimum = minimum.to_string();
+        self.set_string(key, &minimum);
+        self.save_or_warn();
+        self.events.emit(Event::PlistChanged(PlistChange::MinimumVersion(minimum)));
+        true
+    }
+
+    /// `MinimumOSVersion` (`LSMinimumSystemVersion` on macOS), if it's set and valid
+    pub fn minimum_version(&self) -> Option<Version> {
+        let key = Platform::detect(self).minimum_version_key();
+        self.get_string(key)?.parse().ok()
+    }
+
+    /// Delete each of `keys`, a top-level key or a dotted path into nested
+    /// dictionaries (`NSAppTransportSecurity.NSAllowsArbitraryLoads`). A key
+    /// that itself has dots, like a domain, is matched whole before splitting.
+    /// Missing keys are skipped with a warning.
+    pub fn remove_keys(&mut self, keys: &[String]) -> bool {
+        let mut removed = Vec::new();
+        for key in keys {
+            if remove_key_path(&mut self.data, key) {
+                removed.push(key.clone());
+            } else {
+                self.events.warn(format!("{} has no key {}", self.path.display(), key));
+            }
+        }
+        if removed.is_empty() {
+            return false;
+        }
+
+        self.save_or_warn();
+        self.events.emit(Event::PlistChanged(PlistChange::RemovedKeys(removed)));
+        true
+    }
+
+    pub fn merge_plist<P: AsRef<Path>>(&mut self, path: P) -> Result<bool> {
+        self.merge_plist_with(path, PlistMerge::Replace)
+    }
+
+    /// Merge the plist at `path` into this one, combining values as `mode` says
+    pub fn merge_plist_with<P: AsRef<Path>>(&mut self, path: P, mode: PlistMerge) -> Result<bool> {
+        let other = PlistFile::open(path)?;
+        let changed = !other.data.is_empty();
+
+        let keys: Vec<String> = other.data.keys().cloned().collect();
+        merge_dictionary(&mut self.data, other.data, mode);
+
+        if changed {
+            self.save()?;
+            self.events.emit(Event::PlistChanged(PlistChange::Merged(keys.len())));
+        }
+
+        Ok(changed)
+    }
+}
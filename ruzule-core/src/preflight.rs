@@ -0,0 +1,187 @@
+//! Checks on everything a run reads and writes, made before the input is
+//! unpacked, so a malformed entitlements file or a full disk fails in seconds
+//! instead of after extraction and signing.
+//!
+//! [`Injector::validate`](crate::Injector::validate) only checks that files
+//! exist; these open and parse them the way the run will.
+
+use crate::cache::cache_dir;
+use crate::cyan_config::summarize_cyan;
+use crate::entitlements;
+use crate::error::{Result, RuzuleError};
+use crate::icon;
+use crate::injector::{has_extension, is_stdout, InjectOptions};
+use crate::macho;
+use crate::plan::disk_size;
+use crate::platform::bundle_executable;
+use crate::sign;
+use crate::temp::{TempDir, TempFile};
+use std::fs::File;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Every check, in the order a run would hit the problem
+pub(crate) fn check(input: &Path, output: &Path, options: &InjectOptions) -> Result<()> {
+    let unpacked = if has_extension(input, &["ipa", "tipa"]) {
+        check_ipa(input)?
+    } else {
+        disk_size(input)
+    };
+
+    for cyan in &options.cyans {
+        summarize_cyan(cyan).map_err(|e| invalid(cyan, e))?;
+    }
+    for file in &options.files {
+        check_injected(file)?;
+    }
+    if let Some(ref dylib) = options.patch_plugins_dylib {
+        macho::architectures(dylib).map_err(|e| invalid(dylib, e))?;
+    }
+    for path in options.icon.iter().chain(options.alternate_icons.values()) {
+        let tmpdir = TempDir::new()?;
+        icon::render(path, &[120], tmpdir.path()).map_err(|e| invalid(path, e))?;
+    }
+    for path in options.plist.iter().chain(&options.entitlements) {
+        plist::Value::from_file(path).map_err(|e| invalid(path, e.into()))?;
+    }
+    if let Some(ref p12) = options.certificate {
+        let certificate = options.open_certificate().map_err(|e| invalid(p12, e))?;
+        if let (Some(certificate), Some(profile)) = (certificate, &options.profile) {
+            sign::check_profile(profile, &certificate)?;
+        }
+    }
+
+    if !is_stdout(output) {
+        check_writable(output)?;
+    }
+
+    // Unpacked app and everything injected in the work directory, then about
+    // the input's size again for the output
+    let injected: u64 = options
+        .files
+        .iter()
+        .chain(&options.cyans)
+        .map(|f| disk_size(f))
+        .sum();
+    let work_dir = if options.resume {
+        cache_dir()
+    } else {
+        Some(std::env::temp_dir())
+    };
+    if let Some(dir) = work_dir {
+        check_space(&dir, unpacked + injected)?;
+    }
+    if !is_stdout(output) {
+        let packed = if has_extension(output, &["app"]) {
+            unpacked
+        } else {
+            disk_size(input)
+        };
+        check_space(&parent(output), packed + injected)?;
+    }
+    Ok(())
+}
+
+/// Opens the archive's central directory, returning the unpacked size
+fn check_ipa(ipa: &Path) -> Result<u64> {
+    let mut archive = zip::ZipArchive::new(File::open(ipa)?).map_err(|e| invalid(ipa, e.into()))?;
+    if !archive
+        .file_names()
+        .any(|name| name.starts_with("Payload/"))
+    {
+        return Err(RuzuleError::InvalidIpa(
+            "No Payload folder found".to_string(),
+        ));
+    }
+
+    let mut size = 0;
+    for i in 0..archive.len() {
+        size += archive.by_index_raw(i)?.size();
+    }
+    Ok(size)
+}
+
+fn check_injected(file: &Path) -> Result<()> {
+    entitlements::read_sidecar(file).map_err(|e| invalid(&entitlements::sidecar_path(file), e))?;
+    if has_extension(file, &["dylib"]) {
+        macho::architectures(file).map_err(|e| invalid(file, e))?;
+    } else if has_extension(file, &["framework"]) {
+        if let Some(binary) = bundle_executable(file).filter(|b| b.is_file()) {
+            macho::architectures(&binary).map_err(|e| invalid(file, e))?;
+        }
+    } else if has_extension(file, &["deb"]) {
+        check_deb(file)?;
+    }
+    Ok(())
+}
+
+/// A .deb is an ar archive with a `data.tar.*` member
+fn check_deb(deb: &Path) -> Result<()> {
+    let mut archive = ar::Archive::new(Cursor::new(std::fs::read(deb)?));
+    while let Some(entry) = archive.next_entry() {
+        let entry = entry.map_err(|e| invalid(deb, e.into()))?;
+        if entry.header().identifier().starts_with(b"data.tar") {
+            return Ok(());
+        }
+    }
+    Err(RuzuleError::InvalidInput(format!(
+        "{}: no data.tar member",
+        deb.display()
+    )))
+}
+
+/// The output's directory exists and a file can be created in it
+fn check_writable(output: &Path) -> Result<()> {
+    let dir = parent(output);
+    if !dir.is_dir() {
+        return Err(RuzuleError::FileNotFound(dir));
+    }
+    TempFile::new_in(&dir).map_err(|e| {
+        RuzuleError::InvalidInput(format!("can't write to {}: {}", dir.display(), e))
+    })?;
+    Ok(())
+}
+
+fn check_space(dir: &Path, needed: u64) -> Result<()> {
+    match available_space(dir) {
+        Some(available) if available < needed => Err(RuzuleError::NoSpace {
+            path: dir.to_path_buf(),
+            needed,
+            available,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Free bytes on the filesystem holding `dir`, if it can be told
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid out-pointer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // The field types differ between platforms
+    #[allow(clippy::useless_conversion)]
+    let free = u64::from(stat.f_bavail) * u64::from(stat.f_frsize);
+    Some(free)
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+fn parent(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => ".".into(),
+    }
+}
+
+fn invalid(path: &Path, e: RuzuleError) -> RuzuleError {
+    RuzuleError::InvalidInput(format!("{}: {}", path.display(), e))
+}
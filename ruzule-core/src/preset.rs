@@ -0,0 +1,250 @@
+//! Named recipes: TOML files of [`InjectOptions`] fields, kept in a presets directory.
+//!
+//! ```toml
+//! description = "YouTube with uYouEnhanced"
+//! cyans = ["uyouenhanced.cyan"]
+//! remove_supported_devices = true
+//! fakesign = true
+//! ```
+//!
+//! Relative paths are resolved against the preset's directory. Options given
+//! explicitly take precedence over the preset, see [`Preset::merge`].
+
+use crate::error::{Result, RuzuleError};
+use crate::injector::InjectOptions;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where presets are looked up by name.
+///
+/// `$RUZULE_PRESETS_DIR` if set, otherwise `%APPDATA%\ruzule\presets` on Windows
+/// and `$XDG_CONFIG_HOME/ruzule/presets` (default `~/.config/ruzule/presets`) elsewhere.
+pub fn presets_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("RUZULE_PRESETS_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    config_dir().map(|dir| dir.join("presets"))
+}
+
+/// ruzule's configuration directory, `%APPDATA%\ruzule` or `$XDG_CONFIG_HOME/ruzule`
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+
+    #[cfg(not(windows))]
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    base.map(|dir| dir.join("ruzule"))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Preset {
+    /// File name without `.toml`
+    #[serde(skip)]
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub options: InjectOptions,
+}
+
+impl Preset {
+    /// A preset by name from [`presets_dir`], or by path to a .toml file
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Path::new(name);
+        if path.extension().is_some_and(|e| e == "toml") && path.is_file() {
+            return Self::open(path);
+        }
+
+        let dir = presets_dir().ok_or_else(|| {
+            RuzuleError::InvalidInput("no presets directory (set RUZULE_PRESETS_DIR)".to_string())
+        })?;
+        let path = dir.join(format!("{}.toml", name));
+        if !path.is_file() {
+            return Err(RuzuleError::InvalidInput(format!(
+                "unknown preset \"{}\" (looked in {})",
+                name,
+                dir.display()
+            )));
+        }
+        Self::open(&path)
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut preset: Preset = toml::from_str(&text).map_err(|e| {
+            RuzuleError::InvalidInput(format!("invalid preset {}: {}", path.display(), e))
+        })?;
+        preset.name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // Where to write is up to each run
+        preset.options.output = None;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let opts = &mut preset.options;
+        for p in opts.files.iter_mut().chain(&mut opts.cyans) {
+            *p = base.join(&*p);
+        }
+        let single = [
+            &mut opts.icon,
+            &mut opts.plist,
+            &mut opts.entitlements,
+            &mut opts.patch_plugins_dylib,
+            &mut opts.swift_libs,
+            &mut opts.certificate,
+            &mut opts.profile,
+            &mut opts.export_certificate,
+            &mut opts.export_profile,
+        ];
+        for p in single.into_iter().flatten() {
+            *p = base.join(&*p);
+        }
+        for p in opts.alternate_icons.values_mut() {
+            *p = base.join(&*p);
+        }
+        Ok(preset)
+    }
+
+    /// Every preset in [`presets_dir`], by name. Files that don't parse are skipped
+    /// with a log warning.
+    pub fn list() -> Result<Vec<Preset>> {
+        let Some(dir) = presets_dir().filter(|d| d.is_dir()) else {
+            return Ok(Vec::new());
+        };
+
+        let mut presets = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension() != Some("toml".as_ref()) {
+                continue;
+            }
+            match Self::open(&path) {
+                Ok(preset) => presets.push(preset),
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+        presets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(presets)
+    }
+
+    /// `explicit` on top of this preset.
+    ///
+    /// Values set in `explicit` win; flags are on if either turns them on.
+    /// Files, .cyans, removed frameworks and defines are combined, the preset's
+    /// first, so explicit .cyans override the preset's under the default conflict
+    /// policy. Options left at their default (compression, substrate backend,
+    /// conflict policy, export) take the preset's value.
+    pub fn merge(self, explicit: InjectOptions) -> InjectOptions {
+        let preset = self.options;
+        let default = InjectOptions::default();
+        let mut defines = preset.defines;
+        defines.extend(explicit.defines);
+
+        InjectOptions {
+            output: explicit.output,
+            cyans: preset.cyans.into_iter().chain(explicit.cyans).collect(),
+            cyan_conflict: if explicit.cyan_conflict == default.cyan_conflict {
+                preset.cyan_conflict
+            } else {
+                explicit.cyan_conflict
+            },
+            defines,
+            files: preset.files.into_iter().chain(explicit.files).collect(),
+            name: explicit.name.or(preset.name),
+            version: explicit.version.or(preset.version),
+            bundle_id: explicit.bundle_id.or(preset.bundle_id),
+            minimum: explicit.minimum.or(preset.minimum),
+            minimum_check: if explicit.minimum_check == default.minimum_check {
+                preset.minimum_check
+            } else {
+                explicit.minimum_check
+            },
+            auto_minimum: explicit.auto_minimum || preset.auto_minimum,
+            icon: explicit.icon.or(preset.icon),
+            alternate_icons: preset
+                .alternate_icons
+                .into_iter()
+                .chain(explicit.alternate_icons)
+                .collect(),
+            plist: explicit.plist.or(preset.plist),
+            plist_merge: explicit.plist_merge.or(preset.plist_merge),
+            plist_format: explicit.plist_format.or(preset.plist_format),
+            remove_plist_keys: preset
+                .remove_plist_keys
+                .into_iter()
+                .chain(explicit.remove_plist_keys)
+                .collect(),
+            entitlements: explicit.entitlements.or(preset.entitlements),
+            remove_entitlements: preset
+                .remove_entitlements
+                .into_iter()
+                .chain(explicit.remove_entitlements)
+                .collect(),
+            remove_supported_devices: explicit.remove_supported_devices
+                || preset.remove_supported_devices,
+            add_url_schemes: preset
+                .add_url_schemes
+                .into_iter()
+                .chain(explicit.add_url_schemes)
+                .collect(),
+            remove_url_schemes: preset
+                .remove_url_schemes
+                .into_iter()
+                .chain(explicit.remove_url_schemes)
+                .collect(),
+            no_watch: explicit.no_watch || preset.no_watch,
+            watch_app: explicit.watch_app || preset.watch_app,
+            stub_watch: explicit.stub_watch || preset.stub_watch,
+            remove_frameworks: preset
+                .remove_frameworks
+                .into_iter()
+                .chain(explicit.remove_frameworks)
+                .collect(),
+            arch: explicit.arch.or(preset.arch),
+            uninject: preset.uninject.into_iter().chain(explicit.uninject).collect(),
+            remove_rpaths: preset.remove_rpaths.into_iter().chain(explicit.remove_rpaths).collect(),
+            enable_documents: explicit.enable_documents || preset.enable_documents,
+            fakesign: explicit.fakesign || preset.fakesign,
+            certificate: explicit.certificate.or(preset.certificate),
+            certificate_password: explicit.certificate_password.or(preset.certificate_password),
+            profile: explicit.profile.or(preset.profile),
+            strip_risky_entitlements: explicit.strip_risky_entitlements
+                || preset.strip_risky_entitlements,
+            thin: explicit.thin || preset.thin,
+            thin_tweaks: explicit.thin_tweaks.or(preset.thin_tweaks),
+            remove_extensions: explicit.remove_extensions || preset.remove_extensions,
+            remove_encrypted: explicit.remove_encrypted || preset.remove_encrypted,
+            compress: if explicit.compress == default.compress {
+                preset.compress
+            } else {
+                explicit.compress
+            },
+            jobs: explicit.jobs.or(preset.jobs),
+            ignore_encrypted: explicit.ignore_encrypted || preset.ignore_encrypted,
+            use_frameworks_dir: explicit.use_frameworks_dir || preset.use_frameworks_dir,
+            inject_into: explicit.inject_into.or(preset.inject_into),
+            patch_plugins: explicit.patch_plugins || preset.patch_plugins,
+            patch_plugins_dylib: explicit.patch_plugins_dylib.or(preset.patch_plugins_dylib),
+            substrate_backend: if explicit.substrate_backend == default.substrate_backend {
+                preset.substrate_backend
+            } else {
+                explicit.substrate_backend
+            },
+            swift_libs: explicit.swift_libs.or(preset.swift_libs),
+            keep_temp: explicit.keep_temp || preset.keep_temp,
+            resume: explicit.resume || preset.resume,
+            export: if explicit.export == default.export {
+                preset.export
+            } else {
+                explicit.export
+            },
+            export_certificate: explicit.export_certificate.or(preset.export_certificate),
+            export_profile: explicit.export_profile.or(preset.export_profile),
+        }
+    }
+}
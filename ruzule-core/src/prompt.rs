@@ -0,0 +1,52 @@
+//! Confirmations asked before something is replaced.
+//!
+//! The library never reads stdin on its own: an [`Injector`](crate::Injector)
+//! only asks before overwriting an existing output when it's given a
+//! [`Prompter`], and then through it. Frontends pick how questions are
+//! answered, e.g. by asking on their own terminal or window; [`AssumeYes`]
+//! and [`NoInput`] answer without asking, for scripts and CI.
+
+use crate::error::{Result, RuzuleError};
+use std::fmt;
+
+/// Answers yes/no questions; implemented for any `Fn(&str) -> Result<bool>`
+pub trait Prompter: Send + Sync {
+    /// Ask `question` (e.g. "App.ipa already exists, overwrite it?"); `false` stops
+    fn confirm(&self, question: &str) -> Result<bool>;
+}
+
+impl<F: Fn(&str) -> Result<bool> + Send + Sync> Prompter for F {
+    fn confirm(&self, question: &str) -> Result<bool> {
+        self(question)
+    }
+}
+
+impl fmt::Debug for dyn Prompter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Prompter")
+    }
+}
+
+/// Answers yes to everything without asking
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AssumeYes;
+
+impl Prompter for AssumeYes {
+    fn confirm(&self, _question: &str) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+/// Never asks: any question is an error, so an unattended run fails instead
+/// of waiting for an answer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoInput;
+
+impl Prompter for NoInput {
+    fn confirm(&self, question: &str) -> Result<bool> {
+        Err(RuzuleError::InvalidInput(format!(
+            "{} (not asking, input is disabled)",
+            question
+        )))
+    }
+}
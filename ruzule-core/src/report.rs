@@ -0,0 +1,143 @@
+//! What an app asks the system for, for vetting an IPA before installing it:
+//! privacy prompts, background modes, entitlements, queried URL schemes and
+//! App Transport Security exceptions.
+
+use crate::app_bundle::AppBundle;
+use crate::cancel::CancellationToken;
+use crate::entitlements;
+use crate::error::Result;
+use crate::events::Events;
+use crate::injector::has_extension;
+use crate::ipa::extract_ipa;
+use crate::plist_ext::PlistFile;
+use crate::sign;
+use crate::temp::TempDir;
+use plist::Value;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AppReport {
+    pub name: Option<String>,
+    pub bundle_id: Option<String>,
+    pub version: Option<String>,
+    pub platform: String,
+    /// `NS*UsageDescription` keys and the text shown in the permission prompt
+    pub usage_descriptions: BTreeMap<String, String>,
+    /// `UIBackgroundModes`
+    pub background_modes: Vec<String>,
+    /// The main binary's entitlements
+    pub entitlements: BTreeMap<String, serde_json::Value>,
+    /// Entitlements that ad-hoc signing can't honor, with the reason
+    pub entitlement_risks: BTreeMap<String, String>,
+    /// `LSApplicationQueriesSchemes`, the apps it checks for
+    pub queried_schemes: Vec<String>,
+    pub ats_exceptions: Vec<AtsException>,
+}
+
+/// An `NSAppTransportSecurity` setting that loosens the default HTTPS requirements
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AtsException {
+    /// `None` for app-wide settings (`NSAllowsArbitraryLoads`, ...)
+    pub domain: Option<String>,
+    /// Keys that are enabled, e.g. `NSExceptionAllowsInsecureHTTPLoads`
+    pub keys: Vec<String>,
+}
+
+impl AppReport {
+    /// Report on an .ipa, .tipa or .app; archives are unpacked to a temporary directory
+    pub fn from_path<P: AsRef<Path>>(input: P) -> Result<Self> {
+        let input = input.as_ref();
+        if !has_extension(input, &["ipa", "tipa"]) {
+            return Self::from_bundle(&AppBundle::new(input)?);
+        }
+
+        let tmpdir = TempDir::new()?;
+        let cancel = CancellationToken::new();
+        let app_path = extract_ipa(input, tmpdir.path(), &cancel, &Events::none())?;
+        Self::from_bundle(&AppBundle::new(app_path)?)
+    }
+
+    pub fn from_bundle(app: &AppBundle) -> Result<Self> {
+        let plist = &app.plist;
+        let string = |key| plist.get_string(key).map(str::to_string);
+        let strings = |key| -> Vec<String> {
+            plist
+                .get(key)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v.as_string().map(str::to_string))
+                .collect()
+        };
+
+        let usage_descriptions = plist
+            .data
+            .iter()
+            .filter(|(k, _)| k.starts_with("NS") && k.ends_with("UsageDescription"))
+            .map(|(k, v)| (k.clone(), v.as_string().unwrap_or_default().to_string()))
+            .collect();
+
+        let data = sign::extract_entitlements(&app.executable.inner.path)?;
+        let entitlements = if data.is_empty() {
+            BTreeMap::new()
+        } else {
+            let dict: plist::Dictionary = plist::from_bytes(&data)?;
+            dict.into_iter()
+                .map(|(k, v)| -> Result<_> { Ok((k, serde_json::to_value(v)?)) })
+                .collect::<Result<_>>()?
+        };
+
+        let entitlement_risks = entitlements
+            .keys()
+            .filter_map(|k| Some((k.clone(), entitlements::adhoc_risk(k)?.to_string())))
+            .collect();
+
+        Ok(Self {
+            name: string("CFBundleDisplayName").or_else(|| string("CFBundleName")),
+            bundle_id: string("CFBundleIdentifier"),
+            version: string("CFBundleShortVersionString"),
+            platform: app.platform.to_string(),
+            usage_descriptions,
+            background_modes: strings("UIBackgroundModes"),
+            entitlements,
+            entitlement_risks,
+            queried_schemes: strings("LSApplicationQueriesSchemes"),
+            ats_exceptions: ats_exceptions(plist),
+        })
+    }
+}
+
+fn ats_exceptions(plist: &PlistFile) -> Vec<AtsException> {
+    let Some(ats) = plist.get("NSAppTransportSecurity").and_then(Value::as_dictionary) else {
+        return Vec::new();
+    };
+    // Keys that are on when true; NSExceptionMinimumTLSVersion etc. are listed with their value
+    let enabled = |dict: &plist::Dictionary| -> Vec<String> {
+        dict.iter()
+            .filter_map(|(k, v)| match v {
+                Value::Boolean(true) => Some(k.clone()),
+                Value::String(s) if k.ends_with("TLSVersion") => Some(format!("{}={}", k, s)),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let mut exceptions = Vec::new();
+    let global = enabled(ats);
+    if !global.is_empty() {
+        exceptions.push(AtsException { domain: None, keys: global });
+    }
+    let domains = ats.get("NSExceptionDomains").and_then(Value::as_dictionary);
+    for (domain, settings) in domains.into_iter().flatten() {
+        let keys = settings.as_dictionary().map(enabled).unwrap_or_default();
+        if !keys.is_empty() {
+            exceptions.push(AtsException {
+                domain: Some(domain.clone()),
+                keys,
+            });
+        }
+    }
+    exceptions
+}
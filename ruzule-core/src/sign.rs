@@ -0,0 +1,242 @@
+use crate::error::{Result, RuzuleError};
+use apple_codesign::cryptography::{parse_pfx_data, InMemoryPrivateKey};
+use apple_codesign::{AppleCertificate, MachFile, SettingsScope, SigningSettings, UnifiedSigner};
+use std::fs;
+use crate::temp::TempFile;
+use std::path::Path;
+use x509_certificate::CapturedX509Certificate;
+
+/// A developer certificate and its private key, read from a .p12
+pub struct Certificate {
+    cert: CapturedX509Certificate,
+    key: InMemoryPrivateKey,
+}
+
+impl Certificate {
+    pub fn from_p12<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        let data = fs::read(path.as_ref())?;
+        let (cert, key) = parse_pfx_data(&data, password)
+            .map_err(|e| RuzuleError::Sign(format!("Failed to read certificate: {}", e)))?;
+        Ok(Self { cert, key })
+    }
+
+    /// The certificate's common name, e.g. `Apple Development: Jane Doe (AB12CD34EF)`
+    pub fn name(&self) -> String {
+        self.cert.subject_common_name().unwrap_or_default()
+    }
+
+    pub fn team_id(&self) -> Option<String> {
+        self.cert.apple_team_id()
+    }
+}
+
+/// The plist inside a .mobileprovision (a CMS envelope around an XML plist)
+pub fn read_profile<P: AsRef<Path>>(path: P) -> Result<plist::Dictionary> {
+    let path = path.as_ref();
+    let data = fs::read(path)?;
+    let invalid = || RuzuleError::Sign(format!("{} is not a provisioning profile", path.display()));
+    let start = data.windows(5).position(|w| w == b"<?xml").ok_or_else(invalid)?;
+    let end = data.windows(8).rposition(|w| w == b"</plist>").ok_or_else(invalid)? + 8;
+    plist::from_bytes(&data[start..end.max(start)]).map_err(|e| {
+        RuzuleError::Sign(format!("Bad provisioning profile {}: {}", path.display(), e))
+    })
+}
+
+/// Check a provisioning profile parses and was issued to the certificate's team
+pub fn check_profile<P: AsRef<Path>>(profile: P, certificate: &Certificate) -> Result<()> {
+    let profile = profile.as_ref();
+    let teams: Vec<String> = read_profile(profile)?
+        .get("TeamIdentifier")
+        .and_then(|v| v.as_array())
+        .map(|teams| teams.iter().filter_map(|t| Some(t.as_string()?.to_string())).collect())
+        .unwrap_or_default();
+    match certificate.team_id() {
+        Some(team) if !teams.contains(&team) => Err(RuzuleError::Sign(format!(
+            "{} is for team {}, the certificate is team {}",
+            profile.display(),
+            teams.join(", "),
+            team
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The entitlements a profile grants `bundle_id`: its `Entitlements` with the
+/// app id and keychain group wildcards filled in and other wildcards left out.
+/// Fails if the profile's app id doesn't cover `bundle_id`.
+pub fn profile_entitlements<P: AsRef<Path>>(
+    profile: P,
+    bundle_id: &str,
+) -> Result<plist::Dictionary> {
+    let path = profile.as_ref();
+    let profile = read_profile(path)?;
+    let mut entitlements = profile
+        .get("Entitlements")
+        .and_then(|v| v.as_dictionary())
+        .cloned()
+        .unwrap_or_default();
+
+    let app_id = entitlements
+        .get("application-identifier")
+        .and_then(|v| v.as_string())
+        .ok_or_else(|| {
+            RuzuleError::Sign(format!("{} has no application-identifier", path.display()))
+        })?
+        .to_string();
+    // TEAMID.com.example.app or TEAMID.com.example.* (or just TEAMID.*)
+    let (prefix, pattern) = app_id.split_once('.').unwrap_or((&app_id, ""));
+    let covers = match pattern.strip_suffix('*') {
+        Some(stem) => bundle_id.starts_with(stem),
+        None => pattern == bundle_id,
+    };
+    if !covers {
+        return Err(RuzuleError::Sign(format!(
+            "{} is for app id {}, the app is {}",
+            path.display(),
+            pattern,
+            bundle_id
+        )));
+    }
+
+    let team_app_id = format!("{}.{}", prefix, bundle_id);
+    entitlements.insert("application-identifier".to_string(), team_app_id.clone().into());
+    let groups = entitlements.get_mut("keychain-access-groups").and_then(|v| v.as_array_mut());
+    if let Some(groups) = groups {
+        for group in groups.iter_mut() {
+            if group.as_string().is_some_and(|g| g.ends_with('*')) {
+                *group = team_app_id.clone().into();
+            }
+        }
+    }
+    // What's left with a wildcard (associated domains, iCloud containers, ...)
+    // has no concrete value to sign with
+    entitlements.retain(|_, value| !has_wildcard(value));
+    Ok(entitlements)
+}
+
+fn has_wildcard(value: &plist::Value) -> bool {
+    match value {
+        plist::Value::String(s) => s.contains('*'),
+        plist::Value::Array(items) => items.iter().any(has_wildcard),
+        _ => false,
+    }
+}
+
+/// Sign a bundle and everything nested in it (frameworks, plugins, dylibs)
+/// with `certificate`, in place. The main binary gets `entitlements` when
+/// given (see [`profile_entitlements`]); other binaries keep their own.
+pub fn sign_bundle<P: AsRef<Path>>(
+    bundle: P,
+    certificate: &Certificate,
+    entitlements: Option<&plist::Dictionary>,
+) -> Result<()> {
+    let bundle = bundle.as_ref();
+    log::debug!("signing {} as {}", bundle.display(), certificate.name());
+    let mut settings = SigningSettings::default();
+    settings.set_signing_key(&certificate.key, certificate.cert.clone());
+    settings.chain_apple_certificates();
+    settings.set_team_id_from_signing_certificate();
+    if let Some(entitlements) = entitlements {
+        let xml = crate::buffer::plist_to_xml(entitlements)?;
+        settings
+            .set_entitlements_xml(SettingsScope::Main, String::from_utf8_lossy(&xml))
+            .map_err(|e| RuzuleError::Sign(format!("Failed to set entitlements: {}", e)))?;
+    }
+
+    UnifiedSigner::new(settings)
+        .sign_path_in_place(bundle)
+        .map_err(|e| RuzuleError::Sign(format!("Failed to sign: {}", e)))
+}
+
+/// Ad-hoc sign a Mach-O binary (no entitlements, no certificate)
+pub fn fakesign<P: AsRef<Path>>(path: P) -> Result<bool> {
+    let path = path.as_ref();
+    let settings = SigningSettings::default();
+    sign_macho_in_place(path, &settings)
+}
+
+/// Sign a Mach-O binary with entitlements (ad-hoc, no certificate)
+pub fn sign_with_entitlements<P: AsRef<Path>, Q: AsRef<Path>>(
+    path: P,
+    entitlements: Q,
+) -> Result<bool> {
+    let path = path.as_ref();
+    let ent_path = entitlements.as_ref();
+
+    let ent_xml = fs::read_to_string(ent_path)?;
+
+    let mut settings = SigningSettings::default();
+    settings
+        .set_entitlements_xml(SettingsScope::Main, &ent_xml)
+        .map_err(|e| RuzuleError::Sign(format!("Failed to set entitlements: {}", e)))?;
+
+    sign_macho_in_place(path, &settings)
+}
+
+/// Extract entitlements from a signed Mach-O binary
+pub fn extract_entitlements<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let data = crate::macho::map(path.as_ref())?;
+
+    let mach = MachFile::parse(&data)
+        .map_err(|e| RuzuleError::Sign(format!("Failed to parse Mach-O: {}", e)))?;
+
+    // Get entitlements from first arch
+    if let Some(macho) = mach.iter_macho().next() {
+        if let Ok(Some(sig)) = macho.code_signature() {
+            if let Ok(Some(ent)) = sig.entitlements() {
+                return Ok(ent.as_str().as_bytes().to_vec());
+            }
+        }
+    }
+
+    Ok(Vec::new())
+}
+
+/// The identifier a signed Mach-O binary was signed under (from its first arch)
+pub fn signed_identifier<P: AsRef<Path>>(path: P) -> Result<Option<String>> {
+    let data = crate::macho::map(path.as_ref())?;
+
+    let mach = MachFile::parse(&data)
+        .map_err(|e| RuzuleError::Sign(format!("Failed to parse Mach-O: {}", e)))?;
+    let Some(macho) = mach.iter_macho().next() else {
+        return Ok(None);
+    };
+    let Ok(Some(sig)) = macho.code_signature() else {
+        return Ok(None);
+    };
+    match sig.code_directory() {
+        Ok(Some(cd)) => Ok(Some(cd.ident.to_string())),
+        _ => Ok(None),
+    }
+}
+
+/// Remove code signature from a Mach-O binary
+pub fn remove_signature<P: AsRef<Path>>(path: P) -> Result<()> {
+    crate::macho::remove_code_signature(path)?;
+    Ok(())
+}
+
+fn sign_macho_in_place(path: &Path, settings: &SigningSettings) -> Result<bool> {
+    log::debug!("signing {}", path.display());
+    let mut settings = settings.clone();
+    // Otherwise the old signature's identifier is kept, which goes stale when
+    // the bundle id changes and installd rejects the app
+    if let Some(identifier) = crate::platform::bundle_identifier(path) {
+        settings.set_binary_identifier(SettingsScope::Main, identifier);
+    }
+    let signer = UnifiedSigner::new(settings);
+
+    // Sign to a unique temp file next to the binary, so concurrent signs never
+    // share a path and the result can be renamed over the original in one step
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let temp_file = TempFile::new_in(dir)?;
+
+    signer
+        .sign_macho(path, temp_file.path())
+        .map_err(|e| RuzuleError::Sign(format!("Failed to sign: {}", e)))?;
+
+    fs::set_permissions(temp_file.path(), fs::metadata(path)?.permissions())?;
+    temp_file.persist(path)?;
+
+    Ok(true)
+}
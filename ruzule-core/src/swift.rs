@@ -0,0 +1,39 @@
+//! Swift runtime compatibility libraries.
+//!
+//! Tweaks built with a newer toolchain can link back-deployed runtime pieces
+//! such as `@rpath/libswift_Concurrency.dylib`. The OS copies live under
+//! `/usr/lib/swift/`, so any `@rpath/libswift*` dependency has to be shipped
+//! in the app's Frameworks directory, which older apps don't do.
+//!
+//! ruzule doesn't ship or download these libraries; they're taken from
+//! directories the user points at (see [`search_dirs`]).
+
+use std::path::{Path, PathBuf};
+
+/// Names of the `@rpath/libswift*.dylib` libraries in `deps`
+pub fn rpath_swift_libs(deps: &[String]) -> Vec<String> {
+    deps.iter()
+        .filter_map(|dep| dep.strip_prefix("@rpath/"))
+        .filter(|name| name.starts_with("libswift") && name.ends_with(".dylib"))
+        .filter(|name| !name.contains('/'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Directories searched for compatibility libraries, in order:
+/// `extra` (from `--swift-libs`), `$RUZULE_SWIFT_LIBS`, then `<cache>/swift`.
+pub fn search_dirs(extra: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = extra.map(Path::to_path_buf).into_iter().collect();
+    if let Some(dir) = std::env::var_os("RUZULE_SWIFT_LIBS") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(dir) = crate::cache::cache_dir() {
+        dirs.push(dir.join("swift"));
+    }
+    dirs
+}
+
+/// Find `name` in the first search directory that has it
+pub fn find_lib(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter().map(|dir| dir.join(name)).find(|path| path.is_file())
+}
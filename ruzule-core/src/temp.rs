@@ -0,0 +1,82 @@
+//! Scratch files and directories, removed on drop.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+fn unique_name(prefix: &str) -> String {
+    format!(".{}-{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+/// A directory under the system temp directory, or at a given path
+#[derive(Debug)]
+pub(crate) struct TempDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempDir {
+    pub(crate) fn new() -> io::Result<Self> {
+        Self::at(&std::env::temp_dir().join(unique_name("ruzule")))
+    }
+
+    /// `path`, created if needed
+    pub(crate) fn at(path: &Path) -> io::Result<Self> {
+        fs::create_dir_all(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            keep: false,
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether to leave the directory behind when dropped
+    pub(crate) fn keep(&mut self, keep: bool) {
+        self.keep = keep;
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+/// A new, empty file in `dir`, so it can be renamed over a file next to it
+#[derive(Debug)]
+pub(crate) struct TempFile {
+    path: Option<PathBuf>,
+}
+
+impl TempFile {
+    pub(crate) fn new_in(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(unique_name("ruzule"));
+        fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+        Ok(Self { path: Some(path) })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        self.path.as_deref().expect("not persisted yet")
+    }
+
+    /// Rename over `to`, keeping the file
+    pub(crate) fn persist(mut self, to: &Path) -> io::Result<()> {
+        let path = self.path.take().expect("not persisted yet");
+        fs::rename(&path, to).inspect_err(|_| {
+            let _ = fs::remove_file(&path);
+        })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
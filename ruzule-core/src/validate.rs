@@ -0,0 +1,102 @@
+//! Checks on the nested bundles of a modified app that installd is strict
+//! about, and whose failures (error 64, 13...) don't say which bundle is wrong.
+//!
+//! Fakesigning signs every binary under its bundle's current identifier, which
+//! fixes the mismatches found here; duplicate ids are only reported.
+
+use crate::app_bundle::AppBundle;
+use crate::error::Result;
+use crate::platform::{bundle_executable, bundle_paths};
+use crate::plist_ext::PlistFile;
+use crate::sign;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleIssue {
+    /// Several apps or extensions share one CFBundleIdentifier; bundles are
+    /// relative to the app
+    DuplicateId { id: String, bundles: Vec<String> },
+    /// A bundle's binary is signed under another identifier than its Info.plist has
+    SignedIdMismatch {
+        bundle: String,
+        plist_id: String,
+        signed_id: String,
+    },
+}
+
+impl fmt::Display for BundleIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleIssue::DuplicateId { id, bundles } => {
+                write!(f, "{} is the bundle id of {}", id, bundles.join(", "))
+            }
+            BundleIssue::SignedIdMismatch {
+                bundle,
+                plist_id,
+                signed_id,
+            } => write!(
+                f,
+                "{} is signed as {} but its Info.plist says {} (fakesign with -s to fix)",
+                bundle, signed_id, plist_id
+            ),
+        }
+    }
+}
+
+/// Every [`BundleIssue`] in `app`, the app itself included.
+///
+/// Frameworks aren't checked for duplicates: an extension may carry its own
+/// copy of one the app has.
+pub fn check_bundles(app: &AppBundle) -> Result<Vec<BundleIssue>> {
+    let mut issues = Vec::new();
+    let mut ids: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for entry in WalkDir::new(&app.path).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        let is_bundle = path.extension().is_some_and(|e| e == "app" || e == "appex");
+        let is_framework = path.extension().is_some_and(|e| e == "framework");
+        if !entry.file_type().is_dir() || !(is_bundle || is_framework) {
+            continue;
+        }
+
+        let Some(id) = PlistFile::try_open(bundle_paths(path).0)
+            .and_then(|plist| plist.get_string("CFBundleIdentifier").map(str::to_string))
+        else {
+            continue;
+        };
+        let name = relative(&app.path, path);
+
+        let signed_id = bundle_executable(path)
+            .filter(|exec| exec.is_file())
+            .and_then(|exec| sign::signed_identifier(exec).ok().flatten());
+        if let Some(signed_id) = signed_id.filter(|signed| *signed != id) {
+            issues.push(BundleIssue::SignedIdMismatch {
+                bundle: name.clone(),
+                plist_id: id.clone(),
+                signed_id,
+            });
+        }
+        if is_bundle {
+            ids.entry(id).or_default().push(name);
+        }
+    }
+
+    for (id, bundles) in ids {
+        if bundles.len() > 1 {
+            issues.push(BundleIssue::DuplicateId { id, bundles });
+        }
+    }
+    Ok(issues)
+}
+
+/// `path` relative to the app, or the app's own name for the app
+fn relative(app: &Path, path: &Path) -> String {
+    match path.strip_prefix(app) {
+        Ok(rel) if !rel.as_os_str().is_empty() => rel.to_string_lossy().to_string(),
+        _ => app.file_name().unwrap_or_default().to_string_lossy().to_string(),
+    }
+}
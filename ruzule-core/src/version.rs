@@ -0,0 +1,66 @@
+//! OS versions as used by `MinimumOSVersion` and Mach-O build load commands.
+
+use crate::error::RuzuleError;
+use std::fmt;
+use std::str::FromStr;
+
+/// `major[.minor[.patch]]`, ordered numerically (`9.0 < 10.0`, `15 == 15.0`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// From the `xxxx.yy.zz` nibble encoding of `LC_BUILD_VERSION` and `LC_VERSION_MIN_*`
+    pub fn from_packed(v: u32) -> Self {
+        Self::new(v >> 16, (v >> 8) & 0xff, v & 0xff)
+    }
+
+    pub fn to_packed(self) -> u32 {
+        (self.major << 16) | ((self.minor & 0xff) << 8) | (self.patch & 0xff)
+    }
+}
+
+impl FromStr for Version {
+    type Err = RuzuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RuzuleError::InvalidInput(format!("Invalid OS version: {}", s));
+        let parts = s
+            .split('.')
+            .map(|part| match part.bytes().all(|b| b.is_ascii_digit()) {
+                true => part.parse::<u32>().ok(),
+                false => None,
+            })
+            .collect::<Option<Vec<u32>>>()
+            .ok_or_else(invalid)?;
+
+        match parts[..] {
+            [major] => Ok(Self::new(major, 0, 0)),
+            [major, minor] => Ok(Self::new(major, minor, 0)),
+            [major, minor, patch] => Ok(Self::new(major, minor, patch)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// `major.minor`, with `.patch` only when it isn't 0
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if self.patch != 0 {
+            write!(f, ".{}", self.patch)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+//! File operations used by bundle, ipa and deb handling.
+//!
+//! [`RealFs`] goes to disk; [`MemFs`] keeps everything in memory so injection
+//! logic can be exercised without fixture IPAs. Mach-O editing and signing
+//! still work on real files.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub trait Fs: Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Recursively copy a directory, keeping symlinks as symlinks
+    fn copy_dir(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Remove a file, or a directory and everything in it
+    fn remove(&self, path: &Path) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn exists(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    /// Paths matching a glob pattern (`*`, `?`, `**`)
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>>;
+}
+
+/// The default [`Fs`] handle
+pub fn real() -> Arc<dyn Fs> {
+    Arc::new(RealFs)
+}
+
+/// `std::fs`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+impl Fs for RealFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        fs::write(path, data)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+
+    fn copy_dir(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::create_dir_all(to)?;
+
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            let ty = entry.file_type()?;
+            let src_path = entry.path();
+            let dst_path = to.join(entry.file_name());
+
+            if ty.is_dir() {
+                self.copy_dir(&src_path, &dst_path)?;
+            } else if ty.is_symlink() {
+                let target = fs::read_link(&src_path)?;
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(target, &dst_path)?;
+                #[cfg(windows)]
+                {
+                    if src_path.is_dir() {
+                        std::os::windows::fs::symlink_dir(target, &dst_path)?;
+                    } else {
+                        std::os::windows::fs::symlink_file(target, &dst_path)?;
+                    }
+                }
+                #[cfg(not(any(unix, windows)))]
+                Err::<(), _>(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("can't create symlink {}", target.display()),
+                ))?;
+            } else {
+                fs::copy(&src_path, &dst_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if path.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        let paths = glob::glob(pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        Ok(paths.flatten().collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    File(Vec<u8>),
+    Dir,
+}
+
+/// In-memory [`Fs`]. Paths are used as given, so stick to absolute ones.
+#[derive(Debug, Default)]
+pub struct MemFs {
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+}
+
+impl MemFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, creating its parent directories
+    pub fn with_file<P: Into<PathBuf>, D: Into<Vec<u8>>>(self, path: P, data: D) -> Self {
+        let path = path.into();
+        {
+            let mut nodes = self.lock();
+            if let Some(parent) = path.parent() {
+                insert_dirs(&mut nodes, parent);
+            }
+            nodes.insert(path, Node::File(data.into()));
+        }
+        self
+    }
+
+    /// Every file and directory, sorted
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.lock().keys().cloned().collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Node>> {
+        self.nodes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path.display()))
+}
+
+fn insert_dirs(nodes: &mut BTreeMap<PathBuf, Node>, path: &Path) {
+    for dir in path.ancestors() {
+        if !dir.as_os_str().is_empty() {
+            nodes.entry(dir.to_path_buf()).or_insert(Node::Dir);
+        }
+    }
+}
+
+fn parent_exists(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> bool {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() && parent.parent().is_some() => {
+            matches!(nodes.get(parent), Some(Node::Dir))
+        }
+        _ => true,
+    }
+}
+
+/// `path` and everything below it
+fn subtree(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> Vec<(PathBuf, Node)> {
+    nodes
+        .range(path.to_path_buf()..)
+        .take_while(|(p, _)| p.starts_with(path))
+        .map(|(p, n)| (p.clone(), n.clone()))
+        .collect()
+}
+
+impl Fs for MemFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.lock().get(path) {
+            Some(Node::File(data)) => Ok(data.clone()),
+            Some(Node::Dir) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is a directory", path.display()),
+            )),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut nodes = self.lock();
+        if !parent_exists(&nodes, path) {
+            return Err(not_found(path.parent().unwrap_or(path)));
+        }
+        nodes.insert(path.to_path_buf(), Node::File(data.to_vec()));
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let data = self.read(from)?;
+        self.write(to, &data)
+    }
+
+    fn copy_dir(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut nodes = self.lock();
+        let entries = subtree(&nodes, from);
+        if entries.is_empty() {
+            return Err(not_found(from));
+        }
+        insert_dirs(&mut nodes, to);
+        for (path, node) in entries {
+            let rel = path.strip_prefix(from).expect("subtree is below from");
+            nodes.insert(to.join(rel), node);
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        insert_dirs(&mut self.lock(), path);
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.lock();
+        let entries = subtree(&nodes, path);
+        if entries.is_empty() {
+            return Err(not_found(path));
+        }
+        for (p, _) in entries {
+            nodes.remove(&p);
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut nodes = self.lock();
+        let entries = subtree(&nodes, from);
+        if entries.is_empty() {
+            return Err(not_found(from));
+        }
+        if !parent_exists(&nodes, to) {
+            return Err(not_found(to.parent().unwrap_or(to)));
+        }
+        for (p, _) in subtree(&nodes, to) {
+            nodes.remove(&p);
+        }
+        for (path, node) in entries {
+            nodes.remove(&path);
+            let rel = path.strip_prefix(from).expect("subtree is below from");
+            nodes.insert(to.join(rel), node);
+        }
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.lock().contains_key(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        matches!(self.lock().get(path), Some(Node::Dir))
+    }
+
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        let pattern = glob::Pattern::new(pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        Ok(self
+            .lock()
+            .keys()
+            .filter(|p| pattern.matches_path_with(p, options))
+            .cloned()
+            .collect())
+    }
+}
@@ -1,18 +0,0 @@
-pub mod app_bundle;
-pub mod cyan_config;
-pub mod deb;
-pub mod error;
-pub mod executable;
-pub mod frameworks;
-pub mod ipa;
-pub mod macho;
-pub mod plist_ext;
-pub mod sign;
-
-pub use app_bundle::AppBundle;
-pub use cyan_config::{parse_cyan, CyanConfig, ParsedCyan};
-pub use error::{Result, RuzuleError};
-pub use executable::{Executable, MainExecutable};
-pub use frameworks::{get_framework_for_dep, BundledFramework};
-pub use ipa::{copy_app, create_ipa, extract_ipa};
-pub use plist_ext::PlistFile;